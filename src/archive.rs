@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::sevenz_handler::{self, SevenZIndex};
+use crate::tar_handler::{self, TarCompression, TarIndex};
+use crate::zip_handler::{self, ZipEntry};
+
+/// `::` 区切りのURLでブラウズできるアーカイブの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar(TarCompression),
+    SevenZ,
+}
+
+pub enum ArchiveIndex {
+    Zip(Vec<ZipEntry>),
+    Tar(TarCompression, TarIndex),
+    SevenZ(Box<SevenZIndex>),
+}
+
+impl ArchiveIndex {
+    pub fn entries(&self) -> &[ZipEntry] {
+        match self {
+            ArchiveIndex::Zip(entries) => entries,
+            ArchiveIndex::Tar(_, index) => &index.entries,
+            ArchiveIndex::SevenZ(index) => &index.entries,
+        }
+    }
+}
+
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    if zip_handler::is_zip_file(path) {
+        Some(ArchiveKind::Zip)
+    } else if sevenz_handler::is_sevenz_file(path) {
+        Some(ArchiveKind::SevenZ)
+    } else {
+        tar_handler::tar_compression(path).map(ArchiveKind::Tar)
+    }
+}
+
+pub fn is_archive_file(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+fn build_index(path: &Path) -> Result<ArchiveIndex, String> {
+    match archive_kind(path) {
+        Some(ArchiveKind::Zip) => zip_handler::list_zip_contents(path).map(ArchiveIndex::Zip),
+        Some(ArchiveKind::Tar(compression)) => {
+            tar_handler::build_index(path, compression).map(|index| ArchiveIndex::Tar(compression, index))
+        }
+        Some(ArchiveKind::SevenZ) => sevenz_handler::build_index(path).map(|index| ArchiveIndex::SevenZ(Box::new(index))),
+        None => Err(format!("Not an archive: {}", path.display())),
+    }
+}
+
+/// `password` は暗号化ZIPのエントリにのみ使われる
+pub fn read_file(path: &Path, index: &ArchiveIndex, file_path: &str, password: Option<&str>) -> Result<Vec<u8>, String> {
+    match index {
+        ArchiveIndex::Zip(_) => zip_handler::read_file_from_zip(path, file_path, password),
+        ArchiveIndex::Tar(compression, tar_index) => {
+            tar_handler::read_file_from_tar(path, *compression, tar_index, file_path)
+        }
+        ArchiveIndex::SevenZ(sevenz_index) => sevenz_handler::read_file_from_sevenz(path, sevenz_index, file_path),
+    }
+}
+
+/// キャッシュしておくアーカイブの数（超えたら最後に使ってから一番経ったものを捨てる）
+const MAX_CACHED_ARCHIVES: usize = 32;
+
+struct CachedIndex {
+    modified: Option<SystemTime>,
+    len: u64,
+    index: Arc<ArchiveIndex>,
+    // 最後に使った順番（大きいほど新しい）
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<PathBuf, CachedIndex>,
+    clock: u64,
+}
+
+/// アーカイブのインデックスをパスごとにキャッシュする
+/// ファイルの更新日時かサイズが変わったら作り直す
+pub struct ArchiveIndexCache {
+    inner: Mutex<CacheInner>,
+    capacity: usize,
+}
+
+impl Default for ArchiveIndexCache {
+    fn default() -> Self {
+        Self::with_capacity(MAX_CACHED_ARCHIVES)
+    }
+}
+
+impl ArchiveIndexCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(CacheInner::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> Result<Arc<ArchiveIndex>, String> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to open archive: {}", e))?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.clock += 1;
+            let clock = inner.clock;
+            if let Some(cached) = inner.entries.get_mut(path) {
+                if cached.modified == modified && cached.len == len {
+                    cached.last_used = clock;
+                    return Ok(cached.index.clone());
+                }
+            }
+        }
+
+        let index = Arc::new(build_index(path)?);
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+        inner.entries.insert(
+            path.to_path_buf(),
+            CachedIndex {
+                modified,
+                len,
+                index: index.clone(),
+                last_used: clock,
+            },
+        );
+        while inner.entries.len() > self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(path, _)| path.clone());
+            match oldest {
+                Some(oldest) => inner.entries.remove(&oldest),
+                None => break,
+            };
+        }
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_kind() {
+        assert_eq!(archive_kind(Path::new("a.zip")), Some(ArchiveKind::Zip));
+        assert_eq!(archive_kind(Path::new("a.tar.gz")), Some(ArchiveKind::Tar(TarCompression::Gzip)));
+        assert_eq!(archive_kind(Path::new("a.7z")), Some(ArchiveKind::SevenZ));
+        assert_eq!(archive_kind(Path::new("a.txt")), None);
+        assert!(is_archive_file(Path::new("a.tzst")));
+    }
+
+    #[test]
+    fn test_cache_reuses_index() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_archive_cache");
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let tar_path = temp_dir.join("test.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_cksum();
+        builder.append_data(&mut header, "a.txt", &b"hello"[..]).unwrap();
+        std::fs::write(&tar_path, builder.into_inner().unwrap()).unwrap();
+
+        let cache = ArchiveIndexCache::new();
+        let first = cache.get(&tar_path).unwrap();
+        let second = cache.get(&tar_path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(read_file(&tar_path, &first, "a.txt", None).unwrap(), b"hello");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_archive_cache_lru");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let paths: Vec<PathBuf> = ["a.tar", "b.tar", "c.tar"].iter().map(|name| temp_dir.join(name)).collect();
+        for path in &paths {
+            let mut builder = tar::Builder::new(Vec::new());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(1);
+            header.set_cksum();
+            builder.append_data(&mut header, "x.txt", &b"x"[..]).unwrap();
+            std::fs::write(path, builder.into_inner().unwrap()).unwrap();
+        }
+
+        let cache = ArchiveIndexCache::with_capacity(2);
+        let a = cache.get(&paths[0]).unwrap();
+        cache.get(&paths[1]).unwrap();
+        // a を使い直したので、c を入れると b が捨てられる
+        assert!(Arc::ptr_eq(&a, &cache.get(&paths[0]).unwrap()));
+        cache.get(&paths[2]).unwrap();
+        assert_eq!(cache.inner.lock().unwrap().entries.len(), 2);
+        assert!(Arc::ptr_eq(&a, &cache.get(&paths[0]).unwrap()));
+        assert!(cache.inner.lock().unwrap().entries.contains_key(&paths[2]));
+        assert!(!cache.inner.lock().unwrap().entries.contains_key(&paths[1]));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+}
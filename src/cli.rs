@@ -13,6 +13,30 @@ pub struct Args {
     #[arg(long, default_value = "0.0.0.0")]
     pub host: String,
 
+    /// Directory to cache generated thumbnails (default: <tmp>/mvu_thumbnails)
+    #[arg(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Directory to persist reading positions (default: $XDG_STATE_HOME/mvu or ~/.local/state/mvu)
+    #[arg(long)]
+    pub state_dir: Option<PathBuf>,
+
+    /// Sort names case-insensitively (numbers are always compared by value)
+    #[arg(long)]
+    pub sort_ignore_case: bool,
+
+    /// Markdown files rendered below directory listings (first match wins, case-insensitive)
+    #[arg(long, value_delimiter = ',', default_value = "README.md,index.md")]
+    pub readme_names: Vec<String>,
+
+    /// Serve index.md as the directory page instead of the listing (`?list=1` shows the listing)
+    #[arg(long)]
+    pub index_as_page: bool,
+
+    /// Deepest heading level shown in the table of contents (1-6)
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u8).range(1..=6))]
+    pub toc_depth: u8,
+
     /// Directory to serve
     #[arg(default_value = ".")]
     pub directory: PathBuf,
@@ -24,33 +48,71 @@ mod tests {
 
     #[test]
     fn test_default_args() {
-        let args = Args::try_parse_from(&["mvu"]).unwrap();
+        let args = Args::try_parse_from(["mvu"]).unwrap();
         assert_eq!(args.port, 8080);
         assert_eq!(args.host, "0.0.0.0");
         assert_eq!(args.directory, PathBuf::from("."));
+        assert_eq!(args.cache_dir, None);
+        assert_eq!(args.state_dir, None);
+        assert!(!args.sort_ignore_case);
+        assert_eq!(args.readme_names, vec!["README.md", "index.md"]);
+        assert!(!args.index_as_page);
+        assert_eq!(args.toc_depth, 6);
+    }
+
+    #[test]
+    fn test_custom_cache_dir() {
+        let args = Args::try_parse_from(["mvu", "--cache-dir", "/var/cache/mvu"]).unwrap();
+        assert_eq!(args.cache_dir, Some(PathBuf::from("/var/cache/mvu")));
+    }
+
+    #[test]
+    fn test_custom_state_dir() {
+        let args = Args::try_parse_from(["mvu", "--state-dir", "/var/lib/mvu"]).unwrap();
+        assert_eq!(args.state_dir, Some(PathBuf::from("/var/lib/mvu")));
+    }
+
+    #[test]
+    fn test_sort_ignore_case() {
+        let args = Args::try_parse_from(["mvu", "--sort-ignore-case"]).unwrap();
+        assert!(args.sort_ignore_case);
+    }
+
+    #[test]
+    fn test_readme_options() {
+        let args = Args::try_parse_from(["mvu", "--readme-names", "README.md,about.md", "--index-as-page"]).unwrap();
+        assert_eq!(args.readme_names, vec!["README.md", "about.md"]);
+        assert!(args.index_as_page);
+    }
+
+    #[test]
+    fn test_toc_depth() {
+        let args = Args::try_parse_from(["mvu", "--toc-depth", "3"]).unwrap();
+        assert_eq!(args.toc_depth, 3);
+        assert!(Args::try_parse_from(["mvu", "--toc-depth", "7"]).is_err());
     }
 
     #[test]
     fn test_custom_port() {
-        let args = Args::try_parse_from(&["mvu", "--port", "3000"]).unwrap();
+        let args = Args::try_parse_from(["mvu", "--port", "3000"]).unwrap();
         assert_eq!(args.port, 3000);
     }
 
     #[test]
     fn test_custom_host() {
-        let args = Args::try_parse_from(&["mvu", "--host", "127.0.0.1"]).unwrap();
+        let args = Args::try_parse_from(["mvu", "--host", "127.0.0.1"]).unwrap();
         assert_eq!(args.host, "127.0.0.1");
     }
 
     #[test]
     fn test_custom_directory() {
-        let args = Args::try_parse_from(&["mvu", "/tmp"]).unwrap();
+        let args = Args::try_parse_from(["mvu", "/tmp"]).unwrap();
         assert_eq!(args.directory, PathBuf::from("/tmp"));
     }
 
     #[test]
     fn test_all_custom_args() {
-        let args = Args::try_parse_from(&["mvu", "--port", "9090", "--host", "localhost", "/var/www"]).unwrap();
+        let args = Args::try_parse_from(["mvu", "--port", "9090", "--host", "localhost", "/var/www"]).unwrap();
         assert_eq!(args.port, 9090);
         assert_eq!(args.host, "localhost");
         assert_eq!(args.directory, PathBuf::from("/var/www"));
@@ -0,0 +1,245 @@
+use std::path::{Path, PathBuf};
+
+use crate::file_index::is_ignored;
+use crate::front_matter::{self, FrontMatter};
+use crate::heading::Heading;
+use crate::obsidian;
+use crate::zip_handler;
+
+/// これより大きいファイル（ZIP内のエントリも）は索引に入れない
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// 1つのZIPから索引に入れるテキストの合計の上限
+pub const MAX_ARCHIVE_TEXT_SIZE: u64 = 16 * 1024 * 1024;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "mkd", "markdown"];
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "rst", "adoc", "org", "csv", "log", "rs", "py", "js", "mjs", "ts", "tsx", "jsx", "go", "c", "h", "cc", "cpp",
+    "hpp", "java", "kt", "rb", "php", "swift", "cs", "sh", "bash", "zsh", "fish", "ps1", "lua", "sql", "toml", "yaml",
+    "yml", "json", "xml", "ini", "cfg", "conf", "css", "scss", "html", "htm", "vue", "svelte",
+];
+
+fn extension(name: &str) -> Option<String> {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => Some(ext.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+pub fn is_markdown(name: &str) -> bool {
+    extension(name).is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Markdown・テキスト・ソースファイルか（拡張子で判定）
+pub fn is_indexable(name: &str) -> bool {
+    extension(name).is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext.as_str()) || TEXT_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// 索引に入れるファイル（全文検索・タグ・リンク元の索引で共有し、1ファイル1回だけ読む）
+pub struct Document {
+    // base_dir からの相対パス（ZIP内のエントリは `a.zip::dir/file.md`）
+    pub relative: String,
+    // Markdownは front matter を除いた本文
+    pub text: String,
+    pub markdown: Option<Markdown>,
+}
+
+/// Markdownから取り出しておくもの
+pub struct Markdown {
+    pub front_matter: FrontMatter,
+    // idはレンダリング結果と同じ（ウィキリンクを書き換えた後のテキストから作る）
+    pub headings: Vec<Heading>,
+    // front matter のタイトル、なければ最初のh1、なければファイル名
+    pub title: String,
+}
+
+impl Document {
+    fn new(relative: String, content: String) -> Self {
+        if !is_markdown(&relative) {
+            return Self { relative, text: content, markdown: None };
+        }
+        let (front_matter, body) = front_matter::split(&content);
+        let front_matter = front_matter.unwrap_or_default();
+        let headings = obsidian::headings(body);
+        let title = front_matter
+            .title
+            .clone()
+            .or_else(|| headings.iter().find(|h| h.level == 1).map(|h| h.text.clone()))
+            .unwrap_or_else(|| relative.rsplit(['/', ':']).next().unwrap_or(&relative).to_string());
+        let text = body.to_string();
+        Self {
+            relative,
+            text,
+            markdown: Some(Markdown { front_matter, headings, title }),
+        }
+    }
+
+    /// アーカイブの中ではないMarkdown（タグとリンク元の索引に入れるもの）
+    pub fn tree_markdown(&self) -> Option<&Markdown> {
+        self.markdown.as_ref().filter(|_| !self.relative.contains("::"))
+    }
+}
+
+/// watcher で変更があったパスを読み直したもの
+pub struct Changes {
+    // 索引から消すパス（このパス自身とその下）
+    pub removed: Vec<String>,
+    // 読み直した文書
+    pub documents: Vec<Document>,
+}
+
+/// ツリー全体の文書を読む
+pub fn collect(base_dir: &Path) -> Vec<Document> {
+    let mut documents = Vec::new();
+    collect_dir(base_dir, base_dir, &mut documents);
+    documents
+}
+
+/// 変更のあったパス（ディレクトリならその下すべて）を読み直す
+pub fn read_changes(base_dir: &Path, changed: &[PathBuf]) -> Changes {
+    let mut changes = Changes { removed: Vec::new(), documents: Vec::new() };
+    for path in changed {
+        let relative = match path.strip_prefix(base_dir).ok().and_then(|p| p.to_str()) {
+            Some(relative) if !relative.is_empty() => relative.to_string(),
+            _ => continue,
+        };
+        if path.is_dir() {
+            collect_dir(base_dir, path, &mut changes.documents);
+        } else {
+            read_file(base_dir, path, &relative, &mut changes.documents);
+        }
+        changes.removed.push(relative);
+    }
+    changes
+}
+
+fn collect_dir(base_dir: &Path, dir: &Path, documents: &mut Vec<Document>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if is_ignored(base_dir, &path) {
+            continue;
+        }
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            collect_dir(base_dir, &path, documents);
+        } else if let Some(relative) = path.strip_prefix(base_dir).ok().and_then(|p| p.to_str()) {
+            read_file(base_dir, &path, relative, documents);
+        }
+    }
+}
+
+fn read_file(base_dir: &Path, path: &Path, relative: &str, documents: &mut Vec<Document>) {
+    if is_ignored(base_dir, path) {
+        return;
+    }
+    if zip_handler::is_zip_file(path) {
+        read_zip(path, relative, documents);
+        return;
+    }
+    if !is_indexable(relative) {
+        return;
+    }
+    let too_large = std::fs::metadata(path).map(|m| m.len() > MAX_FILE_SIZE).unwrap_or(true);
+    if too_large {
+        return;
+    }
+    // UTF-8 でないファイル（バイナリなど）は飛ばす
+    if let Ok(content) = std::fs::read_to_string(path) {
+        documents.push(Document::new(relative.to_string(), content));
+    }
+}
+
+/// ZIP内のテキストのエントリを `a.zip::dir/file.md` として読む
+/// 暗号化されたエントリや大きすぎるエントリは飛ばし、合計が上限に達したらそこで打ち切る
+fn read_zip(path: &Path, relative: &str, documents: &mut Vec<Document>) {
+    let entries = match zip_handler::list_zip_contents(path) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut archive = match zip_handler::open_zip(path) {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+
+    let mut total = 0;
+    for entry in entries {
+        if entry.is_dir || entry.encrypted || entry.size > MAX_FILE_SIZE || !is_indexable(&entry.name) {
+            continue;
+        }
+        total += entry.size;
+        if total > MAX_ARCHIVE_TEXT_SIZE {
+            break;
+        }
+        let content = zip_handler::extract_file_from_zip(&mut archive, &entry.name, None)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+        if let Some(content) = content {
+            documents.push(Document::new(format!("{}::{}", relative, entry.name), content));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_and_read_changes() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_documents");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(temp_dir.join("notes")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("notes/a.md"), "---\ntitle: Front\n---\n# Heading\n").unwrap();
+        std::fs::write(temp_dir.join("notes/b.md"), "# Heading B\n\n## Sub\n").unwrap();
+        std::fs::write(temp_dir.join("notes/c.txt"), "plain").unwrap();
+        std::fs::write(temp_dir.join("image.png"), "not text").unwrap();
+
+        let mut documents = collect(&temp_dir);
+        documents.sort_by(|a, b| a.relative.cmp(&b.relative));
+        let paths: Vec<_> = documents.iter().map(|d| d.relative.as_str()).collect();
+        assert_eq!(paths, vec!["notes/a.md", "notes/b.md", "notes/c.txt"]);
+
+        let a = documents[0].markdown.as_ref().unwrap();
+        assert_eq!(a.title, "Front");
+        assert_eq!(documents[0].text, "# Heading\n");
+        let b = documents[1].markdown.as_ref().unwrap();
+        assert_eq!(b.title, "Heading B");
+        assert_eq!(b.headings.len(), 2);
+        assert!(documents[2].markdown.is_none());
+
+        std::fs::remove_file(temp_dir.join("notes/a.md")).unwrap();
+        let changes = read_changes(&temp_dir, &[temp_dir.join("notes/a.md"), temp_dir.join("notes")]);
+        assert_eq!(changes.removed, vec!["notes/a.md", "notes"]);
+        assert_eq!(changes.documents.len(), 2);
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_base_dir_inside_ignored_directory() {
+        let root = std::env::temp_dir().join("mvu_test_documents_venv");
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("venv/notes/node_modules")).unwrap();
+        let temp_dir = root.join("venv/notes").canonicalize().unwrap();
+        std::fs::write(temp_dir.join("a.md"), "# A\n").unwrap();
+        std::fs::write(temp_dir.join("node_modules/b.md"), "# B\n").unwrap();
+
+        let documents = collect(&temp_dir);
+        let paths: Vec<_> = documents.iter().map(|d| d.relative.as_str()).collect();
+        assert_eq!(paths, vec!["a.md"]);
+        let changes = read_changes(&temp_dir, &[temp_dir.join("a.md")]);
+        assert_eq!(changes.documents.len(), 1);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,265 @@
+use axum::body::Bytes;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// ZIPに詰めるエントリ（ディレクトリは末尾 `/`）
+pub struct DownloadEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// `dir` 以下（`selection` があればその直下の名前だけ）を再帰的に集める
+/// base_dir 外を指すシンボリックリンクは含めない
+/// ディレクトリを辿って集めるときは `.git` などの隠しファイル（`.` で始まる名前）を飛ばすが、
+/// 一覧には出ているので、チェックボックスで明示的に選んだものはそのまま含める
+pub fn collect_entries(base_dir: &Path, dir: &Path, selection: Option<&[String]>) -> Result<Vec<DownloadEntry>, String> {
+    let mut roots = Vec::new();
+
+    match selection {
+        Some(names) => {
+            for name in names {
+                if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+                    return Err(format!("Invalid entry name: {}", name));
+                }
+                roots.push((dir.join(name), name.clone()));
+            }
+        }
+        None => {
+            for entry in read_dir_sorted(dir)? {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if is_hidden(&name) {
+                    continue;
+                }
+                roots.push((entry.path(), name));
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut visited: HashSet<PathBuf> = dir.canonicalize().into_iter().collect();
+    for (path, name) in roots {
+        collect_recursive(base_dir, &path, name, &mut visited, &mut entries)?;
+    }
+
+    Ok(entries)
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<fs::DirEntry>, String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .collect();
+    entries.sort_by_key(|e| e.file_name());
+    Ok(entries)
+}
+
+/// `visited` は辿ったディレクトリの実パス（シンボリックリンクの循環で無限に潜らないように）
+fn collect_recursive(
+    base_dir: &Path,
+    path: &Path,
+    name: String,
+    visited: &mut HashSet<PathBuf>,
+    entries: &mut Vec<DownloadEntry>,
+) -> Result<(), String> {
+    let canonical = match path.canonicalize() {
+        Ok(p) => p,
+        Err(_) => return Ok(()),
+    };
+
+    if !canonical.starts_with(base_dir) {
+        return Ok(());
+    }
+
+    if canonical.is_dir() {
+        if !visited.insert(canonical.clone()) {
+            return Ok(());
+        }
+        entries.push(DownloadEntry {
+            path: canonical.clone(),
+            name: format!("{}/", name),
+            is_dir: true,
+        });
+        for child in read_dir_sorted(&canonical)? {
+            if is_hidden(&child.file_name().to_string_lossy()) {
+                continue;
+            }
+            let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+            collect_recursive(base_dir, &child.path(), child_name, visited, entries)?;
+        }
+    } else {
+        entries.push(DownloadEntry {
+            path: canonical,
+            name,
+            is_dir: false,
+        });
+    }
+
+    Ok(())
+}
+
+/// 既に圧縮済みの形式は無圧縮で格納する
+fn compression_for(name: &str) -> CompressionMethod {
+    let ext = name.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "zip" | "cbz" | "gz" | "tgz" | "zst" | "7z" | "mp3" | "mp4" | "webm" => {
+            CompressionMethod::Stored
+        }
+        _ => CompressionMethod::Deflated,
+    }
+}
+
+/// エントリをストリーミング形式のZIPとして書き出す（シーク不要）
+pub fn write_zip<W: Write>(entries: &[DownloadEntry], writer: W) -> Result<(), String> {
+    let mut zip = ZipWriter::new_stream(writer);
+
+    for entry in entries {
+        let metadata = fs::metadata(&entry.path)
+            .map_err(|e| format!("Failed to read {}: {}", entry.path.display(), e))?;
+
+        let mut options = SimpleFileOptions::default();
+        if let Some(modified) = metadata
+            .modified()
+            .ok()
+            .map(time::OffsetDateTime::from)
+            .and_then(|t| zip::DateTime::try_from(time::PrimitiveDateTime::new(t.date(), t.time())).ok())
+        {
+            options = options.last_modified_time(modified);
+        }
+
+        if entry.is_dir {
+            zip.add_directory(entry.name.as_str(), options)
+                .map_err(|e| format!("Failed to write zip: {}", e))?;
+            continue;
+        }
+
+        let options = options
+            .compression_method(compression_for(&entry.name))
+            .large_file(metadata.len() >= u32::MAX as u64);
+        zip.start_file(entry.name.as_str(), options)
+            .map_err(|e| format!("Failed to write zip: {}", e))?;
+
+        let mut file = fs::File::open(&entry.path)
+            .map_err(|e| format!("Failed to open {}: {}", entry.path.display(), e))?;
+        io::copy(&mut file, &mut zip)
+            .map_err(|e| format!("Failed to write zip: {}", e))?;
+    }
+
+    zip.finish()
+        .map_err(|e| format!("Failed to write zip: {}", e))?
+        .flush()
+        .map_err(|e| format!("Failed to write zip: {}", e))
+}
+
+/// 書き込まれたバイト列をレスポンスボディ用のチャンネルに流すWriter
+struct ChannelWriter {
+    tx: mpsc::Sender<Result<Bytes, io::Error>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// ZIPを別スレッドで組み立てながらチャンクごとに返す
+/// アーカイブ全体をメモリに載せないため、チャンネルは小さく保つ
+pub fn zip_stream(entries: Vec<DownloadEntry>) -> ReceiverStream<Result<Bytes, io::Error>> {
+    let (tx, rx) = mpsc::channel(8);
+
+    tokio::task::spawn_blocking(move || {
+        let writer = BufWriter::with_capacity(64 * 1024, ChannelWriter { tx: tx.clone() });
+        if let Err(e) = write_zip(&entries, writer) {
+            eprintln!("[download] {}", e);
+            let _ = tx.blocking_send(Err(io::Error::other(e)));
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    fn setup(name: &str) -> PathBuf {
+        let temp_dir = std::env::temp_dir().join(name);
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(temp_dir.join("docs/sub")).unwrap();
+        fs::create_dir_all(temp_dir.join("docs/.git")).unwrap();
+        fs::write(temp_dir.join("docs/a.md"), "# A").unwrap();
+        fs::write(temp_dir.join("docs/sub/b.txt"), "bbb").unwrap();
+        fs::write(temp_dir.join("docs/.git/config"), "secret").unwrap();
+        fs::write(temp_dir.join("docs/Cargo.lock"), "lock").unwrap();
+        temp_dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_collect_and_write_directory() {
+        let base = setup("mvu_test_download_dir");
+        let entries = collect_entries(&base, &base.join("docs"), None).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Cargo.lock", "a.md", "sub/", "sub/b.txt"]);
+
+        let mut buf = Vec::new();
+        write_zip(&entries, &mut buf).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(buf)).unwrap();
+        let mut contents = String::new();
+        archive.by_name("sub/b.txt").unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "bbb");
+
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_collect_selection() {
+        let base = setup("mvu_test_download_selection");
+        let docs = base.join("docs");
+
+        let entries = collect_entries(&base, &docs, Some(&["a.md".to_string()])).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["a.md"]);
+
+        assert!(collect_entries(&base, &docs, Some(&["../docs".to_string()])).is_err());
+
+        // 一覧に出ている隠しファイルも、選べばダウンロードできる
+        fs::write(docs.join(".env"), "KEY=1").unwrap();
+        let entries = collect_entries(&base, &docs, Some(&[".env".to_string(), ".git".to_string()])).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec![".env", ".git/", ".git/config"]);
+
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_symlink_cycle() {
+        let base = setup("mvu_test_download_cycle");
+        std::os::unix::fs::symlink(base.join("docs"), base.join("docs/sub/loop")).unwrap();
+
+        let entries = collect_entries(&base, &base.join("docs"), None).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Cargo.lock", "a.md", "sub/", "sub/b.txt"]);
+
+        fs::remove_dir_all(&base).ok();
+    }
+}
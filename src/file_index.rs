@@ -0,0 +1,299 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::watcher::should_ignore_path;
+use crate::zip_handler;
+
+/// パレットに返す最大件数
+pub const MAX_RESULTS: usize = 50;
+
+/// ツリー全体のパスの索引（「ファイルへ移動」パレット用）
+/// パスは base_dir からの相対パスで、ディレクトリは末尾に `/`、ZIP内のファイルは `a.zip::dir/file`
+pub struct FileIndex {
+    base_dir: PathBuf,
+    paths: RwLock<BTreeSet<String>>,
+    ready: AtomicBool,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileMatch {
+    pub path: String,
+    pub score: i64,
+    // 一致した文字の位置（文字単位、ハイライト用）
+    pub positions: Vec<usize>,
+}
+
+impl FileIndex {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            paths: RwLock::new(BTreeSet::new()),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// ツリー全体を走査して作り直す（起動時にバックグラウンドで呼ぶ）
+    pub fn build(&self) {
+        let mut paths = BTreeSet::new();
+        collect(&self.base_dir, &self.base_dir, &mut paths);
+        *self.paths.write().unwrap() = paths;
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.read().unwrap().len()
+    }
+
+    /// watcher で変更があったパスを反映する（削除されたものは消し、あるものは読み直す）
+    pub fn update(&self, changed: &[PathBuf]) {
+        let relatives: Vec<(String, &PathBuf)> = changed
+            .iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(&self.base_dir).ok()?;
+                let relative = relative.to_str()?.to_string();
+                (!relative.is_empty()).then_some((relative, path))
+            })
+            .collect();
+        if relatives.is_empty() {
+            return;
+        }
+
+        let mut added = BTreeSet::new();
+        for (_, path) in &relatives {
+            if is_ignored(&self.base_dir, path) {
+                continue;
+            }
+            if let Ok(metadata) = std::fs::metadata(path) {
+                add_path(&self.base_dir, path, metadata.is_dir(), &mut added);
+                if metadata.is_dir() {
+                    collect(&self.base_dir, path, &mut added);
+                }
+            }
+        }
+
+        let mut paths = self.paths.write().unwrap();
+        paths.retain(|path| !relatives.iter().any(|(relative, _)| is_under(path, relative)));
+        paths.extend(added);
+    }
+
+    /// パスの末尾が `name` と一致するファイル（大文字小文字は区別しない、ウィキリンクの解決用）
+    /// ディレクトリとアーカイブ内のエントリは含めない
+    pub fn find_by_suffix(&self, name: &str) -> Vec<String> {
+        let name = name.to_lowercase();
+        let suffix = format!("/{}", name);
+        self.paths
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|path| !path.ends_with('/') && !path.contains("::"))
+            .filter(|path| {
+                let lower = path.to_lowercase();
+                lower == name || lower.ends_with(&suffix)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// パス全体に対するあいまい検索（スコアの高い順）
+    pub fn search(&self, query: &str, limit: usize) -> Vec<FileMatch> {
+        let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+        let paths = self.paths.read().unwrap();
+
+        let mut matches: Vec<FileMatch> = paths
+            .iter()
+            .filter_map(|path| {
+                fuzzy_match(path, &query).map(|(score, positions)| FileMatch {
+                    path: path.clone(),
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.len().cmp(&b.path.len())).then_with(|| a.path.cmp(&b.path)));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// 除外するパスか（base_dir からの相対パスで判定し、`target/doc` のような場所で起動しても全部を除外しないようにする）
+pub fn is_ignored(base_dir: &Path, path: &Path) -> bool {
+    match path.strip_prefix(base_dir) {
+        Ok(relative) => should_ignore_path(&Path::new("/").join(relative)),
+        Err(_) => should_ignore_path(path),
+    }
+}
+
+/// `path` が `relative` 自身か、その下（ディレクトリ・アーカイブ内）か
+pub fn is_under(path: &str, relative: &str) -> bool {
+    match path.strip_prefix(relative) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+fn add_path(base_dir: &Path, path: &Path, is_dir: bool, paths: &mut BTreeSet<String>) {
+    let relative = match path.strip_prefix(base_dir).ok().and_then(|p| p.to_str()) {
+        Some(relative) if !relative.is_empty() => relative.to_string(),
+        _ => return,
+    };
+
+    if is_dir {
+        paths.insert(format!("{}/", relative));
+        return;
+    }
+
+    // ZIPはエントリも索引に入れる（中央ディレクトリを読むだけなので安い）
+    if zip_handler::is_zip_file(path) {
+        if let Ok(entries) = zip_handler::list_zip_contents(path) {
+            paths.extend(
+                entries
+                    .into_iter()
+                    .filter(|entry| !entry.is_dir)
+                    .map(|entry| format!("{}::{}", relative, entry.name)),
+            );
+        }
+    }
+    paths.insert(relative);
+}
+
+fn collect(base_dir: &Path, dir: &Path, paths: &mut BTreeSet<String>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if is_ignored(base_dir, &path) {
+            continue;
+        }
+        // シンボリックリンクのディレクトリはたどらない（循環を避ける）
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        add_path(base_dir, &path, is_dir, paths);
+        if is_dir {
+            collect(base_dir, &path, paths);
+        }
+    }
+}
+
+/// クエリの文字が順にすべて含まれていれば (スコア, 一致位置) を返す
+/// 連続した一致、区切り文字の直後、ファイル名部分での一致を高く評価する
+fn fuzzy_match(path: &str, query: &[char]) -> Option<(i64, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let chars: Vec<char> = path.chars().collect();
+    let file_name_start = path
+        .trim_end_matches('/')
+        .rfind(['/', ':'])
+        .map(|i| path[..=i].chars().count())
+        .unwrap_or(0);
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut score = 0i64;
+    let mut next = 0;
+    for &q in query {
+        let found = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(std::iter::once(q)))?;
+        score += 1;
+        if positions.last().is_some_and(|&last| last + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(chars[found - 1], '/' | ':' | '_' | '-' | '.' | ' ') {
+            score += 4;
+        }
+        if found >= file_name_start {
+            score += 2;
+        }
+        positions.push(found);
+        next = found + 1;
+    }
+
+    // 短いパスを優先する
+    score -= (chars.len() / 8) as i64;
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_fuzzy_match() {
+        let query: Vec<char> = "rdme".chars().collect();
+        let (_, positions) = fuzzy_match("docs/README.md", &query).unwrap();
+        assert_eq!(positions, vec![5, 8, 9, 10]);
+        assert!(fuzzy_match("docs/guide.md", &query).is_none());
+
+        // ファイル名の先頭で連続して一致するほうが上
+        let query: Vec<char> = "guide".chars().collect();
+        let exact = fuzzy_match("docs/guide.md", &query).unwrap().0;
+        let scattered = fuzzy_match("go/user/interface/design.md", &query).unwrap().0;
+        assert!(exact > scattered);
+    }
+
+    #[test]
+    fn test_build_update_and_search() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_file_index");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(temp_dir.join("docs/deep/deeper/deepest")).unwrap();
+        std::fs::create_dir_all(temp_dir.join(".git")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("docs/deep/deeper/deepest/setup.md"), "# Setup").unwrap();
+        std::fs::write(temp_dir.join(".git/config"), "").unwrap();
+
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(temp_dir.join("release.zip")).unwrap());
+        writer.start_file("notes/changes.md", SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"# Changes").unwrap();
+        writer.finish().unwrap();
+
+        let index = FileIndex::new(temp_dir.clone());
+        index.build();
+
+        let results = index.search("setup", MAX_RESULTS);
+        assert_eq!(results[0].path, "docs/deep/deeper/deepest/setup.md");
+        assert_eq!(index.search("changes", MAX_RESULTS)[0].path, "release.zip::notes/changes.md");
+        assert!(index.search("config", MAX_RESULTS).is_empty());
+
+        // 追加・削除を反映する
+        std::fs::write(temp_dir.join("docs/install.md"), "# Install").unwrap();
+        std::fs::remove_dir_all(temp_dir.join("docs/deep")).unwrap();
+        index.update(&[temp_dir.join("docs/install.md"), temp_dir.join("docs/deep")]);
+        assert_eq!(index.search("install", MAX_RESULTS)[0].path, "docs/install.md");
+        assert!(index.search("setup", MAX_RESULTS).is_empty());
+        assert!(index.search("deep", MAX_RESULTS).is_empty());
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_base_dir_inside_ignored_directory() {
+        let root = std::env::temp_dir().join("mvu_test_file_index_target");
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("target/doc/target")).unwrap();
+        let temp_dir = root.join("target/doc").canonicalize().unwrap();
+        std::fs::write(temp_dir.join("index.md"), "# Doc").unwrap();
+        std::fs::write(temp_dir.join("target/build.md"), "").unwrap();
+
+        // base_dir より上の `target` では除外せず、下の `target` だけ除外する
+        let index = FileIndex::new(temp_dir.clone());
+        index.build();
+        assert_eq!(index.search("index", MAX_RESULTS)[0].path, "index.md");
+        assert!(index.search("build", MAX_RESULTS).is_empty());
+
+        std::fs::write(temp_dir.join("new.md"), "").unwrap();
+        index.update(&[temp_dir.join("new.md")]);
+        assert_eq!(index.search("new", MAX_RESULTS)[0].path, "new.md");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,241 @@
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::markdown::html_escape;
+use crate::tag_index::tag_url;
+
+/// Markdown先頭のfront matter（`---` で囲んだYAML、`+++` で囲んだTOML）
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub date: Option<String>,
+    pub draft: bool,
+    // それ以外の項目（表示用に文字列にしたもの）
+    pub extra: Vec<(String, String)>,
+}
+
+/// front matter を取り出し、残りの本文と一緒に返す（なければ本文はそのまま）
+pub fn split(source: &str) -> (Option<FrontMatter>, &str) {
+    let text = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let (delimiter, is_toml) = if text.starts_with("---") {
+        ("---", false)
+    } else if text.starts_with("+++") {
+        ("+++", true)
+    } else {
+        return (None, source);
+    };
+
+    let mut lines = text.split_inclusive('\n');
+    if lines.next().map(|line| line.trim_end()) != Some(delimiter) {
+        return (None, source);
+    }
+    let mut offset = delimiter.len() + text[delimiter.len()..].find('\n').map(|i| i + 1).unwrap_or(0);
+    let start = offset;
+    for line in lines {
+        let trimmed = line.trim_end();
+        if trimmed == delimiter || (!is_toml && trimmed == "...") {
+            let raw = &text[start..offset];
+            let body = &text[offset + line.len()..];
+            let parsed = if is_toml { parse_toml(raw) } else { parse_yaml(raw) };
+            // 中身が読めなければ front matter ではない（水平線とSetext見出しなど）
+            return match parsed {
+                Some(front_matter) => (Some(front_matter), body),
+                None => (None, source),
+            };
+        }
+        offset += line.len();
+    }
+    (None, source)
+}
+
+impl FrontMatter {
+    /// `items` は値が配列のときの要素（配列でなければ None）
+    fn set(&mut self, key: &str, items: Option<Vec<String>>, text: String) {
+        match key.to_ascii_lowercase().as_str() {
+            "title" => self.title = Some(text).filter(|t| !t.is_empty()),
+            "tags" | "tag" => {
+                // 配列の要素は空白を含んでも1つのタグ、文字列はカンマで区切る
+                let tags = match items {
+                    Some(items) => items,
+                    None => text.split(',').map(str::to_string).collect(),
+                };
+                self.tags = tags
+                    .iter()
+                    .map(|tag| tag.trim().trim_start_matches('#').to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+            }
+            "date" => self.date = Some(text).filter(|d| !d.is_empty()),
+            "draft" => self.draft = text == "true",
+            _ => self.extra.push((key.to_string(), text)),
+        }
+    }
+
+    /// ページの先頭に出すメタデータのHTML（`show_title` は本文にh1がないとき）
+    pub fn header_html(&self, show_title: bool) -> String {
+        let mut html = String::from("<div class=\"front-matter\">");
+        if let Some(title) = self.title.as_ref().filter(|_| show_title) {
+            html.push_str(&format!("<div class=\"front-matter-title\">{}</div>", html_escape(title)));
+        }
+
+        let mut meta = Vec::new();
+        if self.draft {
+            meta.push("<span class=\"front-matter-draft\">下書き</span>".to_string());
+        }
+        if let Some(date) = &self.date {
+            meta.push(format!("<span class=\"front-matter-date\">📅 {}</span>", html_escape(date)));
+        }
+        for tag in &self.tags {
+            meta.push(format!(
+                "<a class=\"front-matter-tag\" href=\"{}\">#{}</a>",
+                html_escape(&tag_url(tag)),
+                html_escape(tag)
+            ));
+        }
+        if !meta.is_empty() {
+            html.push_str(&format!("<div class=\"front-matter-meta\">{}</div>", meta.join("")));
+        }
+
+        if !self.extra.is_empty() {
+            html.push_str("<dl class=\"front-matter-fields\">");
+            for (key, value) in &self.extra {
+                html.push_str(&format!("<dt>{}</dt><dd>{}</dd>", html_escape(key), html_escape(value)));
+            }
+            html.push_str("</dl>");
+        }
+        html.push_str("</div>");
+        html
+    }
+}
+
+fn parse_yaml(raw: &str) -> Option<FrontMatter> {
+    let documents = YamlLoader::load_from_str(raw).ok()?;
+    let mut front_matter = FrontMatter::default();
+    match documents.into_iter().next() {
+        Some(Yaml::Hash(hash)) => {
+            for (key, value) in hash {
+                let key = yaml_text(&key);
+                let items: Option<Vec<String>> = match &value {
+                    Yaml::Array(items) => Some(items.iter().map(yaml_text).collect()),
+                    _ => None,
+                };
+                front_matter.set(&key, items, yaml_text(&value));
+            }
+            Some(front_matter)
+        }
+        // 空の front matter
+        None | Some(Yaml::Null) => Some(front_matter),
+        Some(_) => None,
+    }
+}
+
+fn yaml_text(value: &Yaml) -> String {
+    match value {
+        Yaml::Real(s) | Yaml::String(s) => s.clone(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        Yaml::Array(items) => items.iter().map(yaml_text).collect::<Vec<_>>().join(", "),
+        Yaml::Hash(hash) => hash
+            .iter()
+            .map(|(key, value)| format!("{}: {}", yaml_text(key), yaml_text(value)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => String::new(),
+    }
+}
+
+fn parse_toml(raw: &str) -> Option<FrontMatter> {
+    let table: toml::Table = raw.parse().ok()?;
+    let mut front_matter = FrontMatter::default();
+    for (key, value) in table {
+        let items: Option<Vec<String>> = match &value {
+            toml::Value::Array(items) => Some(items.iter().map(toml_text).collect()),
+            _ => None,
+        };
+        front_matter.set(&key, items, toml_text(&value));
+    }
+    Some(front_matter)
+}
+
+fn toml_text(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(items) => items.iter().map(toml_text).collect::<Vec<_>>().join(", "),
+        toml::Value::Table(table) => table
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, toml_text(value)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Datetime(datetime) => datetime.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_front_matter() {
+        let source = "---\ntitle: \"Setup Guide\"\ntags: [setup, \"#linux\"]\ndate: 2024-03-01\ndraft: true\nauthor: Alice\n---\n# Body\n";
+        let (front_matter, body) = split(source);
+        let front_matter = front_matter.unwrap();
+        assert_eq!(body, "# Body\n");
+        assert_eq!(front_matter.title.as_deref(), Some("Setup Guide"));
+        assert_eq!(front_matter.tags, vec!["setup", "linux"]);
+        assert_eq!(front_matter.date.as_deref(), Some("2024-03-01"));
+        assert!(front_matter.draft);
+        assert_eq!(front_matter.extra, vec![("author".to_string(), "Alice".to_string())]);
+
+        // ブロック形式のリストや文字列のタグ
+        let (front_matter, _) = split("---\ntags:\n  - a\n  - b\n...\ntext\n");
+        assert_eq!(front_matter.unwrap().tags, vec!["a", "b"]);
+        // 文字列はカンマだけで区切り、配列の要素は空白を含んでもそのまま
+        let (front_matter, _) = split("---\ntags: a, b c\n---\n");
+        assert_eq!(front_matter.unwrap().tags, vec!["a", "b c"]);
+        let (front_matter, _) = split("---\ntags: [machine learning, \"a, b\"]\n---\n");
+        assert_eq!(front_matter.unwrap().tags, vec!["machine learning", "a, b"]);
+        let (front_matter, _) = split("---\ntags:\n  - machine learning\n---\n");
+        assert_eq!(front_matter.unwrap().tags, vec!["machine learning"]);
+    }
+
+    #[test]
+    fn test_toml_front_matter() {
+        let source = "+++\ntitle = \"TOML Page\"\ntags = [\"x\"]\ndate = 2024-01-02\n[extra]\nkey = 1\n+++\nBody\n";
+        let (front_matter, body) = split(source);
+        let front_matter = front_matter.unwrap();
+        assert_eq!(body, "Body\n");
+        assert_eq!(front_matter.title.as_deref(), Some("TOML Page"));
+        assert_eq!(front_matter.tags, vec!["x"]);
+        assert_eq!(front_matter.date.as_deref(), Some("2024-01-02"));
+        assert_eq!(front_matter.extra, vec![("extra".to_string(), "key: 1".to_string())]);
+    }
+
+    #[test]
+    fn test_not_front_matter() {
+        // 水平線で始まる文書や閉じていないものはそのまま
+        for source in ["---\n\nParagraph\n\n---\n", "---\ntitle: x\n", "# Title\n---\n", "----\na: b\n----\n"] {
+            let (front_matter, body) = split(source);
+            assert!(front_matter.is_none(), "{}", source);
+            assert_eq!(body, source);
+        }
+    }
+
+    #[test]
+    fn test_header_html() {
+        let front_matter = FrontMatter {
+            title: Some("A <b>".to_string()),
+            tags: vec!["t".to_string()],
+            date: None,
+            draft: true,
+            extra: vec![],
+        };
+        assert_eq!(
+            front_matter.header_html(true),
+            "<div class=\"front-matter\"><div class=\"front-matter-title\">A &lt;b&gt;</div><div class=\"front-matter-meta\"><span class=\"front-matter-draft\">下書き</span><a class=\"front-matter-tag\" href=\"/__tags__/t\">#t</a></div></div>"
+        );
+        assert!(!front_matter.header_html(false).contains("front-matter-title"));
+    }
+}
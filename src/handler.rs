@@ -1,9 +1,12 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::{StatusCode, header},
+    body::Body,
+    extract::{Form, Json, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::{Html, IntoResponse, Response, Sse},
     response::sse::{Event, KeepAlive},
 };
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use futures::stream::Stream;
 use std::convert::Infallible;
@@ -13,22 +16,56 @@ use tokio::sync::broadcast;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt;
 
+use crate::archive::{self, ArchiveIndexCache};
+use crate::download;
+use crate::file_index::{self, FileIndex};
+use crate::listing_sort::{ListingSort, SortFields, SortKey};
 use crate::markdown;
+use crate::natural_sort::NameOrder;
+use crate::position::{PositionStore, ReadingPosition, ReadingView};
+use crate::reader::{self, ReaderBook};
+use crate::link_index::LinkIndex;
+use crate::obsidian::Vault;
+use crate::search::{self, SearchIndex};
+use crate::tag_index::{tag_url, TagIndex, TagSort};
+use crate::session::{self, PasswordStore};
+use crate::thumbnail::{self, ThumbnailCache};
+use crate::view_mode::{self, ViewMode, VIEW_MODE_CSS};
 use crate::zip_handler;
 
 #[derive(Clone)]
 pub struct AppState {
     pub base_dir: Arc<PathBuf>,
     pub reload_tx: broadcast::Sender<()>,
-}
-
-pub async fn handle_root(State(state): State<AppState>) -> Response {
-    handle_directory(&state.base_dir, "".to_string()).await
-}
-
-pub async fn handle_path(State(state): State<AppState>, Path(path): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    pub archive_cache: Arc<ArchiveIndexCache>,
+    pub zip_passwords: Arc<PasswordStore>,
+    pub thumbnails: Arc<ThumbnailCache>,
+    pub positions: Arc<PositionStore>,
+    pub name_order: NameOrder,
+    // 一覧の下に表示するMarkdownのファイル名
+    pub readme_names: Arc<Vec<String>>,
+    pub index_as_page: bool,
+    // 目次に入れる見出しの深さ
+    pub toc_depth: usize,
+    // 「ファイルへ移動」パレット用のパス索引
+    pub file_index: Arc<FileIndex>,
+    // 全文検索の転置索引
+    pub search_index: Arc<SearchIndex>,
+    // front matter のタグの索引
+    pub tag_index: Arc<TagIndex>,
+    // Markdownどうしのリンクの索引（リンク元の表示用）
+    pub link_index: Arc<LinkIndex>,
+}
+
+pub async fn handle_root(State(state): State<AppState>, Query(params): Query<HashMap<String, String>>, headers: HeaderMap) -> Response {
+    let sort = ListingSort::from_request(&params, &headers);
+    handle_directory_page(&state, &state.base_dir, "".to_string(), &params, sort).await
+}
+
+pub async fn handle_path(State(state): State<AppState>, Path(path): Path<String>, Query(params): Query<HashMap<String, String>>, headers: HeaderMap) -> Response {
     let raw = params.get("raw").map(|v| v == "1").unwrap_or(false);
-    // zipファイル内のパスをチェック (形式: path/to/file.zip::内部パス)
+    let sort = ListingSort::from_request(&params, &headers);
+    // アーカイブ内のパスをチェック (形式: path/to/file.zip::内部パス, path/to/file.7z::内部パス など)
     if let Some(zip_separator_pos) = path.find("::") {
         let zip_path = &path[..zip_separator_pos];
         let internal_path = &path[zip_separator_pos + 2..];
@@ -45,11 +82,11 @@ pub async fn handle_path(State(state): State<AppState>, Path(path): Path<String>
             return (StatusCode::FORBIDDEN, "Access denied").into_response();
         }
 
-        if !zip_handler::is_zip_file(&canonical_zip_path) {
-            return (StatusCode::BAD_REQUEST, "Not a zip file").into_response();
+        if !archive::is_archive_file(&canonical_zip_path) {
+            return (StatusCode::BAD_REQUEST, "Not an archive file").into_response();
         }
 
-        return handle_zip_content(&canonical_zip_path, zip_path, internal_path, &state.base_dir).await;
+        return handle_zip_content(&canonical_zip_path, zip_path, internal_path, &state, &headers, sort, requested_view(&params)).await;
     }
 
     let full_path = state.base_dir.join(&path);
@@ -84,19 +121,109 @@ pub async fn handle_path(State(state): State<AppState>, Path(path): Path<String>
     }
 
     if canonical_path.is_dir() {
-        handle_directory(&canonical_path, path).await
+        handle_directory_page(&state, &canonical_path, path, &params, sort).await
     } else {
-        handle_file(&canonical_path, &path, &state.base_dir, raw).await
+        handle_file(&state, &canonical_path, &path, raw).await
+    }
+}
+
+/// `--index-as-page` のとき、ディレクトリのページとして表示するファイル
+const INDEX_PAGE: &str = "index.md";
+
+/// `--index-as-page` なら index.md をディレクトリのページとして表示する（`?list=1` で一覧）
+async fn handle_directory_page(state: &AppState, dir_path: &std::path::Path, relative_path: String, params: &HashMap<String, String>, sort: ListingSort) -> Response {
+    let index_path = dir_path.join(INDEX_PAGE);
+    let show_listing = params.contains_key("list") || params.contains_key("sort") || params.contains_key("view");
+
+    if state.index_as_page && !show_listing && index_path.is_file() {
+        // index.md 内の相対リンクが解決できるよう、末尾に `/` を付けたURLで表示する
+        if !relative_path.is_empty() && !relative_path.ends_with('/') {
+            let location = format!("/{}/", relative_path);
+            return (
+                StatusCode::PERMANENT_REDIRECT,
+                [(header::LOCATION, utf8_percent_encode(&location, LOCATION_ENCODE_SET).to_string())],
+            )
+                .into_response();
+        }
+        let index_relative = format!("{}{}", relative_path, INDEX_PAGE);
+        return handle_file(state, &index_path, &index_relative, false).await;
     }
+
+    handle_directory(state, dir_path, relative_path.trim_end_matches('/').to_string(), sort, requested_view(params)).await
+}
+
+/// ディレクトリ内の README（設定された名前の順に、大文字小文字を区別せずに探す）
+fn find_readme(dir_path: &std::path::Path, names: &[String]) -> Option<PathBuf> {
+    let files: Vec<String> = std::fs::read_dir(dir_path)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+
+    names.iter().find_map(|name| {
+        files
+            .iter()
+            .find(|file| file.eq_ignore_ascii_case(name))
+            .map(|file| dir_path.join(file))
+    })
+}
+
+/// 一覧の下に README を表示する（変換に失敗したら何も出さない）
+async fn readme_html(state: &AppState, dir_path: &std::path::Path, relative_path: &str) -> String {
+    let readme_path = match find_readme(dir_path, &state.readme_names) {
+        Some(path) => path,
+        None => return String::new(),
+    };
+    let readme_relative = readme_path
+        .strip_prefix(state.base_dir.as_path())
+        .ok()
+        .and_then(|path| path.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let vault = Vault {
+        base_dir: &state.base_dir,
+        current: &readme_relative,
+        file_index: Some(&state.file_index),
+        link_index: None,
+    };
+    let body = match markdown::convert_to_fragment(&readme_path, &vault).await {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("[readme] {}: {}", readme_path.display(), e);
+            return String::new();
+        }
+    };
+    let name = readme_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let base = if relative_path.is_empty() { "/".to_string() } else { format!("/{}/", relative_path) };
+
+    format!(
+        r#"<div class="readme"><div class="readme-title">📝 {}</div><div class="readme-body" data-base="{}">{}</div></div>
+<script>
+// README内の相対リンク・画像はREADMEのあるディレクトリを基準にする
+(function() {{
+    const body = document.querySelector('.readme-body');
+    const base = new URL(body.dataset.base, location.origin);
+    body.querySelectorAll('[href], [src]').forEach(function(el) {{
+        ['href', 'src'].forEach(function(attr) {{
+            const value = el.getAttribute(attr);
+            if (value && !/^([a-z][a-z0-9+.-]*:|\/|#)/i.test(value)) {{
+                const url = new URL(value, base);
+                el.setAttribute(attr, url.pathname + url.search + url.hash);
+            }}
+        }});
+    }});
+}})();
+</script>"#,
+        markdown::html_escape(&name),
+        markdown::html_escape(&reader::encode_url(&base)),
+        body
+    )
 }
 
 async fn handle_not_found(path: &str) -> Response {
     // 親ディレクトリへのパスを計算
-    let parent_path = if path.contains('/') {
-        path.rsplitn(2, '/').nth(1).unwrap_or("")
-    } else {
-        ""
-    };
+    let parent_path = path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
 
     let html = format!(r#"<!DOCTYPE html>
 <html><head>
@@ -104,6 +231,7 @@ async fn handle_not_found(path: &str) -> Response {
 <meta name="viewport" content="width=device-width, initial-scale=1.0">
 <title>404 Not Found</title>
 <script src="/__reload__.js"></script>
+<script src="/__palette__.js"></script>
 <style>
 * {{ margin: 0; padding: 0; box-sizing: border-box; }}
 body {{
@@ -226,9 +354,220 @@ a {{
     (StatusCode::NOT_FOUND, Html(html)).into_response()
 }
 
-async fn handle_directory(dir_path: &PathBuf, relative_path: String) -> Response {
-    let mut entries = match tokio::fs::read_dir(dir_path).await {
-        Ok(entries) => entries,
+/// 一覧ページに埋め込む件数（残りは /__list__ から読み込む）
+const LISTING_PAGE_SIZE: usize = 200;
+
+/// /__list__ で一度に返せる最大件数
+const LISTING_MAX_LIMIT: usize = 1000;
+
+/// 並べ替え済みの一覧と、画像の通し番号（ギャラリーのモーダル・リーダーと同じ順）
+struct ListingItems {
+    entries: Vec<zip_handler::ZipEntry>,
+    image_indexes: Vec<Option<usize>>,
+    image_paths: Vec<String>,
+}
+
+impl ListingItems {
+    /// `link_path` は画像のリンク先（ファイルシステムは相対パス、アーカイブは `/a.zip::name`）
+    fn new(entries: Vec<zip_handler::ZipEntry>, link_path: impl Fn(&zip_handler::ZipEntry) -> String) -> Self {
+        let mut image_indexes = Vec::with_capacity(entries.len());
+        let mut image_paths = Vec::new();
+        for entry in &entries {
+            if !entry.is_dir && reader::is_image_file(&entry.name) {
+                image_indexes.push(Some(image_paths.len()));
+                image_paths.push(link_path(entry));
+            } else {
+                image_indexes.push(None);
+            }
+        }
+        Self { entries, image_indexes, image_paths }
+    }
+
+    fn page(&self, offset: usize, limit: usize) -> impl Iterator<Item = (&zip_handler::ZipEntry, Option<usize>)> {
+        self.entries
+            .iter()
+            .zip(self.image_indexes.iter().copied())
+            .skip(offset)
+            .take(limit)
+    }
+
+    /// モーダル用に最初のページ分の画像パスを埋め込む
+    fn first_images_json(&self) -> String {
+        let first = &self.image_paths[..self.image_paths.len().min(LISTING_PAGE_SIZE)];
+        serde_json::to_string(first).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// mvuListing.init に渡す設定
+    fn init_json(&self, path: &str, sort: &ListingSort) -> String {
+        serde_json::json!({
+            "path": path,
+            "sortQuery": format!("&{}", sort.query()),
+            "total": self.entries.len(),
+            "loaded": self.entries.len().min(LISTING_PAGE_SIZE),
+            "pageSize": LISTING_PAGE_SIZE,
+        })
+        .to_string()
+    }
+}
+
+/// ディレクトリの項目をアーカイブのエントリと同じ形で集めて、同じ並べ替え・列表示を使う
+/// Markdownのタイトルはリンクの索引から引く（ファイルは読まない）
+async fn directory_items(dir_path: &std::path::Path, relative_path: &str, sort: &ListingSort, order: NameOrder, titles: &LinkIndex) -> Result<ListingItems, String> {
+    let mut entries = tokio::fs::read_dir(dir_path)
+        .await
+        .map_err(|e| format!("Cannot read directory: {}", e))?;
+
+    let mut items = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(file_name) = entry.file_name().into_string() {
+            let metadata = tokio::fs::metadata(entry.path()).await.ok();
+            let is_dir = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
+            let is_markdown = !is_dir && (file_name.ends_with(".md") || file_name.ends_with(".mkd"));
+            let title = if is_markdown { titles.front_matter_title(&join_relative(relative_path, &file_name)) } else { None };
+            items.push(zip_handler::ZipEntry {
+                name: file_name,
+                is_dir,
+                size: metadata.as_ref().filter(|_| !is_dir).map(|m| m.len()).unwrap_or(0),
+                modified: metadata
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+                title,
+                ..Default::default()
+            });
+        }
+    }
+    items.sort_by(|a, b| sort.cmp(&SortFields::from(a), &SortFields::from(b), order));
+
+    Ok(ListingItems::new(items, |item| join_relative(relative_path, &item.name)))
+}
+
+/// アーカイブ内ディレクトリの項目（ディレクトリを先に、その中で選択した並び順）
+/// Markdownのタイトルは全文検索の索引から引く（索引に入るのはZIPだけ）
+fn zip_directory_items(all_entries: &[zip_handler::ZipEntry], zip_relative_path: &str, internal_dir: &str, sort: &ListingSort, order: NameOrder, titles: &SearchIndex) -> ListingItems {
+    let mut entries = zip_handler::get_directory_entries(all_entries, internal_dir, order);
+    for entry in entries.iter_mut().filter(|e| !e.is_dir && (e.name.ends_with(".md") || e.name.ends_with(".mkd"))) {
+        entry.title = titles.front_matter_title(&format!("{}::{}", zip_relative_path, entry.name));
+    }
+    entries.sort_by(|a, b| {
+        b.is_dir.cmp(&a.is_dir)
+            .then_with(|| sort.cmp(&SortFields::from(a), &SortFields::from(b), order))
+    });
+    ListingItems::new(entries, |entry| format!("/{}::{}", zip_relative_path, entry.name))
+}
+
+fn join_relative(relative_path: &str, name: &str) -> String {
+    if relative_path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", relative_path, name)
+    }
+}
+
+/// ディレクトリ一覧の1行
+fn directory_row_html(relative_path: &str, item: &zip_handler::ZipEntry, image_index: Option<usize>) -> String {
+    let columns = columns_html(&SortFields::from(item));
+    let (name, is_dir) = (&item.name, item.is_dir);
+    let link_path = join_relative(relative_path, name);
+
+    // アーカイブ(zip/tar系/7z)の判定
+    let is_zip = !is_dir && archive::is_archive_file(std::path::Path::new(name));
+
+    // HTMLファイルの判定
+    let is_html = !is_dir && (name.ends_with(".html") || name.ends_with(".htm") ||
+        name.ends_with(".HTML") || name.ends_with(".HTM"));
+
+    // Markdownファイルには特別なアイコンを使用
+    let (icon, icon_class, link_class, actual_link) = if is_dir {
+        ("📁", "dir", "", link_path.clone())
+    } else if name.ends_with(".md") || name.ends_with(".mkd") {
+        ("📝", "file", " markdown", link_path.clone())
+    } else if is_zip {
+        ("📦", "file", " zip", format!("{}::", link_path))
+    } else if is_html {
+        ("🌐", "file", " html-file", link_path.clone())
+    } else {
+        ("📄", "file", "", link_path.clone())
+    };
+
+    let select_box = format!(
+        "<input type=\"checkbox\" class=\"select-box\" value=\"{}\" aria-label=\"select\">",
+        markdown::html_escape(name)
+    );
+
+    // data-name は絞り込みに使う
+    let data_name = markdown::html_escape(name);
+    match (image_index, &item.title) {
+        (Some(index), _) => format!(
+            "<li class=\"image-item\" data-name=\"{}\">{}<a href=\"javascript:void(0)\" onclick=\"openModal('{}', {})\" class=\"{}\"><img src=\"{}\" class=\"thumbnail\" alt=\"{}\" loading=\"lazy\"><span class=\"name\">{}</span>{}</a></li>",
+            data_name, select_box, link_path, index, link_class.trim(), thumbnail_src(&link_path), name, name, columns
+        ),
+        // front matter のタイトルがあればファイル名の代わりに出す（ファイル名はツールチップに）
+        (None, Some(title)) => format!(
+            "<li data-name=\"{}\" data-title=\"{}\">{}<a href=\"/{}\" class=\"{}\" title=\"{}\"><span class=\"icon {}\">{}</span><span class=\"name\">{}</span>{}</a></li>",
+            data_name, markdown::html_escape(title), select_box, actual_link, link_class.trim(), data_name, icon_class, icon, markdown::html_escape(title), columns
+        ),
+        (None, None) => format!(
+            "<li data-name=\"{}\">{}<a href=\"/{}\" class=\"{}\"><span class=\"icon {}\">{}</span><span class=\"name\">{}</span>{}</a></li>",
+            data_name, select_box, actual_link, link_class.trim(), icon_class, icon, name, columns
+        ),
+    }
+}
+
+/// アーカイブ一覧の1行
+fn zip_row_html(zip_relative_path: &str, entry: &zip_handler::ZipEntry, image_index: Option<usize>) -> String {
+    let file_name = entry.name.rsplit('/').next().unwrap_or(&entry.name);
+    let link_path = format!("/{}::{}", zip_relative_path, entry.name);
+
+    let is_html_zip = !entry.is_dir && (file_name.ends_with(".html") || file_name.ends_with(".htm") ||
+        file_name.ends_with(".HTML") || file_name.ends_with(".HTM"));
+
+    let (icon, icon_class, link_class) = if entry.is_dir {
+        ("📁", "dir", "")
+    } else if entry.encrypted && image_index.is_none() {
+        ("🔒", "file", " encrypted")
+    } else if file_name.ends_with(".md") || file_name.ends_with(".mkd") {
+        ("📝", "file", " markdown")
+    } else if is_html_zip {
+        ("🌐", "file", " html-file")
+    } else {
+        ("📄", "file", "")
+    };
+
+    let meta = zip_entry_meta_html(entry);
+
+    let data_name = markdown::html_escape(file_name);
+    match (image_index, &entry.title) {
+        (Some(index), _) => format!(
+            "<li class=\"image-item\" data-name=\"{}\"><a href=\"javascript:void(0)\" onclick=\"openModal('{}', {})\" class=\"{}\"><img src=\"{}\" class=\"thumbnail\" alt=\"{}\" loading=\"lazy\"><span>{}</span>{}</a></li>",
+            data_name, link_path, index, link_class.trim(), thumbnail_src(&format!("{}::{}", zip_relative_path, entry.name)), file_name, file_name, meta
+        ),
+        // front matter のタイトルがあればファイル名の代わりに出す（ファイル名はツールチップに）
+        (None, Some(title)) => format!(
+            "<li data-name=\"{}\" data-title=\"{}\"><a href=\"{}\" class=\"{}\" title=\"{}\"><span class=\"icon {}\">{}</span>{}{}</a></li>",
+            data_name, markdown::html_escape(title), link_path, link_class.trim(), data_name, icon_class, icon, markdown::html_escape(title), meta
+        ),
+        (None, None) => format!(
+            "<li data-name=\"{}\"><a href=\"{}\" class=\"{}\"><span class=\"icon {}\">{}</span>{}{}</a></li>",
+            data_name, link_path, link_class.trim(), icon_class, icon, file_name, meta
+        ),
+    }
+}
+
+/// 埋め込み分より多い場合の「さらに表示」（スクロールでも読み込む）
+fn listing_more_html(total: usize) -> String {
+    if total <= LISTING_PAGE_SIZE {
+        return String::new();
+    }
+    format!(
+        "<div id=\"listing-more\" class=\"listing-more\"><button>さらに表示 ({} / {})</button></div>",
+        LISTING_PAGE_SIZE, total
+    )
+}
+
+async fn handle_directory(state: &AppState, dir_path: &std::path::Path, relative_path: String, sort: ListingSort, view: Option<ViewMode>) -> Response {
+    let listing = match directory_items(dir_path, &relative_path, &sort, state.name_order, &state.link_index).await {
+        Ok(listing) => listing,
         Err(_) => {
             return (StatusCode::INTERNAL_SERVER_ERROR, "Cannot read directory").into_response()
         }
@@ -240,6 +579,9 @@ async fn handle_directory(dir_path: &PathBuf, relative_path: String) -> Response
 <meta name="viewport" content="width=device-width, initial-scale=1.0">
 <title>Directory Listing</title>
 <script src="/__reload__.js"></script>
+<script src="/__position__.js"></script>
+<script src="/__listing__.js"></script>
+<script src="/__palette__.js"></script>
 <style>
 * { margin: 0; padding: 0; box-sizing: border-box; }
 body {
@@ -282,10 +624,48 @@ ul {
 }
 li {
     border-bottom: 1px solid #e5e7eb;
+    display: flex;
+    align-items: center;
 }
 li:last-child {
     border-bottom: none;
 }
+li > a {
+    flex: 1;
+    min-width: 0;
+}
+.select-box {
+    width: 1.1rem;
+    height: 1.1rem;
+    margin-left: 0.5rem;
+    accent-color: #667eea;
+    cursor: pointer;
+}
+.toolbar {
+    display: flex;
+    gap: 0.75rem;
+    margin-bottom: 1rem;
+}
+.toolbar .tool-button {
+    display: inline-flex;
+    padding: 0.5rem 1rem;
+    border: none;
+    border-radius: 8px;
+    background: #f3f4f6;
+    color: #374151;
+    font-size: 0.9rem;
+    cursor: pointer;
+    text-decoration: none;
+}
+.toolbar .tool-button:hover:not(:disabled) {
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+    transform: none;
+}
+.toolbar .tool-button:disabled {
+    opacity: 0.5;
+    cursor: default;
+}
 a {
     display: flex;
     align-items: center;
@@ -315,6 +695,29 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
     font-weight: 600;
     color: #667eea;
 }
+.continue {
+    margin-bottom: 1.25rem;
+    padding: 0.5rem 0.75rem;
+    background: #f5f3ff;
+    border-radius: 12px;
+}
+.continue-title {
+    padding: 0.25rem 0.5rem;
+    color: #667eea;
+    font-weight: 600;
+    font-size: 0.9rem;
+}
+.continue a {
+    padding: 0.5rem 0.75rem;
+}
+.continue .meta {
+    margin-left: auto;
+    padding-left: 1rem;
+    color: #9ca3af;
+    font-size: 0.8rem;
+    white-space: nowrap;
+}
+.continue a:hover .meta { color: rgba(255, 255, 255, 0.85); }
 .image-item {
     position: relative;
 }
@@ -417,101 +820,189 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
 .modal-nav.next {
     right: 20px;
 }
+.sort-bar {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 0.25rem;
+    margin-bottom: 0.75rem;
+    color: #6b7280;
+    font-size: 0.85rem;
+}
+.sort-bar a.sort-link {
+    padding: 0.3rem 0.75rem;
+    border-radius: 999px;
+    font-size: 0.85rem;
+}
+.sort-bar a.sort-link.active {
+    background: #eef2ff;
+    color: #667eea;
+    font-weight: 600;
+}
+.sort-bar a.sort-link:hover {
+    transform: none;
+}
+.name {
+    flex: 1;
+    min-width: 0;
+    overflow-wrap: anywhere;
+}
+.col {
+    flex-shrink: 0;
+    padding-left: 1rem;
+    color: #9ca3af;
+    font-size: 0.8rem;
+    text-align: right;
+    white-space: nowrap;
+}
+.readme {
+    margin-top: 2rem;
+    padding-top: 1.5rem;
+    border-top: 3px solid #667eea;
+}
+.readme-title {
+    margin-bottom: 1rem;
+    color: #667eea;
+    font-weight: 600;
+    font-size: 0.9rem;
+}
+.readme-body {
+    line-height: 1.7;
+    overflow-wrap: anywhere;
+}
+.readme-body h1, .readme-body h2, .readme-body h3, .readme-body h4 {
+    margin: 1.25em 0 0.5em;
+    color: #667eea;
+    line-height: 1.3;
+}
+.readme-body h1 { font-size: 1.75rem; }
+.readme-body h2 { font-size: 1.4rem; border-bottom: 2px solid #e5e7eb; padding-bottom: 0.3rem; }
+.readme-body p { margin: 0.75rem 0; }
+.readme-body ul, .readme-body ol { list-style: revert; padding-left: 2rem; margin: 0.75rem 0; }
+.readme-body li { border: none; display: list-item; }
+.readme-body a {
+    display: inline;
+    padding: 0;
+    color: #667eea;
+}
+.readme-body a:hover {
+    background: none;
+    color: #764ba2;
+    transform: none;
+    box-shadow: none;
+}
+.readme-body code {
+    background: #f3f4f6;
+    padding: 0.2em 0.4em;
+    border-radius: 6px;
+    font-size: 0.9em;
+    color: #e83e8c;
+}
+.readme-body pre {
+    background: #282c34;
+    color: #abb2bf;
+    padding: 1rem;
+    border-radius: 12px;
+    overflow-x: auto;
+    margin: 1rem 0;
+}
+.readme-body pre code { background: none; color: inherit; padding: 0; }
+.readme-body blockquote {
+    border-left: 4px solid #667eea;
+    background: #f9fafb;
+    padding: 0.5rem 1rem;
+    margin: 1rem 0;
+    color: #555;
+}
+.readme-body .callout {
+    border-left: 4px solid #667eea;
+    background: #f9fafb;
+    padding: 0.5rem 1rem;
+    margin: 1rem 0;
+}
+.readme-body .callout-title { font-weight: 600; }
+.readme-body .wiki-link-unresolved { color: #9ca3af; }
+.readme-body .inline-tag { background: #eef2ff; border-radius: 999px; padding: 0 0.4rem; }
+.readme-body table { border-collapse: collapse; margin: 1rem 0; }
+.readme-body th, .readme-body td { padding: 0.5rem 0.75rem; border: 1px solid #e5e7eb; }
+.readme-body img { max-width: 100%; height: auto; border-radius: 8px; }
+.col-type { width: 5rem; }
+.col-size { width: 5.5rem; }
+.col-mtime { width: 8.5rem; }
+a:hover .col { color: rgba(255, 255, 255, 0.85); }
+.listing-more {
+    margin-top: 1rem;
+    text-align: center;
+}
+.listing-more button {
+    padding: 0.6rem 1.5rem;
+    border: none;
+    border-radius: 8px;
+    background: #f3f4f6;
+    color: #374151;
+    font-size: 0.9rem;
+    cursor: pointer;
+}
+.listing-more button:hover {
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+}
 @media (max-width: 640px) {
     body { padding: 1rem; }
     .container { padding: 1.5rem; }
     h1 { font-size: 1.5rem; }
     .thumbnail { width: 60px; height: 60px; }
+    .col-type, .col-mtime { display: none; }
 }
 </style>
-</head><body><div class="container">"#);
+"#);
+    html.push_str(VIEW_MODE_CSS);
+    html.push_str("</head><body><div class=\"container\">");
 
     html.push_str(&format!(
         "<h1><span class=\"path\">📁 /{}</span></h1>",
         if relative_path.is_empty() { "Home".to_string() } else { relative_path.clone() }
     ));
-    html.push_str("<ul>");
+    // ZIPダウンロード（ディレクトリ全体 / チェックした項目）、画像があればリーダーで開くリンク
+    let reader_link = if listing.image_paths.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<a class=\"tool-button\" href=\"/__reader__/{}\">📖 リーダーで読む</a>",
+            markdown::html_escape(&reader::encode_url(&relative_path))
+        )
+    };
+    html.push_str(&format!(
+        "<div class=\"toolbar\"><a class=\"tool-button\" href=\"/__download__?path={}\">📦 ZIPでダウンロード</a><button class=\"tool-button\" id=\"downloadSelected\" disabled>☑️ 選択をダウンロード</button>{}</div>",
+        utf8_percent_encode(&relative_path, NON_ALPHANUMERIC),
+        reader_link
+    ));
+    html.push_str(&continue_reading_html(&state.positions, &relative_path));
+    html.push_str(&list_controls_html(&sort));
+    html.push_str(&view_mode::listing_open_html(
+        &relative_path,
+        ViewMode::auto(listing.image_paths.len(), listing.entries.len()),
+        view,
+    ));
 
     // 親ディレクトリへのリンク
     if !relative_path.is_empty() {
-        let parent = if relative_path.contains('/') {
-            relative_path.rsplitn(2, '/').nth(1).unwrap()
-        } else {
-            ""
-        };
+        let parent = relative_path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
         html.push_str(&format!(
             "<li><a href=\"/{}\" class=\"parent\"><span class=\"icon\">⬆️</span>Parent Directory</a></li>",
             parent
         ));
     }
 
-    let mut items = Vec::new();
-    while let Ok(Some(entry)) = entries.next_entry().await {
-        if let Ok(file_name) = entry.file_name().into_string() {
-            let is_dir = entry.path().is_dir();
-            items.push((file_name, is_dir));
-        }
-    }
-    items.sort();
-
-    let mut image_paths = Vec::new();
-    for (name, is_dir) in items {
-        let link_path = if relative_path.is_empty() {
-            name.clone()
-        } else {
-            format!("{}/{}", relative_path, name)
-        };
-
-        // 画像ファイルの判定
-        let is_image = !is_dir && (
-            name.ends_with(".jpg") || name.ends_with(".jpeg") ||
-            name.ends_with(".png") || name.ends_with(".gif") ||
-            name.ends_with(".webp") || name.ends_with(".svg") ||
-            name.ends_with(".JPG") || name.ends_with(".JPEG") ||
-            name.ends_with(".PNG") || name.ends_with(".GIF") ||
-            name.ends_with(".WEBP") || name.ends_with(".SVG")
-        );
-
-        if is_image {
-            image_paths.push(link_path.clone());
-        }
-
-        // zipファイルの判定
-        let is_zip = !is_dir && (name.ends_with(".zip") || name.ends_with(".ZIP"));
-
-        // HTMLファイルの判定
-        let is_html = !is_dir && (name.ends_with(".html") || name.ends_with(".htm") ||
-            name.ends_with(".HTML") || name.ends_with(".HTM"));
-
-        // Markdownファイルには特別なアイコンを使用
-        let (icon, icon_class, link_class, actual_link) = if is_dir {
-            ("📁", "dir", "", link_path.clone())
-        } else if name.ends_with(".md") || name.ends_with(".mkd") {
-            ("📝", "file", " markdown", link_path.clone())
-        } else if is_image {
-            ("🖼️", "file", "", link_path.clone())
-        } else if is_zip {
-            ("📦", "file", " zip", format!("{}::", link_path))
-        } else if is_html {
-            ("🌐", "file", " html-file", link_path.clone())
-        } else {
-            ("📄", "file", "", link_path.clone())
-        };
-
-        if is_image {
-            html.push_str(&format!(
-                "<li class=\"image-item\"><a href=\"javascript:void(0)\" onclick=\"openModal('{}', {})\" class=\"{}\"><img src=\"/{}\" class=\"thumbnail\" alt=\"{}\" loading=\"lazy\"><span>{}</span></a></li>",
-                link_path, image_paths.len() - 1, link_class.trim(), link_path, name, name
-            ));
-        } else {
-            html.push_str(&format!(
-                "<li><a href=\"/{}\" class=\"{}\"><span class=\"icon {}\">{}</span>{}</a></li>",
-                actual_link, link_class.trim(), icon_class, icon, name
-            ));
-        }
+    // 最初のページだけ埋め込み、残りは /__list__ からスクロールに合わせて読み込む
+    for (entry, image_index) in listing.page(0, LISTING_PAGE_SIZE) {
+        html.push_str(&directory_row_html(&relative_path, entry, image_index));
     }
 
-    html.push_str("</ul></div>");
+    html.push_str("</ul>");
+    html.push_str(&listing_more_html(listing.entries.len()));
+    html.push_str(&readme_html(state, dir_path, &relative_path).await);
+    html.push_str("</div>");
 
     // モーダルの追加
     html.push_str(r#"<div id="imageModal" class="modal">
@@ -524,10 +1015,19 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
     </div>
 </div>
 <script>
+const currentDir = "#);
+    html.push_str(&serde_json::to_string(&relative_path).unwrap_or_else(|_| "\"\"".to_string()));
+    html.push_str(r#";
 const imagePaths = "#);
-    html.push_str(&serde_json::to_string(&image_paths).unwrap_or_else(|_| "[]".to_string()));
+    html.push_str(&listing.first_images_json());
+    html.push_str(r#";
+const imageTotal = "#);
+    html.push_str(&listing.image_paths.len().to_string());
     html.push_str(r#";
 let currentImageIndex = 0;
+mvuListing.init("#);
+    html.push_str(&listing.init_json(&relative_path, &sort));
+    html.push_str(r#", imagePaths);
 
 function openModal(imagePath, index) {
     currentImageIndex = index;
@@ -540,18 +1040,23 @@ function closeModal() {
 }
 
 function updateModalImage() {
-    const imagePath = imagePaths[currentImageIndex];
-    document.getElementById('modalImage').src = '/' + imagePath;
-    document.getElementById('modalLink').href = '/' + imagePath;
+    const index = currentImageIndex;
+    // まだ読み込んでいない画像は一覧APIから取得する
+    mvuListing.image(index).then(function(imagePath) {
+        if (index !== currentImageIndex || !imagePath) return;
+        document.getElementById('modalImage').src = '/' + imagePath;
+        document.getElementById('modalLink').href = '/' + imagePath;
+    });
+    mvuPosition.save(currentDir, { view: 'gallery', page: index, total: imageTotal });
 }
 
 function nextImage() {
-    currentImageIndex = (currentImageIndex + 1) % imagePaths.length;
+    currentImageIndex = (currentImageIndex + 1) % imageTotal;
     updateModalImage();
 }
 
 function prevImage() {
-    currentImageIndex = (currentImageIndex - 1 + imagePaths.length) % imagePaths.length;
+    currentImageIndex = (currentImageIndex - 1 + imageTotal) % imageTotal;
     updateModalImage();
 }
 
@@ -561,6 +1066,12 @@ document.getElementById('imageModal').addEventListener('click', function(e) {
     }
 });
 
+// 「続きから読む」から開いた場合は保存した画像を表示する
+const resumeImage = location.hash.match(/^#image=(\d+)$/);
+if (resumeImage && imageTotal > 0) {
+    openModal(null, Math.min(parseInt(resumeImage[1], 10), imageTotal - 1));
+}
+
 document.addEventListener('keydown', function(e) {
     const modal = document.getElementById('imageModal');
     if (modal.classList.contains('active')) {
@@ -573,17 +1084,41 @@ document.addEventListener('keydown', function(e) {
         }
     }
 });
+
+(function() {
+    // 後から読み込まれる項目にも効くように一覧全体で受ける
+    const listing = document.getElementById('listing');
+    const downloadButton = document.getElementById('downloadSelected');
+
+    listing.addEventListener('change', function(e) {
+        if (e.target.classList.contains('select-box')) {
+            downloadButton.disabled = !listing.querySelector('.select-box:checked');
+        }
+    });
+
+    downloadButton.addEventListener('click', function() {
+        const names = Array.from(listing.querySelectorAll('.select-box:checked')).map(b => b.value);
+        location.href = '/__download__?path=' + encodeURIComponent(currentDir) +
+            '&select=' + encodeURIComponent(JSON.stringify(names));
+    });
+})();
 </script>
 </body></html>"#);
-    Html(html).into_response()
+    with_sort_cookie(Html(html).into_response(), &sort)
 }
 
-async fn handle_file(file_path: &PathBuf, relative_path: &str, base_dir: &PathBuf, raw: bool) -> Response {
+async fn handle_file(state: &AppState, file_path: &PathBuf, relative_path: &str, raw: bool) -> Response {
     let extension = file_path.extension().and_then(|s| s.to_str());
 
     // マークダウンファイルの場合はunidocで変換
     if matches!(extension, Some("md") | Some("mkd")) {
-        match markdown::convert_to_html(file_path, relative_path, base_dir).await {
+        let vault = Vault {
+            base_dir: &state.base_dir,
+            current: relative_path,
+            file_index: Some(&state.file_index),
+            link_index: Some(&state.link_index),
+        };
+        match markdown::convert_to_html(file_path, &vault, state.name_order, state.toc_depth).await {
             Ok(html) => Html(html).into_response(),
             Err(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -593,7 +1128,7 @@ async fn handle_file(file_path: &PathBuf, relative_path: &str, base_dir: &PathBu
         }
     } else if matches!(extension, Some("html") | Some("htm")) && !raw {
         // HTMLファイルはiframeで包んだラッパーページを返す
-        let html = generate_html_wrapper(relative_path, base_dir);
+        let html = generate_html_wrapper(relative_path, &state.base_dir, state.name_order, &state.link_index);
         Html(html).into_response()
     } else {
         // その他のファイルはそのまま返す
@@ -630,14 +1165,16 @@ fn guess_content_type(extension: Option<&str>) -> &'static str {
         Some("mp4") => "video/mp4",
         Some("webm") => "video/webm",
         Some("zip") => "application/zip",
+        Some("cbz") => "application/vnd.comicbook+zip",
         Some("tar") => "application/x-tar",
         Some("gz") => "application/gzip",
+        Some("7z") => "application/x-7z-compressed",
         _ => "application/octet-stream",
     }
 }
 
-fn generate_html_wrapper(relative_path: &str, base_dir: &PathBuf) -> String {
-    let file_tree = crate::markdown::generate_file_tree_html(base_dir, relative_path)
+fn generate_html_wrapper(relative_path: &str, base_dir: &PathBuf, order: NameOrder, titles: &LinkIndex) -> String {
+    let file_tree = crate::markdown::generate_file_tree_html(base_dir, relative_path, order, Some(titles))
         .unwrap_or_else(|_| String::from("<p>ファイルツリーの読み込み失敗</p>"));
 
     format!(r#"<!DOCTYPE html>
@@ -646,6 +1183,7 @@ fn generate_html_wrapper(relative_path: &str, base_dir: &PathBuf) -> String {
 <meta name="viewport" content="width=device-width, initial-scale=1.0">
 <title>{}</title>
 <script src="/__reload__.js"></script>
+<script src="/__palette__.js"></script>
 <style>
 * {{ margin: 0; padding: 0; box-sizing: border-box; }}
 html, body {{ height: 100%; }}
@@ -735,18 +1273,19 @@ body {{
 </body></html>"#, relative_path, file_tree, relative_path)
 }
 
-async fn handle_zip_content(zip_path: &PathBuf, zip_relative_path: &str, internal_path: &str, base_dir: &PathBuf) -> Response {
-    // zip内のすべてのエントリを取得
-    let all_entries = match zip_handler::list_zip_contents(zip_path) {
-        Ok(entries) => entries,
+async fn handle_zip_content(zip_path: &std::path::Path, zip_relative_path: &str, internal_path: &str, state: &AppState, headers: &HeaderMap, sort: ListingSort, view: Option<ViewMode>) -> Response {
+    // アーカイブ内のすべてのエントリを取得（インデックスはキャッシュされる）
+    let index = match state.archive_cache.get(zip_path) {
+        Ok(index) => index,
         Err(e) => {
-            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read zip: {}", e)).into_response();
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read archive: {}", e)).into_response();
         }
     };
+    let all_entries = index.entries();
 
     // internal_pathが空の場合はzipのルートディレクトリを表示
     if internal_path.is_empty() || internal_path == "/" {
-        return handle_zip_directory(zip_path, zip_relative_path, "", &all_entries).await;
+        return handle_zip_directory(state, zip_relative_path, "", all_entries, sort, view).await;
     }
 
     // internal_pathがディレクトリかファイルか判定
@@ -756,48 +1295,226 @@ async fn handle_zip_content(zip_path: &PathBuf, zip_relative_path: &str, interna
     });
 
     if is_directory {
-        handle_zip_directory(zip_path, zip_relative_path, internal_path, &all_entries).await
+        return handle_zip_directory(state, zip_relative_path, internal_path, all_entries, sort, view).await;
+    }
+
+    // 暗号化エントリはセッションに保存されたパスワードで開く。なければ入力を求める
+    let encrypted = all_entries.iter().any(|e| e.name == internal_path && e.encrypted);
+    let password = if encrypted {
+        let stored = session::session_id(headers)
+            .and_then(|id| state.zip_passwords.get(&id, zip_path));
+        match stored {
+            Some(password) => Some(password),
+            None => return handle_zip_password_prompt(zip_relative_path, internal_path, None),
+        }
     } else {
-        handle_zip_file(zip_path, zip_relative_path, internal_path, base_dir).await
+        None
+    };
+
+    handle_zip_file(state, zip_path, &index, zip_relative_path, internal_path, password.as_deref()).await
+}
+
+/// Locationヘッダーに入れるパスのエンコード (`/` と `:` はそのまま残す)
+const LOCATION_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>')
+    .add(b'?').add(b'`').add(b'{').add(b'}');
+
+/// Content-Disposition の filename* 用 (RFC 5987)
+const FILENAME_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'.').remove(b'-').remove(b'_');
+
+#[derive(Deserialize)]
+pub struct ZipPasswordForm {
+    archive: String,
+    entry: String,
+    password: String,
+}
+
+pub async fn handle_zip_password(State(state): State<AppState>, headers: HeaderMap, Form(form): Form<ZipPasswordForm>) -> Response {
+    let canonical_zip_path = match state.base_dir.join(&form.archive).canonicalize() {
+        Ok(p) => p,
+        Err(_) => return handle_not_found(&form.archive).await,
+    };
+
+    if !canonical_zip_path.starts_with(&*state.base_dir) {
+        return (StatusCode::FORBIDDEN, "Access denied").into_response();
+    }
+
+    if !zip_handler::is_zip_file(&canonical_zip_path) {
+        return (StatusCode::BAD_REQUEST, "Not a zip file").into_response();
+    }
+
+    // 暗号化されたエントリがあるときだけパスワードを受け付ける
+    let index = match state.archive_cache.get(&canonical_zip_path) {
+        Ok(index) => index,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read archive: {}", e)).into_response(),
+    };
+    match index.entries().iter().find(|e| e.name == form.entry && !e.is_dir) {
+        Some(entry) if entry.encrypted => {}
+        Some(_) => return (StatusCode::BAD_REQUEST, "Entry is not encrypted").into_response(),
+        None => return handle_not_found(&format!("{}::{}", form.archive, form.entry)).await,
+    }
+
+    // 対象のエントリが実際に復号できるかでパスワードを検証する
+    if zip_handler::read_file_from_zip(&canonical_zip_path, &form.entry, Some(&form.password)).is_err() {
+        return handle_zip_password_prompt(&form.archive, &form.entry, Some("パスワードが違います"));
     }
+
+    let session_id = session::session_id(&headers).unwrap_or_else(session::new_session_id);
+    state.zip_passwords.insert(&session_id, &canonical_zip_path, form.password);
+
+    let location = format!("/{}::{}", form.archive, form.entry);
+    (
+        StatusCode::SEE_OTHER,
+        [
+            (header::LOCATION, utf8_percent_encode(&location, LOCATION_ENCODE_SET).to_string()),
+            (header::SET_COOKIE, session::session_cookie(&session_id)),
+        ],
+    )
+        .into_response()
 }
 
-async fn handle_zip_directory(_zip_path: &PathBuf, zip_relative_path: &str, internal_dir: &str, all_entries: &[zip_handler::ZipEntry]) -> Response {
-    let entries = zip_handler::get_directory_entries(all_entries, internal_dir);
+fn handle_zip_password_prompt(zip_relative_path: &str, internal_file: &str, error: Option<&str>) -> Response {
+    let error_html = error
+        .map(|e| format!("<div class=\"error\">{}</div>", markdown::html_escape(e)))
+        .unwrap_or_default();
 
-    let mut html = String::from(r#"<!DOCTYPE html>
+    let html = format!(r#"<!DOCTYPE html>
 <html><head>
 <meta charset="UTF-8">
 <meta name="viewport" content="width=device-width, initial-scale=1.0">
-<title>Zip Contents</title>
-<script src="/__reload__.js"></script>
+<title>Password Required</title>
 <style>
-* { margin: 0; padding: 0; box-sizing: border-box; }
-body {
+* {{ margin: 0; padding: 0; box-sizing: border-box; }}
+body {{
     font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', 'Noto Sans', Helvetica, Arial, sans-serif;
     background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
     min-height: 100vh;
+    display: flex;
+    align-items: center;
+    justify-content: center;
     padding: 2rem;
     color: #333;
-}
-.container {
-    max-width: 900px;
-    margin: 0 auto;
+}}
+.container {{
+    max-width: 480px;
+    width: 100%;
     background: rgba(255, 255, 255, 0.95);
     backdrop-filter: blur(10px);
     border-radius: 20px;
     box-shadow: 0 20px 60px rgba(0, 0, 0, 0.3);
     padding: 2.5rem;
-    animation: fadeIn 0.5s ease;
-}
-@keyframes fadeIn {
-    from { opacity: 0; transform: translateY(20px); }
-    to { opacity: 1; transform: translateY(0); }
-}
-h1 {
-    font-size: 2rem;
+    text-align: center;
+}}
+.lock {{ font-size: 4rem; margin-bottom: 1rem; }}
+h1 {{
+    font-size: 1.5rem;
     font-weight: 700;
-    margin-bottom: 1.5rem;
+    margin-bottom: 1rem;
+    color: #374151;
+}}
+.path {{
+    font-size: 0.9rem;
+    color: #6b7280;
+    margin-bottom: 1.5rem;
+    word-break: break-all;
+    padding: 0.75rem;
+    background: #f3f4f6;
+    border-radius: 8px;
+    font-family: 'Monaco', 'Courier New', monospace;
+}}
+.error {{
+    color: #dc2626;
+    margin-bottom: 1rem;
+    font-weight: 600;
+}}
+input[type="password"] {{
+    width: 100%;
+    padding: 0.75rem 1rem;
+    border: 2px solid #e5e7eb;
+    border-radius: 12px;
+    font-size: 1rem;
+    margin-bottom: 1rem;
+}}
+input[type="password"]:focus {{
+    outline: none;
+    border-color: #667eea;
+}}
+button {{
+    width: 100%;
+    padding: 0.9rem 2rem;
+    border: none;
+    border-radius: 12px;
+    font-size: 1rem;
+    font-weight: 600;
+    cursor: pointer;
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+    box-shadow: 0 4px 12px rgba(102, 126, 234, 0.4);
+}}
+</style>
+</head><body>
+<div class="container">
+    <div class="lock">🔒</div>
+    <h1>パスワードが必要です</h1>
+    <div class="path">{}::{}</div>
+    {}
+    <form method="post" action="/__zip_password__">
+        <input type="hidden" name="archive" value="{}">
+        <input type="hidden" name="entry" value="{}">
+        <input type="password" name="password" placeholder="パスワード" autofocus required>
+        <button type="submit">開く</button>
+    </form>
+</div>
+</body></html>"#,
+        markdown::html_escape(zip_relative_path),
+        markdown::html_escape(internal_file),
+        error_html,
+        markdown::html_escape(zip_relative_path),
+        markdown::html_escape(internal_file),
+    );
+
+    (StatusCode::UNAUTHORIZED, Html(html)).into_response()
+}
+
+async fn handle_zip_directory(state: &AppState, zip_relative_path: &str, internal_dir: &str, all_entries: &[zip_handler::ZipEntry], sort: ListingSort, view: Option<ViewMode>) -> Response {
+    let listing = zip_directory_items(all_entries, zip_relative_path, internal_dir, &sort, state.name_order, &state.search_index);
+
+    let mut html = String::from(r#"<!DOCTYPE html>
+<html><head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>Zip Contents</title>
+<script src="/__reload__.js"></script>
+<script src="/__position__.js"></script>
+<script src="/__listing__.js"></script>
+<script src="/__palette__.js"></script>
+<style>
+* { margin: 0; padding: 0; box-sizing: border-box; }
+body {
+    font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', 'Noto Sans', Helvetica, Arial, sans-serif;
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    min-height: 100vh;
+    padding: 2rem;
+    color: #333;
+}
+.container {
+    max-width: 900px;
+    margin: 0 auto;
+    background: rgba(255, 255, 255, 0.95);
+    backdrop-filter: blur(10px);
+    border-radius: 20px;
+    box-shadow: 0 20px 60px rgba(0, 0, 0, 0.3);
+    padding: 2.5rem;
+    animation: fadeIn 0.5s ease;
+}
+@keyframes fadeIn {
+    from { opacity: 0; transform: translateY(20px); }
+    to { opacity: 1; transform: translateY(0); }
+}
+h1 {
+    font-size: 2rem;
+    font-weight: 700;
+    margin-bottom: 1.5rem;
     color: #667eea;
     border-bottom: 3px solid #667eea;
     padding-bottom: 0.75rem;
@@ -846,6 +1563,29 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
     font-weight: 600;
     color: #667eea;
 }
+.continue {
+    margin-bottom: 1.25rem;
+    padding: 0.5rem 0.75rem;
+    background: #f5f3ff;
+    border-radius: 12px;
+}
+.continue-title {
+    padding: 0.25rem 0.5rem;
+    color: #667eea;
+    font-weight: 600;
+    font-size: 0.9rem;
+}
+.continue a {
+    padding: 0.5rem 0.75rem;
+}
+.continue .meta {
+    margin-left: auto;
+    padding-left: 1rem;
+    color: #9ca3af;
+    font-size: 0.8rem;
+    white-space: nowrap;
+}
+.continue a:hover .meta { color: rgba(255, 255, 255, 0.85); }
 .image-item {
     position: relative;
 }
@@ -859,14 +1599,90 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
     border-radius: 8px;
     margin-right: 1rem;
 }
+.summary {
+    margin: -0.75rem 0 1.25rem;
+    color: #6b7280;
+    font-size: 0.9rem;
+}
+.meta {
+    margin-left: auto;
+    padding-left: 1rem;
+    color: #9ca3af;
+    font-size: 0.8rem;
+    text-align: right;
+    white-space: nowrap;
+}
+.comment {
+    margin-left: 0.75rem;
+    color: #6b7280;
+    font-size: 0.85rem;
+    font-style: italic;
+}
+a:hover .meta, a:hover .comment { color: rgba(255, 255, 255, 0.85); }
+.toolbar {
+    margin-bottom: 1rem;
+}
+.toolbar .tool-button {
+    display: inline-flex;
+    padding: 0.5rem 1rem;
+    border-radius: 8px;
+    background: #f3f4f6;
+    color: #374151;
+    font-size: 0.9rem;
+}
+.toolbar .tool-button:hover {
+    transform: none;
+}
+.sort-bar {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 0.25rem;
+    margin-bottom: 0.75rem;
+    color: #6b7280;
+    font-size: 0.85rem;
+}
+.sort-bar a.sort-link {
+    padding: 0.3rem 0.75rem;
+    border-radius: 999px;
+    font-size: 0.85rem;
+}
+.sort-bar a.sort-link.active {
+    background: #eef2ff;
+    color: #667eea;
+    font-weight: 600;
+}
+.sort-bar a.sort-link:hover {
+    transform: none;
+}
+.listing-more {
+    margin-top: 1rem;
+    text-align: center;
+}
+.listing-more button {
+    padding: 0.6rem 1.5rem;
+    border: none;
+    border-radius: 8px;
+    background: #f3f4f6;
+    color: #374151;
+    font-size: 0.9rem;
+    cursor: pointer;
+}
+.listing-more button:hover {
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+}
 @media (max-width: 640px) {
     body { padding: 1rem; }
     .container { padding: 1.5rem; }
     h1 { font-size: 1.5rem; }
     .thumbnail { width: 60px; height: 60px; }
+    .meta { display: none; }
 }
 </style>
-</head><body><div class="container">"#);
+"#);
+    html.push_str(VIEW_MODE_CSS);
+    html.push_str("</head><body><div class=\"container\">");
 
     let display_path = if internal_dir.is_empty() {
         format!("📦 {}", zip_relative_path)
@@ -875,15 +1691,41 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
     };
 
     html.push_str(&format!("<h1><span class=\"path\">{}</span></h1>", display_path));
-    html.push_str("<ul>");
+
+    // 画像があればリーダーで開くリンクを出す
+    if !reader::pages_in_archive(all_entries, zip_relative_path, internal_dir, state.name_order).is_empty() {
+        html.push_str(&format!(
+            "<div class=\"toolbar\"><a class=\"tool-button\" href=\"/__reader__/{}\">📖 リーダーで読む</a></div>",
+            markdown::html_escape(&reader::encode_url(&format!("{}::{}", zip_relative_path, internal_dir)))
+        ));
+    }
+
+    // アーカイブ全体のサマリー
+    let files: Vec<_> = all_entries.iter().filter(|e| !e.is_dir).collect();
+    let total_size: u64 = files.iter().map(|e| e.size).sum();
+    let total_compressed: Option<u64> = files.iter().map(|e| e.compressed_size).sum();
+    html.push_str(&format!(
+        "<div class=\"summary\">{} エントリ（{} ファイル · {} フォルダ） · 合計 {}{}</div>",
+        all_entries.len(),
+        files.len(),
+        all_entries.len() - files.len(),
+        format_size(total_size),
+        total_compressed
+            .map(|c| format!(" (圧縮後 {})", format_size(c)))
+            .unwrap_or_default()
+    ));
+    let gallery_key = format!("{}::{}", zip_relative_path, internal_dir);
+    html.push_str(&continue_reading_html(&state.positions, &gallery_key));
+    html.push_str(&list_controls_html(&sort));
+    html.push_str(&view_mode::listing_open_html(
+        &gallery_key,
+        ViewMode::auto(listing.image_paths.len(), listing.entries.len()),
+        view,
+    ));
 
     // 親ディレクトリへのリンク
     if !internal_dir.is_empty() {
-        let parent = if internal_dir.contains('/') {
-            internal_dir.rsplitn(2, '/').nth(1).unwrap()
-        } else {
-            ""
-        };
+        let parent = internal_dir.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
         let parent_link = if parent.is_empty() {
             format!("/{}::", zip_relative_path)
         } else {
@@ -895,58 +1737,21 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
         ));
     }
 
-    let mut image_paths = Vec::new();
-
-    for entry in entries {
-        let file_name = entry.name.rsplit('/').next().unwrap_or(&entry.name);
-        let link_path = format!("/{}::{}", zip_relative_path, entry.name);
-
-        // 画像ファイルの判定
-        let is_image = !entry.is_dir && (
-            file_name.ends_with(".jpg") || file_name.ends_with(".jpeg") ||
-            file_name.ends_with(".png") || file_name.ends_with(".gif") ||
-            file_name.ends_with(".webp") || file_name.ends_with(".svg") ||
-            file_name.ends_with(".JPG") || file_name.ends_with(".JPEG") ||
-            file_name.ends_with(".PNG") || file_name.ends_with(".GIF") ||
-            file_name.ends_with(".WEBP") || file_name.ends_with(".SVG")
-        );
-
-        if is_image {
-            image_paths.push(link_path.clone());
-        }
-
-        let is_html_zip = !entry.is_dir && (file_name.ends_with(".html") || file_name.ends_with(".htm") ||
-            file_name.ends_with(".HTML") || file_name.ends_with(".HTM"));
-
-        let (icon, icon_class, link_class) = if entry.is_dir {
-            ("📁", "dir", "")
-        } else if file_name.ends_with(".md") || file_name.ends_with(".mkd") {
-            ("📝", "file", " markdown")
-        } else if is_image {
-            ("🖼️", "file", "")
-        } else if is_html_zip {
-            ("🌐", "file", " html-file")
-        } else {
-            ("📄", "file", "")
-        };
-
-        if is_image {
-            html.push_str(&format!(
-                "<li class=\"image-item\"><a href=\"javascript:void(0)\" onclick=\"openModal('{}', {})\" class=\"{}\"><img src=\"{}\" class=\"thumbnail\" alt=\"{}\" loading=\"lazy\"><span>{}</span></a></li>",
-                link_path, image_paths.len() - 1, link_class.trim(), link_path, file_name, file_name
-            ));
-        } else {
-            html.push_str(&format!(
-                "<li><a href=\"{}\" class=\"{}\"><span class=\"icon {}\">{}</span>{}</a></li>",
-                link_path, link_class.trim(), icon_class, icon, file_name
-            ));
-        }
+    // 最初のページだけ埋め込み、残りは /__list__ からスクロールに合わせて読み込む
+    for (entry, image_index) in listing.page(0, LISTING_PAGE_SIZE) {
+        html.push_str(&zip_row_html(zip_relative_path, entry, image_index));
     }
 
-    html.push_str("</ul></div>");
+    html.push_str("</ul>");
+    html.push_str(&listing_more_html(listing.entries.len()));
+    html.push_str("</div>");
+    html.push_str(&format!(
+        "<script>mvuListing.init({}, []);</script>",
+        listing.init_json(&gallery_key, &sort)
+    ));
 
     // 画像モーダル追加（画像がある場合のみ）
-    if !image_paths.is_empty() {
+    if !listing.image_paths.is_empty() {
         html.push_str(r#"<div id="imageModal" class="modal" style="display: none; position: fixed; top: 0; left: 0; width: 100%; height: 100%; background: rgba(0, 0, 0, 0.9); z-index: 10000; align-items: center; justify-content: center;">
     <div class="modal-content" style="position: relative; max-width: 90%; max-height: 90%; display: flex; align-items: center; justify-content: center;">
         <a id="modalLink" style="position: absolute; top: 20px; right: 80px; background: rgba(0, 0, 0, 0.5); color: white; padding: 0.5rem 1rem; border-radius: 8px; text-decoration: none; font-size: 0.9rem;" href="" target="_blank">元ファイル</a>
@@ -957,10 +1762,17 @@ a:hover .icon { transform: scale(1.2); transition: transform 0.2s; }
     </div>
 </div>
 <script>
+const galleryKey = "#);
+        html.push_str(&serde_json::to_string(&gallery_key).unwrap_or_else(|_| "\"\"".to_string()));
+        html.push_str(r#";
 const imagePaths = "#);
-        html.push_str(&serde_json::to_string(&image_paths).unwrap_or_else(|_| "[]".to_string()));
+        html.push_str(&listing.first_images_json());
+        html.push_str(r#";
+const imageTotal = "#);
+        html.push_str(&listing.image_paths.len().to_string());
         html.push_str(r#";
 let currentImageIndex = 0;
+mvuListing.setImages(imagePaths);
 function openModal(imagePath, index) {
     currentImageIndex = index;
     updateModalImage();
@@ -970,21 +1782,27 @@ function closeModal() {
     document.getElementById('imageModal').style.display = 'none';
 }
 function updateModalImage() {
-    const imagePath = imagePaths[currentImageIndex];
-    document.getElementById('modalImage').src = imagePath;
-    document.getElementById('modalLink').href = imagePath;
+    const index = currentImageIndex;
+    mvuListing.image(index).then(function(imagePath) {
+        if (index !== currentImageIndex || !imagePath) return;
+        document.getElementById('modalImage').src = imagePath;
+        document.getElementById('modalLink').href = imagePath;
+    });
+    mvuPosition.save(galleryKey, { view: 'gallery', page: index, total: imageTotal });
 }
 function nextImage() {
-    currentImageIndex = (currentImageIndex + 1) % imagePaths.length;
+    currentImageIndex = (currentImageIndex + 1) % imageTotal;
     updateModalImage();
 }
 function prevImage() {
-    currentImageIndex = (currentImageIndex - 1 + imagePaths.length) % imagePaths.length;
+    currentImageIndex = (currentImageIndex - 1 + imageTotal) % imageTotal;
     updateModalImage();
 }
 document.getElementById('imageModal').addEventListener('click', function(e) {
     if (e.target === this) closeModal();
 });
+const resumeImage = location.hash.match(/^#image=(\d+)$/);
+if (resumeImage) openModal(null, Math.min(parseInt(resumeImage[1], 10), imageTotal - 1));
 document.addEventListener('keydown', function(e) {
     const modal = document.getElementById('imageModal');
     if (modal.style.display === 'flex') {
@@ -997,59 +1815,201 @@ document.addEventListener('keydown', function(e) {
     }
 
     html.push_str("</body></html>");
-    Html(html).into_response()
+    with_sort_cookie(Html(html).into_response(), &sort)
 }
 
-async fn handle_zip_file(zip_path: &PathBuf, zip_relative_path: &str, internal_file: &str, base_dir: &PathBuf) -> Response {
-    // zipからファイルを抽出
-    let contents = match zip_handler::read_file_from_zip(zip_path, internal_file) {
-        Ok(data) => data,
-        Err(e) => {
-            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to extract file: {}", e)).into_response();
-        }
+pub async fn handle_reader_root(State(state): State<AppState>) -> Response {
+    reader_response(&state, "").await
+}
+
+pub async fn handle_reader(State(state): State<AppState>, Path(path): Path<String>) -> Response {
+    reader_response(&state, &path).await
+}
+
+/// 画像ディレクトリ / アーカイブ (path/to/comic.cbz, path/to/a.zip::chapter1) をリーダーで表示する
+async fn reader_response(state: &AppState, path: &str) -> Response {
+    let (source_path, internal_dir) = match path.find("::") {
+        Some(pos) => (&path[..pos], Some(path[pos + 2..].trim_end_matches('/'))),
+        None => (path.trim_end_matches('/'), None),
     };
 
-    // ファイル拡張子で処理を分ける
-    let extension = internal_file.rsplit('.').next().and_then(|ext| {
-        if ext.contains('/') { None } else { Some(ext) }
-    });
+    let canonical_path = match state.base_dir.join(source_path).canonicalize() {
+        Ok(p) => p,
+        Err(_) => return handle_not_found(path).await,
+    };
 
-    // マークダウンファイルの場合
-    if matches!(extension, Some("md") | Some("mkd")) {
-        // 一時ファイルに書き出してunidocで処理
-        let temp_file = std::env::temp_dir().join(format!("mvu_zip_{}.md", internal_file.replace('/', "_")));
-        if let Err(e) = std::fs::write(&temp_file, &contents) {
-            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write temp file: {}", e)).into_response();
-        }
+    if !canonical_path.starts_with(&*state.base_dir) {
+        return (StatusCode::FORBIDDEN, "Access denied").into_response();
+    }
 
-        let full_path = format!("{}/{}", zip_relative_path, internal_file);
-        match markdown::convert_to_html(&temp_file, &full_path, base_dir).await {
-            Ok(html) => {
-                std::fs::remove_file(&temp_file).ok();
-                Html(html).into_response()
-            },
+    let (prev, next) = reader::siblings(&state.base_dir, source_path, state.name_order);
+
+    // 前回の続きのページ（URLに #p= があればそちらが優先される）
+    let key = match internal_dir {
+        Some(internal_dir) if !internal_dir.is_empty() => format!("{}::{}", source_path, internal_dir),
+        _ => source_path.to_string(),
+    };
+    let start_page = state
+        .positions
+        .get(&key)
+        .filter(|p| p.view == ReadingView::Reader)
+        .and_then(|p| p.page)
+        .unwrap_or(0);
+
+    let book = if archive::is_archive_file(&canonical_path) {
+        let index = match state.archive_cache.get(&canonical_path) {
+            Ok(index) => index,
             Err(e) => {
-                std::fs::remove_file(&temp_file).ok();
-                (StatusCode::INTERNAL_SERVER_ERROR, format!("Markdown conversion failed: {}", e)).into_response()
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read archive: {}", e)).into_response();
             }
+        };
+        let internal_dir = internal_dir.unwrap_or("");
+        ReaderBook {
+            title: if internal_dir.is_empty() { source_path.to_string() } else { format!("{}::{}", source_path, internal_dir) },
+            back_link: format!("/{}::{}", source_path, internal_dir),
+            pages: reader::pages_in_archive(index.entries(), source_path, internal_dir, state.name_order),
+            key,
+            start_page,
+            prev,
+            next,
+        }
+    } else if canonical_path.is_dir() && internal_dir.is_none() {
+        let pages = match reader::pages_in_dir(&canonical_path, source_path, state.name_order) {
+            Ok(pages) => pages,
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Cannot read directory").into_response(),
+        };
+        ReaderBook {
+            title: if source_path.is_empty() { "Home".to_string() } else { source_path.to_string() },
+            back_link: format!("/{}", source_path),
+            pages,
+            key,
+            start_page,
+            prev,
+            next,
         }
     } else {
-        // その他のファイルはそのまま返す
-        contents.into_response()
+        return (StatusCode::BAD_REQUEST, "Not a directory or archive").into_response();
+    };
+
+    Html(reader::render(&book)).into_response()
+}
+
+/// 一覧の並び替えリンク（選択中の列をもう一度押すと向きが反転）
+fn sort_bar_html(sort: &ListingSort) -> String {
+    let links: Vec<String> = SortKey::ALL
+        .into_iter()
+        .map(|key| {
+            let (class, arrow) = match (key == sort.key, sort.descending) {
+                (true, true) => (" active", " ▼"),
+                (true, false) => (" active", " ▲"),
+                (false, _) => ("", ""),
+            };
+            format!(
+                "<a href=\"{}\" class=\"sort-link{}\">{}{}</a>",
+                markdown::html_escape(&sort.query_for(key)),
+                class,
+                key.label(),
+                arrow
+            )
+        })
+        .collect();
+    format!("<div class=\"sort-bar\"><span>並び替え:</span>{}</div>", links.concat())
+}
+
+/// 絞り込み・並び替え・表示モードの切り替え
+fn list_controls_html(sort: &ListingSort) -> String {
+    format!(
+        "<div class=\"list-controls\"><input type=\"search\" id=\"listing-filter\" class=\"listing-filter\" placeholder=\"絞り込み ( / )\" aria-label=\"filter\">{}{}</div>",
+        sort_bar_html(sort),
+        view_mode::switch_html()
+    )
+}
+
+/// `?view=list|grid|gallery` で表示モードを指定する
+fn requested_view(params: &HashMap<String, String>) -> Option<ViewMode> {
+    params.get("view").and_then(|value| ViewMode::parse(value))
+}
+
+/// 一覧の種類・サイズ・更新日時の列
+fn columns_html(fields: &SortFields) -> String {
+    let (kind, size) = if fields.is_dir {
+        ("フォルダ".to_string(), String::new())
+    } else {
+        (fields.kind().to_uppercase(), format_size(fields.size))
+    };
+    format!(
+        "<span class=\"col col-type\">{}</span><span class=\"col col-size\">{}</span><span class=\"col col-mtime\">{}</span>",
+        markdown::html_escape(&kind),
+        size,
+        fields.modified.map(format_timestamp).unwrap_or_default()
+    )
+}
+
+fn with_sort_cookie(mut response: Response, sort: &ListingSort) -> Response {
+    if let Some(value) = sort.cookie().and_then(|cookie| cookie.parse().ok()) {
+        response.headers_mut().append(header::SET_COOKIE, value);
     }
+    response
 }
 
-pub async fn handle_reload_events(
-    State(state): State<AppState>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let rx = state.reload_tx.subscribe();
-    let stream = BroadcastStream::new(rx).map(|_| Ok(Event::default().data("reload")));
+/// 一覧の「続きから読む」（このディレクトリ以下で最近読んだもの）
+fn continue_reading_html(positions: &PositionStore, dir_key: &str) -> String {
+    let recent = positions.recent_under(dir_key, 5);
+    if recent.is_empty() {
+        return String::new();
+    }
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    let mut html = String::from("<div class=\"continue\"><div class=\"continue-title\">▶ 続きから読む</div><ul>");
+    for (path, position) in recent {
+        let (icon, detail) = match position.view {
+            ReadingView::Markdown => ("📝", position.progress.map(|p| format!("{}%", p))),
+            ReadingView::Reader => ("📖", position.page.map(|p| format!("p. {} / {}", p + 1, position.total.unwrap_or(0)))),
+            ReadingView::Gallery => ("🖼️", position.page.map(|p| format!("画像 {} / {}", p + 1, position.total.unwrap_or(0)))),
+        };
+        let label = path
+            .strip_prefix(dir_key)
+            .map(|rest| rest.trim_start_matches('/'))
+            .filter(|rest| !rest.is_empty())
+            .unwrap_or("このディレクトリ");
+        let meta: Vec<String> = detail.into_iter().chain(Some(format_timestamp(position.updated))).collect();
+
+        html.push_str(&format!(
+            "<li><a href=\"{}\"><span class=\"icon\">{}</span>{}<span class=\"meta\">{}</span></a></li>",
+            markdown::html_escape(&position.resume_url(&reader::encode_url(&path))),
+            icon,
+            markdown::html_escape(label),
+            meta.join(" · ")
+        ));
+    }
+    html.push_str("</ul></div>");
+    html
 }
 
-pub async fn handle_reload_js() -> Response {
-    let js = include_str!("reload.js");
+#[derive(Deserialize)]
+pub struct PositionUpdate {
+    path: String,
+    #[serde(flatten)]
+    position: ReadingPosition,
+}
+
+pub async fn handle_position(State(state): State<AppState>, Json(update): Json<PositionUpdate>) -> Response {
+    // 存在するパス（アーカイブ内ならアーカイブ自体）だけ保存する
+    let source = update.path.split("::").next().unwrap_or("").trim_start_matches('/');
+    match state.base_dir.join(source).canonicalize() {
+        Ok(p) if p.starts_with(&*state.base_dir) => {}
+        _ => return (StatusCode::NOT_FOUND, "Not found").into_response(),
+    }
+
+    let positions = state.positions.clone();
+    match tokio::task::spawn_blocking(move || positions.set(&update.path, update.position)).await {
+        Ok(Ok(())) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save position").into_response(),
+    }
+}
+
+pub async fn handle_position_js() -> Response {
+    let js = include_str!("position.js");
     (
         StatusCode::OK,
         [("Content-Type", "application/javascript")],
@@ -1058,51 +2018,796 @@ pub async fn handle_reload_js() -> Response {
         .into_response()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-
-    fn create_test_state(base_dir: PathBuf) -> AppState {
-        let (reload_tx, _) = broadcast::channel(100);
-        AppState {
-            base_dir: Arc::new(base_dir),
-            reload_tx,
-        }
+/// 一覧のサムネイル画像のURL（縮小できない形式は元画像）
+fn thumbnail_src(link_path: &str) -> String {
+    if thumbnail::is_thumbnailable(link_path) {
+        format!("/__thumb__/{}", link_path)
+    } else {
+        format!("/{}", link_path)
     }
+}
 
-    #[tokio::test]
-    async fn test_path_traversal_protection() {
-        let temp_dir = std::env::temp_dir().join("mvu_test_traversal");
-        fs::create_dir_all(&temp_dir).unwrap();
+/// アーカイブエントリのメタデータ（サイズ・圧縮方式・更新日時・CRC・コメント）
+fn zip_entry_meta_html(entry: &zip_handler::ZipEntry) -> String {
+    if entry.is_dir {
+        return String::new();
+    }
 
-        // temp_dirの親ディレクトリに実際にファイルを作成
-        let parent_dir = temp_dir.parent().unwrap();
-        let target_file = parent_dir.join("secret.txt");
-        fs::write(&target_file, "secret data").unwrap();
+    let mut parts = vec![format_size(entry.size)];
+    match (&entry.compression, entry.compressed_size) {
+        (Some(method), Some(compressed)) if entry.size > 0 => {
+            parts.push(format!("{} {}%", method, compressed * 100 / entry.size));
+        }
+        (Some(method), _) => parts.push(method.clone()),
+        _ => {}
+    }
+    if let Some(modified) = entry.modified {
+        parts.push(format_timestamp(modified));
+    }
+    if let Some(crc) = entry.crc32 {
+        parts.push(format!("CRC {:08x}", crc));
+    }
 
-        let state = create_test_state(temp_dir.clone());
+    let comment = entry
+        .comment
+        .as_ref()
+        .map(|c| format!("<span class=\"comment\">💬 {}</span>", markdown::html_escape(c)))
+        .unwrap_or_default();
 
-        // パストラバーサル攻撃の試み: 親ディレクトリのファイルにアクセス
-        let response = handle_path(State(state), Path("../secret.txt".to_string()), Query(HashMap::new())).await;
-        let status = response.status();
+    format!("{}<span class=\"meta\">{}</span>", comment, parts.join(" · "))
+}
 
-        // base_dir外のアクセスは403 FORBIDDENになるべき
-        assert_eq!(status, StatusCode::FORBIDDEN);
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
 
-        // クリーンアップ
-        fs::remove_file(&target_file).ok();
-        fs::remove_dir_all(&temp_dir).ok();
+/// UNIX秒を `YYYY-MM-DD HH:MM` (UTC) に整形する
+fn format_timestamp(secs: i64) -> String {
+    match time::OffsetDateTime::from_unix_timestamp(secs) {
+        Ok(t) => format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            t.year(), u8::from(t.month()), t.day(), t.hour(), t.minute()
+        ),
+        Err(_) => String::from("-"),
     }
+}
 
-    #[tokio::test]
-    async fn test_nonexistent_path() {
-        let temp_dir = std::env::temp_dir().join("mvu_test_nonexist");
+async fn handle_zip_file(state: &AppState, zip_path: &std::path::Path, index: &archive::ArchiveIndex, zip_relative_path: &str, internal_file: &str, password: Option<&str>) -> Response {
+    // アーカイブからファイルを抽出
+    let contents = match archive::read_file(zip_path, index, internal_file, password) {
+        Ok(data) => data,
+        Err(_) if password.is_some() => {
+            // 保存済みのパスワードで開けない場合は再入力してもらう
+            return handle_zip_password_prompt(zip_relative_path, internal_file, Some("パスワードが違います"));
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to extract file: {}", e)).into_response();
+        }
+    };
+
+    // ファイル拡張子で処理を分ける
+    let extension = internal_file.rsplit('.').next().and_then(|ext| {
+        if ext.contains('/') { None } else { Some(ext) }
+    });
+
+    // マークダウンファイルの場合
+    if matches!(extension, Some("md") | Some("mkd")) {
+        let source = String::from_utf8_lossy(&contents);
+        let full_path = format!("{}/{}", zip_relative_path, internal_file);
+        // アーカイブ内の文書はウィキリンクを相対パスのまま扱い、リンク元も出さない
+        let vault = Vault {
+            base_dir: &state.base_dir,
+            current: &full_path,
+            file_index: None,
+            link_index: None,
+        };
+        match markdown::convert_source_to_html(&source, None, &vault, state.name_order, state.toc_depth).await {
+            Ok(html) => Html(html).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Markdown conversion failed: {}", e)).into_response(),
+        }
+    } else {
+        // その他のファイルはそのまま返す
+        contents.into_response()
+    }
+}
+
+pub async fn handle_download(State(state): State<AppState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let relative_path = params.get("path").cloned().unwrap_or_default();
+
+    let canonical_dir = match state.base_dir.join(&relative_path).canonicalize() {
+        Ok(p) => p,
+        Err(_) => return handle_not_found(&relative_path).await,
+    };
+
+    if !canonical_dir.starts_with(&*state.base_dir) {
+        return (StatusCode::FORBIDDEN, "Access denied").into_response();
+    }
+
+    if !canonical_dir.is_dir() {
+        return (StatusCode::BAD_REQUEST, "Not a directory").into_response();
+    }
+
+    // select: ディレクトリ直下の名前のJSON配列（省略時はディレクトリ全体）
+    let selection: Option<Vec<String>> = match params.get("select") {
+        Some(json) => match serde_json::from_str(json) {
+            Ok(names) => Some(names),
+            Err(_) => return (StatusCode::BAD_REQUEST, "Invalid selection").into_response(),
+        },
+        None => None,
+    };
+
+    let base_dir = state.base_dir.clone();
+    let dir = canonical_dir.clone();
+    let entries = match tokio::task::spawn_blocking(move || {
+        download::collect_entries(&base_dir, &dir, selection.as_deref())
+    })
+    .await
+    {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(e)) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to collect files").into_response(),
+    };
+
+    let archive_name = format!(
+        "{}.zip",
+        canonical_dir.file_name().and_then(|n| n.to_str()).unwrap_or("download")
+    );
+    let disposition = format!(
+        "attachment; filename=\"download.zip\"; filename*=UTF-8''{}",
+        utf8_percent_encode(&archive_name, FILENAME_ENCODE_SET)
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(download::zip_stream(entries)),
+    )
+        .into_response()
+}
+
+/// 一覧の1行を描画する関数（ディレクトリ / アーカイブ）
+type RowRenderer<'a> = &'a dyn Fn(&zip_handler::ZipEntry, Option<usize>) -> String;
+
+#[derive(Serialize)]
+struct ListingEntryJson<'a> {
+    name: &'a str,
+    is_dir: bool,
+    size: u64,
+    modified: Option<i64>,
+    // 一覧ページに差し込む行
+    html: String,
+}
+
+/// 一覧API: `/__list__?path=dir&offset=200&limit=200&sort=name&order=asc`
+/// `path` はディレクトリか `a.zip::dir`。`kind=images` なら画像のパスだけを通し番号の範囲で返す
+pub async fn handle_listing(State(state): State<AppState>, Query(params): Query<HashMap<String, String>>, headers: HeaderMap) -> Response {
+    let path = params.get("path").map(|p| p.trim_matches('/')).unwrap_or("").to_string();
+    let sort = ListingSort::from_request(&params, &headers);
+    let offset: usize = params.get("offset").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(LISTING_PAGE_SIZE)
+        .min(LISTING_MAX_LIMIT);
+
+    let (source_path, internal_dir) = match path.find("::") {
+        Some(pos) => (&path[..pos], Some(path[pos + 2..].trim_end_matches('/'))),
+        None => (path.as_str(), None),
+    };
+
+    let canonical_path = match state.base_dir.join(source_path).canonicalize() {
+        Ok(p) => p,
+        Err(_) => return handle_not_found(&path).await,
+    };
+
+    if !canonical_path.starts_with(&*state.base_dir) {
+        return (StatusCode::FORBIDDEN, "Access denied").into_response();
+    }
+
+    // 行の描画は一覧ページと共通
+    let zip_row = |entry: &zip_handler::ZipEntry, image_index| zip_row_html(source_path, entry, image_index);
+    let directory_row = |entry: &zip_handler::ZipEntry, image_index| directory_row_html(source_path, entry, image_index);
+    let (listing, row_html): (ListingItems, RowRenderer) = match internal_dir {
+        Some(internal_dir) if archive::is_archive_file(&canonical_path) => {
+            let index = match state.archive_cache.get(&canonical_path) {
+                Ok(index) => index,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read archive: {}", e)).into_response();
+                }
+            };
+            (zip_directory_items(index.entries(), source_path, internal_dir, &sort, state.name_order, &state.search_index), &zip_row)
+        }
+        None if canonical_path.is_dir() => match directory_items(&canonical_path, source_path, &sort, state.name_order, &state.link_index).await {
+            Ok(listing) => (listing, &directory_row),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        },
+        _ => return (StatusCode::BAD_REQUEST, "Not a directory or archive").into_response(),
+    };
+
+    if params.get("kind").map(String::as_str) == Some("images") {
+        let images: Vec<&String> = listing.image_paths.iter().skip(offset).take(limit).collect();
+        return Json(serde_json::json!({
+            "total": listing.image_paths.len(),
+            "offset": offset,
+            "images": images,
+        }))
+        .into_response();
+    }
+
+    let items: Vec<ListingEntryJson> = listing
+        .page(offset, limit)
+        .map(|(entry, image_index)| ListingEntryJson {
+            name: entry.name.rsplit('/').next().unwrap_or(&entry.name),
+            is_dir: entry.is_dir,
+            size: entry.size,
+            modified: entry.modified,
+            html: row_html(entry, image_index),
+        })
+        .collect();
+
+    Json(serde_json::json!({
+        "total": listing.entries.len(),
+        "offset": offset,
+        "image_total": listing.image_paths.len(),
+        "items": items,
+    }))
+    .into_response()
+}
+
+pub async fn handle_listing_js() -> Response {
+    let js = include_str!("listing.js");
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/javascript")],
+        js,
+    )
+        .into_response()
+}
+
+pub async fn handle_thumbnail(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap) -> Response {
+    // 画像ファイル (path/to/img.jpg) かアーカイブ内の画像 (path/to/a.zip::img.jpg)
+    let (source_path, internal_path) = match path.find("::") {
+        Some(pos) => (&path[..pos], Some(path[pos + 2..].to_string())),
+        None => (path.as_str(), None),
+    };
+
+    let canonical_path = match state.base_dir.join(source_path).canonicalize() {
+        Ok(p) => p,
+        Err(_) => return handle_not_found(&path).await,
+    };
+
+    if !canonical_path.starts_with(&*state.base_dir) {
+        return (StatusCode::FORBIDDEN, "Access denied").into_response();
+    }
+
+    let original = (
+        StatusCode::TEMPORARY_REDIRECT,
+        [(header::LOCATION, utf8_percent_encode(&format!("/{}", path), LOCATION_ENCODE_SET).to_string())],
+    );
+
+    let name = internal_path.as_deref().unwrap_or(source_path);
+    if !thumbnail::is_thumbnailable(name) {
+        return original.into_response();
+    }
+
+    // 暗号化エントリはキャッシュより先にこのセッションのパスワードを確かめる
+    // （ディスクのキャッシュや304で、パスワードを知らないセッションに中身が見えないように）
+    let mut encrypted = false;
+    let mut password = None;
+    if let Some(internal) = &internal_path {
+        let index = match state.archive_cache.get(&canonical_path) {
+            Ok(index) => index,
+            Err(_) => return handle_not_found(&path).await,
+        };
+        encrypted = index.entries().iter().any(|e| &e.name == internal && e.encrypted);
+        if encrypted {
+            password = session::session_id(&headers).and_then(|id| state.zip_passwords.get(&id, &canonical_path));
+            if password.is_none() {
+                return (StatusCode::UNAUTHORIZED, "Password required").into_response();
+            }
+        }
+    }
+
+    let metadata = match std::fs::metadata(&canonical_path) {
+        Ok(m) => m,
+        Err(_) => return handle_not_found(&path).await,
+    };
+    let key = ThumbnailCache::cache_key(
+        &format!("{}::{}", canonical_path.display(), internal_path.as_deref().unwrap_or("")),
+        metadata.modified().ok(),
+        metadata.len(),
+    );
+    let etag = format!("\"{}\"", key);
+
+    if !encrypted && headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) == Some(etag.as_str()) {
+        return StatusCode::NOT_MODIFIED.into_response();
+    }
+
+    let thumbnails = state.thumbnails.clone();
+    let archive_cache = state.archive_cache.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let load = || match &internal_path {
+            Some(internal) => {
+                let index = archive_cache.get(&canonical_path)?;
+                archive::read_file(&canonical_path, &index, internal, password.as_deref())
+            }
+            None => std::fs::read(&canonical_path).map_err(|e| format!("Failed to read image: {}", e)),
+        };
+        // 暗号化エントリのサムネイルはディスクに残さない
+        if encrypted {
+            thumbnail::generate(&load()?)
+        } else {
+            thumbnails.get_or_create(&key, load)
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(data)) if encrypted => (
+            [
+                (header::CONTENT_TYPE, thumbnail::content_type(&data).to_string()),
+                (header::CACHE_CONTROL, "no-store".to_string()),
+            ],
+            data,
+        )
+            .into_response(),
+        Ok(Ok(data)) => (
+            [
+                (header::CONTENT_TYPE, thumbnail::content_type(&data).to_string()),
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, "no-cache".to_string()),
+            ],
+            data,
+        )
+            .into_response(),
+        // 縮小できない画像は元画像にフォールバック
+        _ => original.into_response(),
+    }
+}
+
+pub async fn handle_reload_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.reload_tx.subscribe();
+    let stream = BroadcastStream::new(rx).map(|_| Ok(Event::default().data("reload")));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// 全文検索・タグ一覧など、索引から作るページの共通スタイル
+const INDEX_PAGE_CSS: &str = r#"
+* { margin: 0; padding: 0; box-sizing: border-box; }
+body {
+    font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', 'Noto Sans', Helvetica, Arial, sans-serif;
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    min-height: 100vh;
+    padding: 2rem;
+    color: #333;
+}
+.container {
+    max-width: 900px;
+    margin: 0 auto;
+    background: rgba(255, 255, 255, 0.95);
+    border-radius: 20px;
+    box-shadow: 0 20px 60px rgba(0, 0, 0, 0.3);
+    padding: 2.5rem;
+}
+h1 {
+    margin-bottom: 1.5rem;
+    font-size: 1.75rem;
+    color: #374151;
+}
+h1 a { color: inherit; text-decoration: none; }
+form {
+    display: flex;
+    gap: 0.5rem;
+    margin-bottom: 1rem;
+}
+input[type="search"] {
+    flex: 1;
+    padding: 0.75rem 1rem;
+    border: 1px solid #e5e7eb;
+    border-radius: 10px;
+    font-size: 1rem;
+}
+input[type="search"]:focus {
+    outline: none;
+    border-color: #667eea;
+    box-shadow: 0 0 0 3px rgba(102, 126, 234, 0.2);
+}
+button {
+    padding: 0.75rem 1.5rem;
+    border: none;
+    border-radius: 10px;
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+    font-size: 1rem;
+    cursor: pointer;
+}
+.search-status {
+    margin-bottom: 1rem;
+    color: #6b7280;
+    font-size: 0.9rem;
+}
+.results { list-style: none; }
+.results li {
+    padding: 1rem 0;
+    border-bottom: 1px solid #e5e7eb;
+}
+.results li:last-child { border-bottom: none; }
+.result-title {
+    color: #4f46e5;
+    font-size: 1.1rem;
+    font-weight: 600;
+    text-decoration: none;
+}
+.result-title:hover { text-decoration: underline; }
+.result-path {
+    margin-top: 0.15rem;
+    color: #9ca3af;
+    font-size: 0.8rem;
+    font-family: 'Monaco', 'Courier New', monospace;
+    word-break: break-all;
+}
+.result-heading {
+    display: inline-block;
+    margin-top: 0.35rem;
+    color: #667eea;
+    font-size: 0.85rem;
+    text-decoration: none;
+}
+.snippet {
+    margin-top: 0.35rem;
+    color: #4b5563;
+    font-size: 0.9rem;
+    line-height: 1.6;
+    word-break: break-word;
+}
+.snippet mark {
+    background: #fef08a;
+    color: inherit;
+    border-radius: 2px;
+}
+.tag-cloud {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 0.5rem;
+    list-style: none;
+}
+.tag-cloud a, .result-tags a {
+    display: inline-block;
+    padding: 0.3rem 0.8rem;
+    border-radius: 999px;
+    background: #eef2ff;
+    color: #4f46e5;
+    text-decoration: none;
+    font-size: 0.9rem;
+}
+.tag-cloud a:hover, .result-tags a:hover {
+    background: #667eea;
+    color: white;
+}
+.tag-count {
+    margin-left: 0.35rem;
+    color: #9ca3af;
+    font-size: 0.8rem;
+}
+.tag-cloud a:hover .tag-count { color: rgba(255, 255, 255, 0.85); }
+.result-tags {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 0.35rem;
+    margin-top: 0.4rem;
+}
+.result-tags a {
+    padding: 0.1rem 0.6rem;
+    font-size: 0.8rem;
+}
+.result-date {
+    color: #6b7280;
+    font-size: 0.85rem;
+    margin-left: 0.5rem;
+    font-weight: normal;
+}
+.sort-links {
+    margin-bottom: 1rem;
+    font-size: 0.9rem;
+    color: #6b7280;
+}
+.sort-links a {
+    color: #667eea;
+    text-decoration: none;
+    margin-left: 0.5rem;
+}
+.sort-links a.active {
+    font-weight: 600;
+    text-decoration: underline;
+}
+.back-link {
+    display: inline-block;
+    margin-bottom: 1rem;
+    color: #667eea;
+    text-decoration: none;
+    font-size: 0.9rem;
+}
+@media (max-width: 640px) {
+    body { padding: 1rem; }
+    .container { padding: 1.5rem; }
+}
+"#;
+
+/// 全文検索のページ: `/__search__?q=install`
+pub async fn handle_search(State(state): State<AppState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let query = params.get("q").map(|q| q.trim()).unwrap_or("");
+
+    let results = if query.is_empty() {
+        String::new()
+    } else if !state.search_index.is_ready() {
+        INDEX_NOT_READY_HTML.to_string()
+    } else {
+        let hits = state.search_index.search(query, search::MAX_RESULTS);
+        let mut html = format!("<p class=\"search-status\">{} 件</p><ul class=\"results\">", hits.len());
+        for hit in &hits {
+            let url = format!("/{}", reader::encode_url(&hit.path));
+            let heading = match (&hit.heading, &hit.anchor) {
+                (Some(heading), Some(anchor)) => format!(
+                    "<a class=\"result-heading\" href=\"{}#{}\">§ {}</a>",
+                    markdown::html_escape(&url),
+                    markdown::html_escape(anchor),
+                    markdown::html_escape(heading)
+                ),
+                _ => String::new(),
+            };
+            let link = match &hit.anchor {
+                Some(anchor) => format!("{}#{}", url, anchor),
+                None => url,
+            };
+            html.push_str(&format!(
+                "<li><a class=\"result-title\" href=\"{}\">{}</a><div class=\"result-path\">{}</div>{}<div class=\"snippet\">{}</div></li>",
+                markdown::html_escape(&link),
+                markdown::html_escape(&hit.title),
+                markdown::html_escape(&hit.path),
+                heading,
+                hit.snippet
+            ));
+        }
+        html.push_str("</ul>");
+        html
+    };
+
+    Html(format!(r#"<!DOCTYPE html>
+<html><head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>{title}</title>
+<script src="/__palette__.js"></script>
+<style>{css}</style>
+</head><body><div class="container">
+<h1><a href="/">🔎 全文検索</a></h1>
+<form action="/__search__" method="get"><input type="search" name="q" value="{value}" placeholder="検索語を入力" autofocus><button type="submit">検索</button></form>
+{results}
+</div></body></html>"#,
+        title = if query.is_empty() { "検索".to_string() } else { format!("{} - 検索", markdown::html_escape(query)) },
+        css = INDEX_PAGE_CSS,
+        value = markdown::html_escape(query),
+        results = results,
+    ))
+    .into_response()
+}
+
+/// 索引から作るページ（全文検索以外）の共通の枠
+fn index_page_html(title: &str, heading: &str, content: &str) -> String {
+    format!(r#"<!DOCTYPE html>
+<html><head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>{}</title>
+<script src="/__palette__.js"></script>
+<style>{}</style>
+</head><body><div class="container">
+<h1>{}</h1>
+{}
+</div></body></html>"#, markdown::html_escape(title), INDEX_PAGE_CSS, heading, content)
+}
+
+const INDEX_NOT_READY_HTML: &str = "<p class=\"search-status\">索引を作成中です。しばらくしてから再読み込みしてください。</p>";
+
+/// タグの一覧: `/__tags__`
+pub async fn handle_tags(State(state): State<AppState>) -> Response {
+    let content = if !state.tag_index.is_ready() {
+        INDEX_NOT_READY_HTML.to_string()
+    } else {
+        let tags = state.tag_index.tags();
+        if tags.is_empty() {
+            "<p class=\"search-status\">front matter に <code>tags</code> のある文書はありません</p>".to_string()
+        } else {
+            let mut html = format!("<p class=\"search-status\">{} 個のタグ</p><ul class=\"tag-cloud\">", tags.len());
+            for (tag, count) in &tags {
+                html.push_str(&format!(
+                    "<li><a href=\"{}\">#{}<span class=\"tag-count\">{}</span></a></li>",
+                    markdown::html_escape(&tag_url(tag)),
+                    markdown::html_escape(tag),
+                    count
+                ));
+            }
+            html.push_str("</ul>");
+            html
+        }
+    };
+
+    Html(index_page_html("タグ", "<a href=\"/\">🏷️ タグ</a>", &content)).into_response()
+}
+
+/// タグの付いた文書: `/__tags__/rust?sort=title`
+pub async fn handle_tag(State(state): State<AppState>, Path(tag): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let sort = params.get("sort").and_then(|s| TagSort::parse(s)).unwrap_or_default();
+
+    let content = if !state.tag_index.is_ready() {
+        INDEX_NOT_READY_HTML.to_string()
+    } else {
+        let documents = state.tag_index.documents(&tag, sort, state.name_order);
+        let url = markdown::html_escape(&tag_url(&tag));
+        let sort_link = |value: &str, label: &str, active: bool| {
+            format!("<a href=\"{}?sort={}\"{}>{}</a>", url, value, if active { " class=\"active\"" } else { "" }, label)
+        };
+        let mut html = format!(
+            "<a class=\"back-link\" href=\"/__tags__\">← タグの一覧</a><p class=\"search-status\">{} 件</p><div class=\"sort-links\">並び順:{}{}</div><ul class=\"results\">",
+            documents.len(),
+            sort_link("date", "日付", sort == TagSort::Date),
+            sort_link("title", "タイトル", sort == TagSort::Title)
+        );
+        for document in &documents {
+            let date = document
+                .date
+                .as_ref()
+                .map(|date| format!("<span class=\"result-date\">📅 {}</span>", markdown::html_escape(date)))
+                .unwrap_or_default();
+            let tags: String = document
+                .tags
+                .iter()
+                .map(|t| format!("<a href=\"{}\">#{}</a>", markdown::html_escape(&tag_url(t)), markdown::html_escape(t)))
+                .collect();
+            html.push_str(&format!(
+                "<li><a class=\"result-title\" href=\"/{}\">{}</a>{}<div class=\"result-path\">{}</div><div class=\"result-tags\">{}</div></li>",
+                markdown::html_escape(&reader::encode_url(&document.path)),
+                markdown::html_escape(&document.title),
+                date,
+                markdown::html_escape(&document.path),
+                tags
+            ));
+        }
+        html.push_str("</ul>");
+        html
+    };
+
+    let heading = format!("<a href=\"/__tags__\">🏷️ #{}</a>", markdown::html_escape(&tag));
+    Html(index_page_html(&format!("#{} - タグ", tag), &heading, &content)).into_response()
+}
+
+/// 「ファイルへ移動」パレットの検索: `/__files__?q=rdme`
+pub async fn handle_file_search(State(state): State<AppState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let query = params.get("q").map(String::as_str).unwrap_or("");
+    let results: Vec<serde_json::Value> = state
+        .file_index
+        .search(query, file_index::MAX_RESULTS)
+        .into_iter()
+        .map(|m| {
+            serde_json::json!({
+                "url": format!("/{}", reader::encode_url(m.path.trim_end_matches('/'))),
+                "path": m.path,
+                "positions": m.positions,
+            })
+        })
+        .collect();
+    Json(results).into_response()
+}
+
+pub async fn handle_palette_js() -> Response {
+    let js = include_str!("palette.js");
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/javascript")],
+        js,
+    )
+        .into_response()
+}
+
+pub async fn handle_permalink_js() -> Response {
+    let js = include_str!("permalink.js");
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/javascript")],
+        js,
+    )
+        .into_response()
+}
+
+pub async fn handle_reload_js() -> Response {
+    let js = include_str!("reload.js");
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/javascript")],
+        js,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents;
+    use std::fs;
+
+    fn create_test_state(base_dir: PathBuf) -> AppState {
+        let (reload_tx, _) = broadcast::channel(100);
+        // 読書位置はテストごとに空の状態から始める
+        let state_dir = std::env::temp_dir()
+            .join("mvu_test_state")
+            .join(base_dir.file_name().unwrap_or_default());
+        fs::remove_dir_all(&state_dir).ok();
+        AppState {
+            base_dir: Arc::new(base_dir.clone()),
+            reload_tx,
+            archive_cache: Arc::new(ArchiveIndexCache::new()),
+            zip_passwords: Arc::new(PasswordStore::new()),
+            thumbnails: Arc::new(ThumbnailCache::new(std::env::temp_dir().join("mvu_test_thumbnails"))),
+            positions: Arc::new(PositionStore::load(state_dir)),
+            name_order: NameOrder::default(),
+            readme_names: Arc::new(vec!["README.md".to_string(), "index.md".to_string()]),
+            index_as_page: false,
+            toc_depth: 6,
+            file_index: Arc::new(FileIndex::new(base_dir.clone())),
+            search_index: Arc::new(SearchIndex::new()),
+            tag_index: Arc::new(TagIndex::new()),
+            link_index: Arc::new(LinkIndex::new(base_dir)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_path_traversal_protection() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_traversal");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // temp_dirの親ディレクトリに実際にファイルを作成
+        let parent_dir = temp_dir.parent().unwrap();
+        let target_file = parent_dir.join("secret.txt");
+        fs::write(&target_file, "secret data").unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        // パストラバーサル攻撃の試み: 親ディレクトリのファイルにアクセス
+        let response = handle_path(State(state), Path("../secret.txt".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        let status = response.status();
+
+        // base_dir外のアクセスは403 FORBIDDENになるべき
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // クリーンアップ
+        fs::remove_file(&target_file).ok();
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_nonexistent_path() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_nonexist");
         fs::create_dir_all(&temp_dir).unwrap();
 
         let state = create_test_state(temp_dir.clone());
 
-        let response = handle_path(State(state), Path("nonexistent.txt".to_string()), Query(HashMap::new())).await;
+        let response = handle_path(State(state), Path("nonexistent.txt".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
         let status = response.status();
 
         assert_eq!(status, StatusCode::NOT_FOUND);
@@ -1119,7 +2824,7 @@ mod tests {
         fs::write(temp_dir.join("test.txt"), "test content").unwrap();
         fs::write(temp_dir.join("test.md"), "# Test").unwrap();
 
-        let response = handle_directory(&temp_dir, "".to_string()).await;
+        let response = handle_directory(&create_test_state(temp_dir.clone()), &temp_dir, "".to_string(), ListingSort::default(), None).await;
         let status = response.status();
 
         assert_eq!(status, StatusCode::OK);
@@ -1171,7 +2876,7 @@ mod tests {
         fs::write(temp_dir.join("test.svg"), "fake image").unwrap();
         fs::write(temp_dir.join("document.txt"), "text file").unwrap();
 
-        let response = handle_directory(&temp_dir, "".to_string()).await;
+        let response = handle_directory(&create_test_state(temp_dir.clone()), &temp_dir, "".to_string(), ListingSort::default(), None).await;
         let status = response.status();
         assert_eq!(status, StatusCode::OK);
 
@@ -1183,6 +2888,8 @@ mod tests {
         assert!(body_str.contains("test.jpg"));
         assert!(body_str.contains("test.png"));
         assert!(body_str.contains("class=\"thumbnail\""));
+        assert!(body_str.contains("src=\"/__thumb__/test.jpg\""));
+        assert!(body_str.contains("src=\"/test.svg\""));
         assert!(body_str.contains("openModal"));
         assert!(body_str.contains("imageModal"));
 
@@ -1195,13 +2902,13 @@ mod tests {
         fs::create_dir_all(&temp_dir).unwrap();
 
         // zipファイルを作成（空のzipファイル）
-        fs::write(temp_dir.join("test.zip"), &[
+        fs::write(temp_dir.join("test.zip"), [
             0x50, 0x4b, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         ]).unwrap();
 
-        let response = handle_directory(&temp_dir, "".to_string()).await;
+        let response = handle_directory(&create_test_state(temp_dir.clone()), &temp_dir, "".to_string(), ListingSort::default(), None).await;
         let status = response.status();
         assert_eq!(status, StatusCode::OK);
 
@@ -1215,6 +2922,687 @@ mod tests {
         fs::remove_dir_all(&temp_dir).ok();
     }
 
+    #[tokio::test]
+    async fn test_encrypted_zip_password_flow() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_zip_password");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("secret.zip")).unwrap());
+        let options = SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "hunter2");
+        writer.start_file("a.txt", options).unwrap();
+        writer.write_all(b"first").unwrap();
+        writer.start_file("b.txt", options).unwrap();
+        writer.write_all(b"second").unwrap();
+        writer.finish().unwrap();
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("plain.zip")).unwrap());
+        writer.start_file("a.txt", SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"open").unwrap();
+        writer.finish().unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        // パスワード未入力ならプロンプトを表示
+        let response = handle_path(State(state.clone()), Path("secret.zip::a.txt".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let form = |password: &str| ZipPasswordForm {
+            archive: "secret.zip".to_string(),
+            entry: "a.txt".to_string(),
+            password: password.to_string(),
+        };
+
+        let response = handle_zip_password(State(state.clone()), HeaderMap::new(), Form(form("wrong"))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // ないエントリ・暗号化されていないエントリにはパスワードを保存しない
+        let missing = ZipPasswordForm { entry: "missing.txt".to_string(), ..form("hunter2") };
+        let response = handle_zip_password(State(state.clone()), HeaderMap::new(), Form(missing)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+        let plain = ZipPasswordForm { archive: "plain.zip".to_string(), ..form("hunter2") };
+        let response = handle_zip_password(State(state.clone()), HeaderMap::new(), Form(plain)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+
+        let response = handle_zip_password(State(state.clone()), HeaderMap::new(), Form(form("hunter2"))).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookie = response.headers().get(header::SET_COOKIE).unwrap().to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_string();
+
+        // 同じセッションなら同じアーカイブの別エントリもそのまま開ける
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, cookie.parse().unwrap());
+        let response = handle_path(State(state), Path("secret.zip::b.txt".to_string()), Query(HashMap::new()), headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"second");
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_thumbnail_requires_password() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_thumbnail_password");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(8, 8, image::Rgb([1, 2, 3])))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("secret.zip")).unwrap());
+        let options = SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "hunter2");
+        writer.start_file("a.png", options).unwrap();
+        writer.write_all(&png).unwrap();
+        writer.finish().unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+        let form = ZipPasswordForm { archive: "secret.zip".to_string(), entry: "a.png".to_string(), password: "hunter2".to_string() };
+        let response = handle_zip_password(State(state.clone()), HeaderMap::new(), Form(form)).await;
+        let cookie = response.headers().get(header::SET_COOKIE).unwrap().to_str().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, cookie.split(';').next().unwrap().parse().unwrap());
+
+        // パスワードを知っているセッションには作るが、キャッシュ用のETagは付けない
+        let thumbnail = || Path("secret.zip::a.png".to_string());
+        let response = handle_thumbnail(State(state.clone()), thumbnail(), headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+
+        // 別のセッションには、一度作られた後でも見せない
+        let response = handle_thumbnail(State(state.clone()), thumbnail(), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let metadata = fs::metadata(temp_dir.join("secret.zip")).unwrap();
+        let key = ThumbnailCache::cache_key(
+            &format!("{}::a.png", temp_dir.join("secret.zip").display()),
+            metadata.modified().ok(),
+            metadata.len(),
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, format!("\"{}\"", key).parse().unwrap());
+        let response = handle_thumbnail(State(state), thumbnail(), headers).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_format_size_and_timestamp() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13");
+    }
+
+    #[tokio::test]
+    async fn test_zip_directory_shows_metadata() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_zip_metadata");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("meta.zip")).unwrap());
+        writer.add_directory("docs/", SimpleFileOptions::default()).unwrap();
+        writer.start_file("notes.txt", SimpleFileOptions::default()).unwrap();
+        writer.write_all(&[b'a'; 2048]).unwrap();
+        writer.finish().unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+        let response = handle_path(State(state), Path("meta.zip::".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body_str.contains("2 エントリ（1 ファイル · 1 フォルダ） · 合計 2.0 KB"));
+        assert!(body_str.contains("Deflated"));
+        assert!(body_str.contains("CRC "));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_download_directory_as_zip() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_download_handler");
+        fs::create_dir_all(temp_dir.join("docs")).unwrap();
+        fs::write(temp_dir.join("docs/a.md"), "# A").unwrap();
+        fs::write(temp_dir.join("docs/b.md"), "# B").unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        let state = create_test_state(temp_dir.clone());
+
+        let mut params = HashMap::new();
+        params.insert("path".to_string(), "docs".to_string());
+        params.insert("select".to_string(), r#"["b.md"]"#.to_string());
+        let response = handle_download(State(state.clone()), Query(params)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().contains("docs.zip"));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), vec!["b.md"]);
+
+        // base_dir 外は拒否
+        let mut params = HashMap::new();
+        params.insert("path".to_string(), "..".to_string());
+        let response = handle_download(State(state), Query(params)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_endpoint() {
+        use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb};
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_thumb_handler");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(1000, 800, Rgb([10, 200, 10])));
+        image.save_with_format(temp_dir.join("photo.png"), ImageFormat::Png).unwrap();
+        fs::write(temp_dir.join("broken.jpg"), "fake image").unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        let response = handle_thumbnail(State(state.clone()), Path("photo.png".to_string()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+        let etag = response.headers()[header::ETAG].clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.len() < 20_000);
+
+        // ETagが一致すれば304
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag);
+        let response = handle_thumbnail(State(state.clone()), Path("photo.png".to_string()), headers).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // 縮小できない画像は元画像へリダイレクト
+        let response = handle_thumbnail(State(state), Path("broken.jpg".to_string()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/broken.jpg");
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_reader_for_directory_and_cbz() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_reader_handler");
+        fs::create_dir_all(temp_dir.join("comics/ch1")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("comics/ch1/001.jpg"), "fake").unwrap();
+
+        for name in ["vol1.cbz", "vol2.cbz"] {
+            let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("comics").join(name)).unwrap());
+            writer.start_file("p02.png", SimpleFileOptions::default()).unwrap();
+            writer.write_all(b"fake").unwrap();
+            writer.start_file("p01.png", SimpleFileOptions::default()).unwrap();
+            writer.write_all(b"fake").unwrap();
+            writer.finish().unwrap();
+        }
+
+        let state = create_test_state(temp_dir.clone());
+
+        // 画像のあるディレクトリ一覧にはリーダーへのリンクが出る
+        let response = handle_directory(&state, &temp_dir.join("comics/ch1"), "comics/ch1".to_string(), ListingSort::default(), None).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("/__reader__/comics/ch1"));
+
+        let response = handle_reader(State(state.clone()), Path("comics/ch1".to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("\"/comics/ch1/001.jpg\""));
+
+        // .cbz はZIPとして開け、最後まで読んだら次の巻へ
+        let response = handle_reader(State(state.clone()), Path("comics/vol1.cbz".to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("[\"/comics/vol1.cbz::p01.png\",\"/comics/vol1.cbz::p02.png\"]"));
+        assert!(body_str.contains("const nextBook = \"/__reader__/comics/vol2.cbz\""));
+
+        let response = handle_path(State(state.clone()), Path("comics/vol2.cbz::".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("/__reader__/comics/vol2.cbz::"));
+
+        let response = handle_reader(State(state), Path("../".to_string())).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_reading_position_on_listing() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_position_handler");
+        fs::create_dir_all(temp_dir.join("docs")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("docs/guide.md"), "# Guide").unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        let update: PositionUpdate = serde_json::from_str(
+            r#"{"path": "docs/guide.md", "view": "markdown", "anchor": "2-Install", "progress": 40}"#,
+        ).unwrap();
+        let response = handle_position(State(state.clone()), Json(update)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // 存在しないパスは保存しない
+        let update: PositionUpdate = serde_json::from_str(r#"{"path": "../etc", "view": "gallery", "page": 1}"#).unwrap();
+        let response = handle_position(State(state.clone()), Json(update)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = handle_directory(&state, &temp_dir, "".to_string(), ListingSort::default(), None).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("続きから読む"));
+        assert!(body_str.contains("href=\"/docs/guide.md#2-Install\""));
+        assert!(body_str.contains("40%"));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_natural_sort_in_listings() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_natural_sort");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        for name in ["page10.jpg", "page2.jpg", "page1.jpg"] {
+            fs::write(temp_dir.join(name), "fake image").unwrap();
+        }
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("pages.zip")).unwrap());
+        for name in ["p10.png", "p9.png"] {
+            writer.start_file(name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(b"fake").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        // 一覧とギャラリーの imagePaths は数値順
+        let response = handle_directory(&state, &temp_dir, "".to_string(), ListingSort::default(), None).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("const imagePaths = [\"page1.jpg\",\"page2.jpg\",\"page10.jpg\"]"));
+
+        let response = handle_path(State(state), Path("pages.zip::".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("const imagePaths = [\"/pages.zip::p9.png\",\"/pages.zip::p10.png\"]"));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_listing_view_modes() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_view_modes");
+        fs::create_dir_all(temp_dir.join("photos")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        for name in ["a.jpg", "b.png", "c.webp"] {
+            fs::write(temp_dir.join("photos").join(name), "fake image").unwrap();
+        }
+        fs::write(temp_dir.join("photos/notes.md"), "# Notes").unwrap();
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("album.zip")).unwrap());
+        for name in ["p1.jpg", "p2.jpg"] {
+            writer.start_file(name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(b"fake").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        async fn body_of(response: Response) -> String {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+
+        // 画像が過半数ならギャラリー、そうでなければリスト
+        let body = body_of(handle_path(State(state.clone()), Path("photos".to_string()), Query(HashMap::new()), HeaderMap::new()).await).await;
+        assert!(body.contains("<ul id=\"listing\" class=\"view-gallery\" data-key=\"photos\""));
+        assert!(body.contains("data-view=\"grid\""));
+        let body = body_of(handle_root(State(state.clone()), Query(HashMap::new()), HeaderMap::new()).await).await;
+        assert!(body.contains("class=\"view-list\""));
+
+        // `?view=` の指定が優先
+        let params: HashMap<String, String> = [("view".to_string(), "grid".to_string())].into_iter().collect();
+        let body = body_of(handle_path(State(state.clone()), Path("photos".to_string()), Query(params), HeaderMap::new()).await).await;
+        assert!(body.contains("class=\"view-grid\" data-key=\"photos\" data-auto=\"gallery\" data-forced=\"1\""));
+
+        let body = body_of(handle_path(State(state), Path("album.zip::".to_string()), Query(HashMap::new()), HeaderMap::new()).await).await;
+        assert!(body.contains("class=\"view-gallery\" data-key=\"album.zip::\""));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_listing_pagination_api() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_listing_api");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(temp_dir.join("big")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        for i in 0..LISTING_PAGE_SIZE + 5 {
+            let name = if i % 2 == 0 { format!("img{}.png", i) } else { format!("note{}.md", i) };
+            fs::write(temp_dir.join("big").join(name), "x").unwrap();
+        }
+
+        let state = create_test_state(temp_dir.clone());
+
+        // 一覧ページには最初のページだけを埋め込む
+        let response = handle_path(State(state.clone()), Path("big".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(body_str.matches("class=\"select-box\"").count(), LISTING_PAGE_SIZE);
+        assert!(body_str.contains("id=\"listing-more\""));
+        assert!(body_str.contains(&format!("const imageTotal = {};", LISTING_PAGE_SIZE / 2 + 3)));
+
+        let params = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+
+        // 続きは一覧APIから
+        let offset = LISTING_PAGE_SIZE.to_string();
+        let response = handle_listing(State(state.clone()), Query(params(&[("path", "big"), ("offset", &offset)])), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["total"], LISTING_PAGE_SIZE + 5);
+        let items = page["items"].as_array().unwrap();
+        assert_eq!(items.len(), 5);
+        assert_eq!(items[0]["name"], "note195.md");
+
+        // 画像の行は全体での通し番号でモーダルを開く
+        let response = handle_listing(State(state.clone()), Query(params(&[("path", "big"), ("offset", "100"), ("limit", "1")])), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(page["items"][0]["html"].as_str().unwrap().contains("openModal('big/img200.png', 100)"));
+
+        // モーダルはまだ読み込んでいない画像も通し番号で取得できる
+        let response = handle_listing(
+            State(state.clone()),
+            Query(params(&[("path", "big"), ("kind", "images"), ("offset", "100"), ("limit", "2")])),
+            HeaderMap::new(),
+        )
+        .await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["images"], serde_json::json!(["big/img200.png", "big/img202.png"]));
+
+        let response = handle_listing(State(state), Query(params(&[("path", "../")])), HeaderMap::new()).await;
+        assert_ne!(response.status(), StatusCode::OK);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_listing_filter_box() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_listing_filter");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("a&b.md"), "# A").unwrap();
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("docs.zip")).unwrap());
+        writer.start_file("guide/intro.md", SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"# Intro").unwrap();
+        writer.finish().unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        // ディレクトリ一覧とアーカイブ一覧で同じ絞り込み欄と data-name を出す
+        for path in ["", "docs.zip::guide"] {
+            let response = if path.is_empty() {
+                handle_root(State(state.clone()), Query(HashMap::new()), HeaderMap::new()).await
+            } else {
+                handle_path(State(state.clone()), Path(path.to_string()), Query(HashMap::new()), HeaderMap::new()).await
+            };
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body_str = String::from_utf8(body.to_vec()).unwrap();
+            assert!(body_str.contains("id=\"listing-filter\""));
+            assert!(body_str.contains("<script src=\"/__listing__.js\"></script>"));
+            assert!(body_str.contains("mvuListing.init("));
+        }
+
+        let response = handle_root(State(state.clone()), Query(HashMap::new()), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("<li data-name=\"a&amp;b.md\">"));
+
+        let response = handle_path(State(state), Path("docs.zip::guide".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("<li data-name=\"intro.md\">"));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_file_search_endpoint() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_file_search");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(temp_dir.join("a/b/c/d")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("a/b/c/d/my notes.md"), "# Notes").unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+        state.file_index.build();
+
+        let params: HashMap<String, String> = [("q".to_string(), "mynotes".to_string())].into_iter().collect();
+        let response = handle_file_search(State(state), Query(params)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(results[0]["path"], "a/b/c/d/my notes.md");
+        assert_eq!(results[0]["url"], "/a/b/c/d/my%20notes.md");
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_listing_uses_front_matter_title() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_listing_front_matter");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("2024-01-setup.md"), "---\ntitle: Setup & Install\n---\nBody\n").unwrap();
+        fs::write(temp_dir.join("plain.md"), "# Plain\n").unwrap();
+        {
+            use std::io::Write;
+            let mut writer = zip::ZipWriter::new(fs::File::create(temp_dir.join("notes.zip")).unwrap());
+            writer.start_file("inner.md", zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(b"---\ntitle: Inner Note\n---\nBody\n").unwrap();
+            writer.finish().unwrap();
+        }
+
+        // タイトルは索引から引くので、索引ができるまではファイル名のまま
+        let state = create_test_state(temp_dir.clone());
+        let response = handle_directory(&state, &temp_dir, "".to_string(), ListingSort::default(), None).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("<li data-name=\"2024-01-setup.md\">"));
+
+        let documents = documents::collect(&temp_dir);
+        state.link_index.replace(&documents);
+        state.search_index.replace(&documents);
+        let response = handle_directory(&state, &temp_dir, "".to_string(), ListingSort::default(), None).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("data-name=\"2024-01-setup.md\" data-title=\"Setup &amp; Install\""));
+        assert!(body_str.contains("<span class=\"name\">Setup &amp; Install</span>"));
+        assert!(body_str.contains("<li data-name=\"plain.md\">"));
+
+        // ZIP内のMarkdownも全文検索の索引からタイトルを出す
+        let response = handle_path(State(state), Path("notes.zip::".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("data-name=\"inner.md\" data-title=\"Inner Note\""));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_search_page() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_search_page");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(temp_dir.join("docs")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("docs/guide.md"), "# Guide\n\n## Install\n\nRun the installer.\n").unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+        let params: HashMap<String, String> = [("q".to_string(), "installer".to_string())].into_iter().collect();
+
+        // 索引ができるまではその旨を表示する
+        let response = handle_search(State(state.clone()), Query(params.clone())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("索引を作成中"));
+
+        state.search_index.replace(&documents::collect(&temp_dir));
+        let response = handle_search(State(state), Query(params)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("1 件"));
+        assert!(body_str.contains("href=\"/docs/guide.md#2-Install\""));
+        assert!(body_str.contains("§ Install"));
+        assert!(body_str.contains("Run the <mark>installer</mark>."));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_tag_pages() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_tag_pages");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("old.md"), "---\ntitle: Apple\ntags: [日記, rust]\ndate: 2023-01-01\n---\n").unwrap();
+        fs::write(temp_dir.join("new.md"), "---\ntitle: New\ntags: [日記]\ndate: 2024-01-01\n---\n").unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+        state.tag_index.replace(&documents::collect(&temp_dir));
+
+        let response = handle_tags(State(state.clone())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("<a href=\"/__tags__/%E6%97%A5%E8%A8%98\">#日記<span class=\"tag-count\">2</span></a>"));
+        assert!(body_str.find("#日記").unwrap() < body_str.find("#rust").unwrap());
+
+        let params: HashMap<String, String> = [("sort".to_string(), "title".to_string())].into_iter().collect();
+        let response = handle_tag(State(state.clone()), Path("日記".to_string()), Query(HashMap::new())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("2 件"));
+        assert!(body_str.find("/new.md").unwrap() < body_str.find("/old.md").unwrap());
+
+        let response = handle_tag(State(state), Path("日記".to_string()), Query(params)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.find("/old.md").unwrap() < body_str.find("/new.md").unwrap());
+        assert!(body_str.contains("?sort=title\" class=\"active\""));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_listing_sort_and_columns() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_listing_sort");
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("sub/small.txt"), "a").unwrap();
+        fs::write(temp_dir.join("sub/large.md"), "a".repeat(2048)).unwrap();
+        fs::write(temp_dir.join("sub/medium.txt"), "a".repeat(100)).unwrap();
+
+        let state = create_test_state(temp_dir.clone());
+
+        let params: HashMap<String, String> = [("sort", "size"), ("order", "desc")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let response = handle_path(State(state.clone()), Path("sub".to_string()), Query(params), HeaderMap::new()).await;
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap().to_string();
+        assert!(cookie.starts_with("mvu_sort=size.desc"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        let large = body_str.find(">large.md<").unwrap();
+        let medium = body_str.find(">medium.txt<").unwrap();
+        let small = body_str.find(">small.txt<").unwrap();
+        assert!(large < medium && medium < small);
+        assert!(body_str.contains("<span class=\"col col-size\">2.0 KB</span>"));
+        assert!(body_str.contains("<span class=\"col col-type\">MD</span>"));
+        assert!(body_str.contains("サイズ ▼"));
+
+        // 次からはCookieの並び順（昇順にした場合）が使われる
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "mvu_sort=size.asc".parse().unwrap());
+        let response = handle_path(State(state), Path("sub".to_string()), Query(HashMap::new()), headers).await;
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.find(">small.txt<").unwrap() < body_str.find(">large.md<").unwrap());
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_find_readme() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_find_readme");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("index.md"), "# Index").unwrap();
+        fs::write(temp_dir.join("Readme.md"), "# Readme").unwrap();
+
+        let names = vec!["README.md".to_string(), "index.md".to_string()];
+        assert_eq!(find_readme(&temp_dir, &names), Some(temp_dir.join("Readme.md")));
+        assert_eq!(find_readme(&temp_dir, &names[1..]), Some(temp_dir.join("index.md")));
+        assert_eq!(find_readme(&temp_dir, &["about.md".to_string()]), None);
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_index_as_page() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_index_page");
+        fs::create_dir_all(temp_dir.join("docs")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        fs::write(temp_dir.join("docs/index.md"), "# Docs").unwrap();
+
+        let mut state = create_test_state(temp_dir.clone());
+        state.index_as_page = true;
+
+        // 相対リンクのため末尾 `/` 付きのURLへリダイレクト
+        let response = handle_path(State(state.clone()), Path("docs".to_string()), Query(HashMap::new()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/docs/");
+
+        // ?list=1 なら一覧
+        let params: HashMap<String, String> = [("list".to_string(), "1".to_string())].into_iter().collect();
+        let response = handle_path(State(state), Path("docs/".to_string()), Query(params), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body_str.contains("📁 /docs<"));
+        assert!(body_str.contains("href=\"/docs/index.md\""));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
     #[test]
     fn test_zip_handler_is_zip_file() {
         use std::path::Path;
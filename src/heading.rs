@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+/// Markdownの見出し（目次・全文検索・レンダリング結果のidで共通）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub level: usize,
+    // インラインの装飾を除いたテキスト
+    pub text: String,
+    pub id: String,
+    // 見出しのある行（0始まり）
+    pub line: usize,
+}
+
+/// 見出しのid（`{レベル}-{パーセントエンコードしたテキスト}`）
+pub fn heading_id(level: usize, text: &str) -> String {
+    const CUSTOM_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+    format!("{}-{}", level, utf8_percent_encode(text, CUSTOM_ENCODE_SET))
+}
+
+/// 同じidが続くときに `-1`, `-2`, ... を付けて一意にする（目次とレンダリング結果で同じ順に使う）
+#[derive(Default)]
+struct UniqueIds {
+    used: HashSet<String>,
+    suffixes: HashMap<String, usize>,
+}
+
+impl UniqueIds {
+    fn assign(&mut self, id: String) -> String {
+        if self.used.insert(id.clone()) {
+            return id;
+        }
+        let suffix = self.suffixes.entry(id.clone()).or_insert(0);
+        loop {
+            *suffix += 1;
+            let candidate = format!("{}-{}", id, suffix);
+            if self.used.insert(candidate.clone()) {
+                return candidate;
+            }
+        }
+    }
+}
+
+pub fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_FOOTNOTES | Options::ENABLE_TASKLISTS
+}
+
+/// 連続する空白を1つにまとめる
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn level_number(level: pulldown_cmark::HeadingLevel) -> usize {
+    level as usize
+}
+
+/// Markdownをパースして見出しを取り出す（コードブロック内の `#` は無視し、Setext見出しも拾う）
+pub fn parse_headings(source: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut ids = UniqueIds::default();
+    // (レベル, 開始位置, テキスト, 画像の入れ子の深さ)
+    let mut current: Option<(usize, usize, String, usize)> = None;
+
+    for (event, range) in Parser::new_ext(source, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some((level_number(level), range.start, String::new(), 0));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, start, text, _)) = current.take() {
+                    let text = collapse_whitespace(&text);
+                    if !text.is_empty() {
+                        headings.push(Heading {
+                            level,
+                            id: ids.assign(heading_id(level, &text)),
+                            text,
+                            line: source[..start].matches('\n').count(),
+                        });
+                    }
+                }
+            }
+            // 画像の代替テキストはレンダリング結果に出ないので含めない
+            Event::Start(Tag::Image { .. }) => {
+                if let Some((_, _, _, images)) = current.as_mut() {
+                    *images += 1;
+                }
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((_, _, _, images)) = current.as_mut() {
+                    *images -= 1;
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, buffer, 0)) = current.as_mut() {
+                    buffer.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some((_, _, buffer, _)) = current.as_mut() {
+                    buffer.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    headings
+}
+
+/// unidoc が解釈できない見出し（Setext見出し、末尾の `#`）を `## 見出し` の形に書き換える
+pub fn normalize_headings(source: &str) -> String {
+    let mut replacements = Vec::new();
+    // (レベル, 見出し全体の範囲, 中身の範囲)
+    let mut current: Option<(usize, std::ops::Range<usize>, Option<std::ops::Range<usize>>)> = None;
+
+    for (event, range) in Parser::new_ext(source, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some((level_number(level), range, None));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                let Some((level, whole, Some(inner))) = current.take() else {
+                    continue;
+                };
+                let raw = source[whole.clone()].trim_end();
+                let is_setext = !raw.trim_start().starts_with('#');
+                if is_setext || raw.ends_with('#') {
+                    let text = collapse_whitespace(&source[inner]);
+                    let newline = if source[whole.clone()].ends_with('\n') { "\n" } else { "" };
+                    replacements.push((whole, format!("{} {}{}", "#".repeat(level), text, newline)));
+                }
+            }
+            _ => {
+                if let Some((_, _, inner)) = current.as_mut() {
+                    let inner = inner.get_or_insert(range.clone());
+                    inner.start = inner.start.min(range.start);
+                    inner.end = inner.end.max(range.end);
+                }
+            }
+        }
+    }
+
+    let mut normalized = source.to_string();
+    for (range, replacement) in replacements.into_iter().rev() {
+        normalized.replace_range(range, &replacement);
+    }
+    normalized
+}
+
+/// タグを除いてHTMLの実体参照を戻したテキスト
+fn html_to_text(html: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    let mut text = String::with_capacity(stripped.len());
+    let mut rest = stripped.as_str();
+    while let Some(start) = rest.find('&') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest
+            .find(';')
+            .filter(|&end| end <= MAX_ENTITY_LEN)
+            .and_then(|end| decode_entity(&rest[..=end]).map(|decoded| (decoded, end)));
+        match decoded {
+            Some((decoded, end)) => {
+                text.push_str(&decoded);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// 実体参照の最大の長さ（`&CounterClockwiseContourIntegral;` が一番長い）
+const MAX_ENTITY_LEN: usize = 40;
+
+/// `&nbsp;` や `&#169;` を1つ戻す
+/// 見出しのテキストは pulldown-cmark で取り出しているので、同じ表で戻すよう pulldown-cmark に読ませる
+fn decode_entity(entity: &str) -> Option<String> {
+    let mut text = String::new();
+    for event in Parser::new(entity) {
+        match event {
+            Event::Text(t) => text.push_str(&t),
+            Event::Start(Tag::Paragraph) | Event::End(TagEnd::Paragraph) => {}
+            _ => return None,
+        }
+    }
+    (text != entity).then_some(text)
+}
+
+/// レンダリング結果の `<h1>`〜`<h6>` のidを、`headings`（同じソースを parse_headings したもの）のidに付け直す
+/// unidoc はインラインのHTMLごとidにするので、目次のidと揃える
+/// HTMLで直接書いた見出しや、`headings` にない見出しは、順番がずれないよう飛ばしてそのままにする
+pub fn rewrite_heading_ids(html: &str, headings: &[Heading]) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut next = 0;
+
+    while let Some(start) = rest.find("<h") {
+        let after = &rest[start + 2..];
+        let level = after
+            .chars()
+            .next()
+            .and_then(|c| c.to_digit(10))
+            .filter(|l| (1..=6).contains(l) && after[1..].starts_with([' ', '>']));
+        let Some(level) = level else {
+            output.push_str(&rest[..start + 2]);
+            rest = after;
+            continue;
+        };
+        let close_tag = format!("</h{}>", level);
+        let (Some(open_end), Some(close_start)) = (rest[start..].find('>'), rest[start..].find(&close_tag)) else {
+            output.push_str(&rest[..start + 2]);
+            rest = after;
+            continue;
+        };
+        let open_end = start + open_end;
+        let close_start = start + close_start;
+        if close_start < open_end {
+            output.push_str(&rest[..start + 2]);
+            rest = after;
+            continue;
+        }
+
+        // unidoc はMarkdownの見出しには必ずidを付け、HTMLで書いた見出しは `<p>` で囲む
+        let end = close_start + close_tag.len();
+        let raw_html = !rest[start..open_end].contains(" id=\"") || rest[..start].ends_with("<p>");
+        if raw_html {
+            output.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let text = collapse_whitespace(&html_to_text(&rest[open_end + 1..close_start]));
+        let found = headings[next..]
+            .iter()
+            .position(|heading| heading.level == level as usize && heading.text == text)
+            .map(|offset| next + offset);
+        match found {
+            Some(index) if !text.is_empty() => {
+                let attributes = remove_id_attribute(&rest[start + 3..open_end]);
+                output.push_str(&rest[..start]);
+                output.push_str(&format!("<h{}{} id=\"{}\">", level, attributes, headings[index].id));
+                output.push_str(&rest[open_end + 1..end]);
+                next = index + 1;
+            }
+            _ => output.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn remove_id_attribute(attributes: &str) -> String {
+    match attributes.find(" id=\"") {
+        Some(start) => match attributes[start + 5..].find('"') {
+            Some(end) => format!("{}{}", &attributes[..start], &attributes[start + 5 + end + 1..]),
+            None => attributes.to_string(),
+        },
+        None => attributes.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headings() {
+        let source = "# Title #\n\nSetext Two\n----------\n\n```sh\n# not a heading\n```\n\n## **Bold** and `code` [link](x.md) ![icon](i.png)\n\n##### Five\n";
+        let headings = parse_headings(source);
+        let summary: Vec<(usize, &str, usize)> = headings.iter().map(|h| (h.level, h.text.as_str(), h.line)).collect();
+        assert_eq!(
+            summary,
+            vec![(1, "Title", 0), (2, "Setext Two", 2), (2, "Bold and code link", 9), (5, "Five", 11)]
+        );
+        assert_eq!(parse_headings("Setext Two\n===\n")[0].id, "1-Setext%20Two");
+    }
+
+    #[test]
+    fn test_normalize_headings() {
+        let source = "Setext\nTitle\n======\n\nText\n\n## Closed ##\n\n- item\n\n### Plain\n";
+        assert_eq!(normalize_headings(source), "# Setext Title\n\nText\n\n## Closed\n\n- item\n\n### Plain\n");
+        // 見出しでない行は触らない
+        assert_eq!(normalize_headings("a\n\n---\n"), "a\n\n---\n");
+    }
+
+    #[test]
+    fn test_rewrite_heading_ids() {
+        let html = "<h1 class=\"title\" id=\"1-Title\">Title</h1>\n<h2 id=\"2-%3Cstrong%3EBold%3C%2Fstrong%3E\"><strong>Bold</strong> &amp; <code>x</code></h2><hr /><header>h</header>";
+        let headings = parse_headings("# Title\n\n## **Bold** & `x`\n");
+        assert_eq!(
+            rewrite_heading_ids(html, &headings),
+            "<h1 class=\"title\" id=\"1-Title\">Title</h1>\n<h2 id=\"2-Bold%20%26%20x\"><strong>Bold</strong> &amp; <code>x</code></h2><hr /><header>h</header>"
+        );
+        // 目次と同じidになる
+        assert_eq!(parse_headings("## **Bold** & `x`\n")[0].id, "2-Bold%20%26%20x");
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_suffixed() {
+        let source = "## Example\n\n## Example\n\n## Example-1\n\n## Example\n";
+        let ids: Vec<String> = parse_headings(source).into_iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec!["2-Example", "2-Example-1", "2-Example-1-1", "2-Example-2"]);
+
+        // レンダリング結果にも同じ順で同じidを付ける
+        let html = "<h2 id=\"a\">Example</h2><h2 id=\"a\">Example</h2><h2 id=\"b\">Example-1</h2><h2 id=\"a\">Example</h2>";
+        let rewritten = rewrite_heading_ids(html, &parse_headings(source));
+        for id in &ids {
+            assert!(rewritten.contains(&format!("id=\"{}\"", id)), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_skips_headings_missing_from_source() {
+        // 空の見出しとHTMLで書いた見出しは目次に入らないので、後ろの重複した見出しのidがずれない
+        let source = "## Example\n\n##\n\n<h2>Example</h2>\n\n## Example\n\n## A&nbsp;B &#169;\n";
+        let headings = parse_headings(source);
+        let ids: Vec<&str> = headings.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["2-Example", "2-Example-1", "2-A%20B%20%C2%A9"]);
+
+        let html = "<h2 id=\"a\">Example</h2><h2 id=\"b\"></h2><p><h2>Example</h2></p><p><h2 id=\"x\">Example</h2></p><h2 id=\"a\">Example</h2><h2 id=\"c\">A&nbsp;B &#169;</h2>";
+        assert_eq!(
+            rewrite_heading_ids(html, &headings),
+            "<h2 id=\"2-Example\">Example</h2><h2 id=\"b\"></h2><p><h2>Example</h2></p><p><h2 id=\"x\">Example</h2></p><h2 id=\"2-Example-1\">Example</h2><h2 id=\"2-A%20B%20%C2%A9\">A&nbsp;B &#169;</h2>"
+        );
+        assert_eq!(html_to_text("a &amp; b &bogus; &#x41;"), "a & b &bogus; A");
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::documents;
+use crate::file_index::FileIndex;
+use crate::link_index::LinkIndex;
+use crate::search::SearchIndex;
+use crate::tag_index::TagIndex;
+
+/// 索引スレッドへの依頼
+#[derive(Debug, PartialEq)]
+pub enum IndexJob {
+    // watcher で変更があったパス
+    Update(Vec<PathBuf>),
+    // 通知を取りこぼしたので全体を作り直す
+    Rebuild,
+}
+
+/// 起動時に作ってファイル変更で更新する索引
+#[derive(Clone)]
+pub struct Indexes {
+    base_dir: PathBuf,
+    pub files: Arc<FileIndex>,
+    pub search: Arc<SearchIndex>,
+    pub tags: Arc<TagIndex>,
+    pub links: Arc<LinkIndex>,
+}
+
+impl Indexes {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            files: Arc::new(FileIndex::new(base_dir.clone())),
+            search: Arc::new(SearchIndex::new()),
+            tags: Arc::new(TagIndex::new()),
+            links: Arc::new(LinkIndex::new(base_dir.clone())),
+            base_dir,
+        }
+    }
+
+    /// 文書は1回だけ読んで、全文検索・タグ・リンク元の索引で共有する
+    fn build(&self) {
+        self.files.build();
+        eprintln!("[INDEX] {} paths indexed", self.files.len());
+        let documents = documents::collect(&self.base_dir);
+        self.search.replace(&documents);
+        eprintln!("[INDEX] {} documents indexed for search", self.search.len());
+        self.tags.replace(&documents);
+        eprintln!("[INDEX] {} tagged documents", self.tags.len());
+        self.links.replace(&documents);
+        eprintln!("[INDEX] {} documents indexed for backlinks", self.links.len());
+    }
+
+    fn update(&self, paths: &[PathBuf]) {
+        self.files.update(paths);
+        let changes = documents::read_changes(&self.base_dir, paths);
+        self.search.apply(&changes);
+        self.tags.apply(&changes);
+        self.links.apply(&changes);
+    }
+
+    /// 索引を作って更新し続けるスレッドを起動する
+    /// 構築中に届いた変更はキューに溜まり、構築が終わってから反映される
+    pub fn spawn(&self) -> Sender<IndexJob> {
+        let (tx, rx) = mpsc::channel();
+        let indexes = self.clone();
+        std::thread::spawn(move || {
+            indexes.build();
+            while let Some(job) = next_job(&rx) {
+                match job {
+                    IndexJob::Update(paths) => indexes.update(&paths),
+                    IndexJob::Rebuild => indexes.build(),
+                }
+            }
+        });
+        tx
+    }
+}
+
+/// 溜まっている依頼をまとめて1つにする（同じパスは1回だけ、作り直しがあればそれだけ）
+fn next_job(rx: &Receiver<IndexJob>) -> Option<IndexJob> {
+    let mut rebuild = false;
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+    for job in std::iter::once(rx.recv().ok()?).chain(rx.try_iter()) {
+        match job {
+            IndexJob::Rebuild => rebuild = true,
+            IndexJob::Update(changed) => {
+                paths.extend(changed.into_iter().filter(|path| seen.insert(path.clone())));
+            }
+        }
+    }
+    Some(if rebuild { IndexJob::Rebuild } else { IndexJob::Update(paths) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_job_coalesces() {
+        let (tx, rx) = mpsc::channel();
+        tx.send(IndexJob::Update(vec![PathBuf::from("/a.md"), PathBuf::from("/b.md")])).unwrap();
+        tx.send(IndexJob::Update(vec![PathBuf::from("/a.md")])).unwrap();
+        assert_eq!(
+            next_job(&rx),
+            Some(IndexJob::Update(vec![PathBuf::from("/a.md"), PathBuf::from("/b.md")]))
+        );
+
+        tx.send(IndexJob::Update(vec![PathBuf::from("/a.md")])).unwrap();
+        tx.send(IndexJob::Rebuild).unwrap();
+        assert_eq!(next_job(&rx), Some(IndexJob::Rebuild));
+
+        drop(tx);
+        assert_eq!(next_job(&rx), None);
+    }
+
+    #[test]
+    fn test_changes_during_build_are_applied() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_indexer");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("a.md"), "# A\n").unwrap();
+
+        let indexes = Indexes::new(temp_dir.clone());
+        let tx = indexes.spawn();
+        // 構築が終わる前に届いた削除も、構築の後で反映される
+        std::fs::remove_file(temp_dir.join("a.md")).unwrap();
+        tx.send(IndexJob::Update(vec![temp_dir.join("a.md")])).unwrap();
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !(indexes.links.is_ready() && indexes.files.len() == 0) {
+            assert!(std::time::Instant::now() < deadline, "index was not updated");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(indexes.search.len(), 0);
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+
+use crate::documents::{Changes, Document};
+use crate::file_index::{is_under, FileIndex};
+use crate::heading::{self, Heading};
+use crate::natural_sort::NameOrder;
+use crate::obsidian::{self, Vault, WikiLink};
+
+/// リンク元に表示する前後の文の最大文字数
+const MAX_CONTEXT_CHARS: usize = 120;
+
+/// 文書からのリンク
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    // 相対リンクを base_dir からの相対パスにしたもの
+    Path(String),
+    // ウィキリンクはファイルの増減で向き先が変わるので、引くときに解決する
+    Wiki(WikiLink),
+}
+
+#[derive(Debug, Clone)]
+struct Reference {
+    target: Target,
+    // リンクのある行
+    context: String,
+}
+
+#[derive(Debug, Clone)]
+struct LinkedDocument {
+    title: String,
+    // front matter のタイトル（一覧・ファイルツリー用）
+    front_matter_title: Option<String>,
+    // ウィキリンクの `#見出し` の解決用
+    headings: Vec<Heading>,
+    references: Vec<Reference>,
+}
+
+/// ある文書へのリンク元
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backlink {
+    pub path: String,
+    pub title: String,
+    pub context: String,
+}
+
+#[derive(Default)]
+struct Inner {
+    // 相対パス → その文書からのリンク
+    documents: HashMap<String, LinkedDocument>,
+    // 相対リンクのリンク先 → リンク元
+    by_path: HashMap<String, HashSet<String>>,
+    // ウィキリンクの名前（小文字のファイル名）→ リンク元
+    by_name: HashMap<String, HashSet<String>>,
+}
+
+impl Inner {
+    fn insert(&mut self, path: String, document: LinkedDocument) {
+        self.remove(&path);
+        for reference in &document.references {
+            match &reference.target {
+                Target::Path(linked) => {
+                    self.by_path.entry(linked.clone()).or_default().insert(path.clone());
+                }
+                Target::Wiki(link) => {
+                    for name in wiki_names(link) {
+                        self.by_name.entry(name).or_default().insert(path.clone());
+                    }
+                }
+            }
+        }
+        self.documents.insert(path, document);
+    }
+
+    fn remove(&mut self, path: &str) {
+        let Some(document) = self.documents.remove(path) else {
+            return;
+        };
+        for reference in &document.references {
+            let (map, keys) = match &reference.target {
+                Target::Path(linked) => (&mut self.by_path, vec![linked.clone()]),
+                Target::Wiki(link) => (&mut self.by_name, wiki_names(link)),
+            };
+            for key in keys {
+                if let Some(sources) = map.get_mut(&key) {
+                    sources.remove(path);
+                    if sources.is_empty() {
+                        map.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Markdownどうしのリンク（相対リンクとウィキリンク）の索引（リンク元の表示用）
+pub struct LinkIndex {
+    base_dir: PathBuf,
+    inner: RwLock<Inner>,
+    ready: AtomicBool,
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// ウィキリンクが指しうるファイル名（`[[a/Note]]` なら `note.md` と `note`）
+fn wiki_names(link: &WikiLink) -> Vec<String> {
+    if link.target.is_empty() {
+        return Vec::new();
+    }
+    let mut names = vec![file_name(&link.file_name()).to_lowercase(), file_name(&link.target).to_lowercase()];
+    names.dedup();
+    names
+}
+
+impl LinkIndex {
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            inner: RwLock::new(Inner::default()),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// ツリー全体の文書で作り直す（起動時にバックグラウンドで呼ぶ）
+    pub fn replace(&self, documents: &[Document]) {
+        let mut inner = Inner::default();
+        for document in documents {
+            if let Some(linked) = linked(document) {
+                inner.insert(document.relative.clone(), linked);
+            }
+        }
+        *self.inner.write().unwrap() = inner;
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().documents.len()
+    }
+
+    /// watcher で変更があったパスを反映する
+    pub fn apply(&self, changes: &Changes) {
+        if changes.removed.is_empty() {
+            return;
+        }
+        let fresh: Vec<(String, LinkedDocument)> = changes
+            .documents
+            .iter()
+            .filter_map(|document| Some((document.relative.clone(), linked(document)?)))
+            .collect();
+
+        let mut inner = self.inner.write().unwrap();
+        let stale: Vec<String> = inner
+            .documents
+            .keys()
+            .filter(|path| changes.removed.iter().any(|relative| is_under(path, relative)))
+            .cloned()
+            .collect();
+        for path in stale {
+            inner.remove(&path);
+        }
+        for (path, document) in fresh {
+            inner.insert(path, document);
+        }
+    }
+
+    /// front matter のタイトル（一覧・ファイルツリーでファイル名の代わりに出す、ファイルは読まない）
+    pub fn front_matter_title(&self, path: &str) -> Option<String> {
+        self.inner.read().unwrap().documents.get(path)?.front_matter_title.clone()
+    }
+
+    /// `path` の文書の見出しのid（`[[ノート#見出し]]` 用、ファイルは読まない）
+    pub fn heading_id(&self, path: &str, heading: &str) -> Option<String> {
+        obsidian::find_heading_id(&self.inner.read().unwrap().documents.get(path)?.headings, heading)
+    }
+
+    /// `target` にリンクしている文書（自分自身は除く、タイトル順）
+    /// 相対リンクはリンク先、ウィキリンクは名前の逆引きで候補を絞ってから確かめる
+    pub fn backlinks(&self, target: &str, file_index: Option<&FileIndex>, order: NameOrder) -> Vec<Backlink> {
+        let inner = self.inner.read().unwrap();
+        let mut candidates: HashSet<&String> = HashSet::new();
+        candidates.extend(inner.by_path.get(target).into_iter().flatten());
+        candidates.extend(inner.by_name.get(&file_name(target).to_lowercase()).into_iter().flatten());
+
+        let mut backlinks: Vec<Backlink> = candidates
+            .into_iter()
+            .filter(|path| path.as_str() != target)
+            .filter_map(|path| {
+                let document = inner.documents.get(path)?;
+                let vault = Vault {
+                    base_dir: &self.base_dir,
+                    current: path,
+                    file_index,
+                    link_index: None,
+                };
+                let reference = document.references.iter().find(|reference| match &reference.target {
+                    Target::Path(linked) => linked == target,
+                    Target::Wiki(link) => vault.resolve(link).as_deref() == Some(target),
+                })?;
+                Some(Backlink {
+                    path: path.clone(),
+                    title: document.title.clone(),
+                    context: reference.context.clone(),
+                })
+            })
+            .collect();
+        backlinks.sort_by(|a, b| order.cmp(&a.title, &b.title).then_with(|| a.path.cmp(&b.path)));
+        backlinks
+    }
+}
+
+/// 相対リンクのリンク先を base_dir からの相対パスにする（外部のURLや同じページ内は None）
+fn resolve_relative(source: &str, destination: &str) -> Option<String> {
+    let has_scheme = destination
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains('/'));
+    if has_scheme || destination.starts_with("//") {
+        return None;
+    }
+    let path = destination.split(['#', '?']).next().unwrap_or("");
+    if path.is_empty() {
+        return None;
+    }
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+
+    let joined = match path.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => match source.rsplit_once('/') {
+            Some((dir, _)) => format!("{}/{}", dir, path),
+            None => path.to_string(),
+        },
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// 行頭の引用・リスト・見出しの記号を取り除く（`**強調**` のような行頭の記号は残す）
+fn strip_block_prefix(line: &str) -> &str {
+    let mut rest = line.trim_start();
+    loop {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        let marker = if rest.starts_with(['>', '-', '*', '+']) {
+            1
+        } else if digits > 0 && rest[digits..].starts_with(['.', ')']) {
+            digits + 1
+        } else if (1..=6).contains(&hashes) {
+            hashes
+        } else {
+            break;
+        };
+        // 記号のあとには空白が要る
+        let after = &rest[marker..];
+        let trimmed = after.trim_start();
+        if trimmed.len() == after.len() {
+            break;
+        }
+        rest = trimmed;
+    }
+    rest
+}
+
+/// `offset` のある行（長ければ切り詰める）
+fn context_line(source: &str, offset: usize) -> String {
+    let start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = source[offset..].find('\n').map(|i| offset + i).unwrap_or(source.len());
+    let line = strip_block_prefix(source[start..end].trim());
+    if line.chars().count() > MAX_CONTEXT_CHARS {
+        format!("{}…", line.chars().take(MAX_CONTEXT_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Markdownからリンクを取り出す
+fn linked(document: &Document) -> Option<LinkedDocument> {
+    let markdown = document.tree_markdown()?;
+    let body = document.text.as_str();
+
+    // (位置, リンク先)
+    let mut targets = Vec::new();
+    for (event, range) in Parser::new_ext(body, heading::options()).into_offset_iter() {
+        if let Event::Start(Tag::Link { link_type, dest_url, .. }) = event {
+            if link_type == LinkType::Email {
+                continue;
+            }
+            if let Some(linked) = resolve_relative(&document.relative, &dest_url) {
+                targets.push((range.start, Target::Path(linked)));
+            }
+        }
+    }
+    targets.extend(obsidian::wiki_links(body).into_iter().map(|(range, link)| (range.start, Target::Wiki(link))));
+    targets.sort_by_key(|(offset, _)| *offset);
+
+    Some(LinkedDocument {
+        title: markdown.title.clone(),
+        front_matter_title: markdown.front_matter.title.clone(),
+        headings: markdown.headings.clone(),
+        references: targets
+            .into_iter()
+            .map(|(offset, target)| Reference { target, context: context_line(body, offset) })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents;
+
+    #[test]
+    fn test_resolve_relative() {
+        assert_eq!(resolve_relative("docs/a.md", "b.md#2-Setup").as_deref(), Some("docs/b.md"));
+        assert_eq!(resolve_relative("docs/a.md", "../My%20Note.md").as_deref(), Some("My Note.md"));
+        assert_eq!(resolve_relative("docs/a.md", "/top.md").as_deref(), Some("top.md"));
+        assert_eq!(resolve_relative("a.md", "../../outside.md"), None);
+        assert_eq!(resolve_relative("a.md", "https://example.com/x.md"), None);
+        assert_eq!(resolve_relative("a.md", "#local"), None);
+    }
+
+    #[test]
+    fn test_context_line_strips_block_prefix() {
+        assert_eq!(context_line("**Bold** links to [[a]]", 0), "**Bold** links to [[a]]");
+        assert_eq!(context_line("> - *see* [[a]]", 4), "*see* [[a]]");
+        assert_eq!(context_line("intro\n12. Step [[a]]\n", 6), "Step [[a]]");
+        assert_eq!(context_line("## Heading [[a]]", 0), "Heading [[a]]");
+        assert_eq!(context_line("#tag [[a]]", 0), "#tag [[a]]");
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_link_index");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(temp_dir.join("notes")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("notes/Target.md"), "# Target\n\nSelf [[Target]].\n").unwrap();
+        std::fs::write(temp_dir.join("relative.md"), "# Relative\n\nSee [the target](notes/Target.md#1-Target).\n").unwrap();
+        std::fs::write(temp_dir.join("wiki.md"), "---\ntitle: Wiki\n---\n- Read [[target|this]] first\n").unwrap();
+        std::fs::write(temp_dir.join("other.md"), "[[Other]] and [x](relative.md)\n").unwrap();
+
+        let file_index = FileIndex::new(temp_dir.clone());
+        file_index.build();
+        let index = LinkIndex::new(temp_dir.clone());
+        index.replace(&documents::collect(&temp_dir));
+        assert_eq!(index.len(), 4);
+
+        // 逆引きの候補はリンク先と名前だけで引ける
+        {
+            let inner = index.inner.read().unwrap();
+            assert_eq!(inner.by_path["notes/Target.md"], HashSet::from(["relative.md".to_string()]));
+            assert!(inner.by_name["target.md"].contains("wiki.md"));
+        }
+
+        let backlinks = index.backlinks("notes/Target.md", Some(&file_index), NameOrder::default());
+        assert_eq!(
+            backlinks,
+            vec![
+                Backlink {
+                    path: "relative.md".to_string(),
+                    title: "Relative".to_string(),
+                    context: "See [the target](notes/Target.md#1-Target).".to_string(),
+                },
+                Backlink {
+                    path: "wiki.md".to_string(),
+                    title: "Wiki".to_string(),
+                    context: "Read [[target|this]] first".to_string(),
+                },
+            ]
+        );
+
+        // リンクを消すとリンク元からも消える
+        std::fs::write(temp_dir.join("wiki.md"), "no links\n").unwrap();
+        index.apply(&documents::read_changes(&temp_dir, &[temp_dir.join("wiki.md")]));
+        let paths: Vec<String> = index
+            .backlinks("notes/Target.md", Some(&file_index), NameOrder::default())
+            .into_iter()
+            .map(|b| b.path)
+            .collect();
+        assert_eq!(paths, vec!["relative.md"]);
+        assert!(!index.inner.read().unwrap().by_name["target.md"].contains("wiki.md"));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+}
@@ -0,0 +1,186 @@
+// 大きなディレクトリの一覧を /__list__ から少しずつ読み込む。絞り込みとキーボード操作もここで扱う
+window.mvuListing = (function() {
+    let config = null;
+    let images = [];
+    let loading = null;
+    let filterText = '';
+
+    function listUrl(params) {
+        return '/__list__?path=' + encodeURIComponent(config.path) + config.sortQuery + params;
+    }
+
+    function fetchJson(url) {
+        return fetch(url).then(function(response) {
+            return response.ok ? response.json() : Promise.reject(new Error(response.status));
+        });
+    }
+
+    function updateMore() {
+        const more = document.getElementById('listing-more');
+        if (!more) return;
+        if (config.loaded >= config.total) {
+            more.remove();
+            return;
+        }
+        more.querySelector('button').textContent = 'さらに表示 (' + config.loaded + ' / ' + config.total + ')';
+    }
+
+    function nearBottom() {
+        const more = document.getElementById('listing-more');
+        return more && more.getBoundingClientRect().top < window.innerHeight + 800;
+    }
+
+    // 一覧の続きを読み込む（絞り込み中は残りをまとめて読む）
+    function loadMore() {
+        if (loading || config.loaded >= config.total) return;
+        const limit = filterText ? 1000 : config.pageSize;
+        loading = fetchJson(listUrl('&offset=' + config.loaded + '&limit=' + limit))
+            .then(function(page) {
+                document.getElementById('listing').insertAdjacentHTML('beforeend', page.items.map(function(item) {
+                    return item.html;
+                }).join(''));
+                // 読み込み中に項目が減った場合も止まるようにする
+                config.total = page.total;
+                config.loaded = page.items.length > 0 ? page.offset + page.items.length : page.total;
+                updateMore();
+                applyFilter();
+                return true;
+            })
+            .catch(function(e) {
+                console.error('[listing]', e);
+                return false;
+            })
+            .then(function(ok) {
+                loading = null;
+                // 読み込んだ分で画面が埋まらなければ続けて読む（失敗したらボタンを押すまで待つ）
+                if (ok && (filterText || nearBottom())) loadMore();
+            });
+    }
+
+    // 空白区切りの語をすべて含む名前（またはタイトル）だけを表示する（大文字小文字は区別しない）
+    function applyFilter() {
+        const terms = filterText.toLowerCase().split(/\s+/).filter(Boolean);
+        document.querySelectorAll('#listing > li').forEach(function(li) {
+            const name = ((li.dataset.name || '') + ' ' + (li.dataset.title || '')).toLowerCase();
+            // 親ディレクトリへのリンクは絞り込み中は隠す
+            const visible = terms.length === 0 || (li.dataset.name !== undefined && terms.every(function(term) {
+                return name.includes(term);
+            }));
+            li.classList.toggle('filtered-out', !visible);
+            if (!visible) li.classList.remove('selected');
+        });
+    }
+
+    function visibleItems() {
+        return Array.from(document.querySelectorAll('#listing > li:not(.filtered-out)'));
+    }
+
+    function select(offset) {
+        const items = visibleItems();
+        if (items.length === 0) return;
+        const current = items.findIndex(function(li) { return li.classList.contains('selected'); });
+        const next = current < 0 ? (offset > 0 ? 0 : items.length - 1) : Math.max(0, Math.min(items.length - 1, current + offset));
+        if (current >= 0) items[current].classList.remove('selected');
+        items[next].classList.add('selected');
+        items[next].scrollIntoView({ block: 'nearest' });
+    }
+
+    function openSelected() {
+        const selected = document.querySelector('#listing > li.selected:not(.filtered-out)') || visibleItems()[0];
+        const link = selected && selected.querySelector('a');
+        if (link) link.click();
+    }
+
+    function modalOpen() {
+        const modal = document.getElementById('imageModal');
+        return modal && (modal.classList.contains('active') || modal.style.display === 'flex');
+    }
+
+    function initKeyboard() {
+        const filter = document.getElementById('listing-filter');
+
+        filter.addEventListener('input', function() {
+            filterText = filter.value.trim();
+            applyFilter();
+            if (filterText) loadMore();
+        });
+
+        filter.addEventListener('keydown', function(e) {
+            if (e.key === 'ArrowDown' || e.key === 'ArrowUp') {
+                e.preventDefault();
+                select(e.key === 'ArrowDown' ? 1 : -1);
+            } else if (e.key === 'Enter') {
+                e.preventDefault();
+                openSelected();
+            } else if (e.key === 'Escape') {
+                filter.value = '';
+                filterText = '';
+                applyFilter();
+                filter.blur();
+            }
+        });
+
+        document.addEventListener('keydown', function(e) {
+            if (e.ctrlKey || e.metaKey || e.altKey || modalOpen()) return;
+            const tag = e.target.tagName;
+            if (tag === 'INPUT' || tag === 'TEXTAREA' || e.target.isContentEditable) return;
+
+            if (e.key === '/') {
+                e.preventDefault();
+                filter.focus();
+                filter.select();
+            } else if (e.key === 'j' || e.key === 'ArrowDown') {
+                e.preventDefault();
+                select(1);
+            } else if (e.key === 'k' || e.key === 'ArrowUp') {
+                e.preventDefault();
+                select(-1);
+            } else if (e.key === 'Enter') {
+                if (document.querySelector('#listing > li.selected')) {
+                    e.preventDefault();
+                    openSelected();
+                }
+            } else if (e.key === 'Backspace') {
+                const parent = document.querySelector('#listing a.parent');
+                if (parent) {
+                    e.preventDefault();
+                    parent.click();
+                }
+            }
+        });
+    }
+
+    // 画像の通し番号からパスを得る（まだ読み込んでいなければ一覧APIから取得する）
+    function image(index) {
+        if (images[index] !== undefined) return Promise.resolve(images[index]);
+        const offset = Math.floor(index / config.pageSize) * config.pageSize;
+        return fetchJson(listUrl('&kind=images&offset=' + offset + '&limit=' + config.pageSize))
+            .then(function(page) {
+                page.images.forEach(function(path, i) {
+                    images[page.offset + i] = path;
+                });
+                return images[index];
+            });
+    }
+
+    function setImages(initial) {
+        images = initial;
+    }
+
+    function init(options, initial) {
+        config = options;
+        if (initial) setImages(initial);
+        initKeyboard();
+
+        const more = document.getElementById('listing-more');
+        if (!more) return;
+        more.querySelector('button').addEventListener('click', loadMore);
+        if ('IntersectionObserver' in window) {
+            new IntersectionObserver(function(entries) {
+                if (entries.some(function(entry) { return entry.isIntersecting; })) loadMore();
+            }, { rootMargin: '800px' }).observe(more);
+        }
+    }
+
+    return { init: init, setImages: setImages, image: image, loadMore: loadMore };
+})();
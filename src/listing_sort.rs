@@ -0,0 +1,218 @@
+use axum::http::HeaderMap;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::natural_sort::NameOrder;
+use crate::session;
+use crate::zip_handler::ZipEntry;
+
+/// 最後に選んだ並び順をブラウザごとに覚えておくCookie
+pub const SORT_COOKIE: &str = "mvu_sort";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Modified,
+    Size,
+    Type,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 4] = [SortKey::Name, SortKey::Modified, SortKey::Size, SortKey::Type];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Modified => "mtime",
+            SortKey::Size => "size",
+            SortKey::Type => "type",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "名前",
+            SortKey::Modified => "更新日時",
+            SortKey::Size => "サイズ",
+            SortKey::Type => "種類",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        SortKey::ALL.into_iter().find(|key| key.as_str() == value)
+    }
+
+    /// 列を選んだときの最初の向き（日時とサイズは大きい順）
+    fn default_descending(self) -> bool {
+        matches!(self, SortKey::Modified | SortKey::Size)
+    }
+}
+
+/// 並べ替えに使う項目の情報（ファイルシステムとアーカイブ共通）
+pub struct SortFields<'a> {
+    pub name: &'a str,
+    pub is_dir: bool,
+    pub size: u64,
+    // 更新日時 (UNIX秒)
+    pub modified: Option<i64>,
+}
+
+impl<'a> From<&'a ZipEntry> for SortFields<'a> {
+    fn from(entry: &'a ZipEntry) -> Self {
+        Self {
+            name: &entry.name,
+            is_dir: entry.is_dir,
+            size: entry.size,
+            modified: entry.modified,
+        }
+    }
+}
+
+impl SortFields<'_> {
+    /// 種類は拡張子（ディレクトリは空）
+    pub fn kind(&self) -> String {
+        if self.is_dir {
+            return String::new();
+        }
+        let file_name = self.name.rsplit('/').next().unwrap_or(self.name);
+        match file_name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => ext.to_ascii_lowercase(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListingSort {
+    pub key: SortKey,
+    pub descending: bool,
+    // クエリパラメータで指定された（Cookieに保存する）か
+    explicit: bool,
+}
+
+impl ListingSort {
+    /// `?sort=mtime&order=desc`、なければCookieに保存した並び順
+    pub fn from_request(params: &HashMap<String, String>, headers: &HeaderMap) -> Self {
+        if let Some(key) = params.get("sort").and_then(|s| SortKey::parse(s)) {
+            let descending = match params.get("order").map(String::as_str) {
+                Some("desc") => true,
+                Some("asc") => false,
+                _ => key.default_descending(),
+            };
+            return Self { key, descending, explicit: true };
+        }
+
+        session::cookie_value(headers, SORT_COOKIE)
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let (key, order) = value.split_once('.')?;
+        Some(Self {
+            key: SortKey::parse(key)?,
+            descending: order == "desc",
+            explicit: false,
+        })
+    }
+
+    fn order_str(&self) -> &'static str {
+        if self.descending { "desc" } else { "asc" }
+    }
+
+    /// 並び順を指定されたときだけ Set-Cookie する
+    pub fn cookie(&self) -> Option<String> {
+        self.explicit.then(|| {
+            format!(
+                "{}={}.{}; Path=/; Max-Age=31536000; SameSite=Lax",
+                SORT_COOKIE,
+                self.key.as_str(),
+                self.order_str()
+            )
+        })
+    }
+
+    /// 現在の並び順のクエリ（`sort=mtime&order=desc`）
+    pub fn query(&self) -> String {
+        format!("sort={}&order={}", self.key.as_str(), self.order_str())
+    }
+
+    /// 列のリンク先（選択中の列なら向きを反転）
+    pub fn query_for(&self, key: SortKey) -> String {
+        let descending = if key == self.key { !self.descending } else { key.default_descending() };
+        format!("?sort={}&order={}", key.as_str(), if descending { "desc" } else { "asc" })
+    }
+
+    /// 同じ値のものは名前順
+    pub fn cmp(&self, a: &SortFields, b: &SortFields, names: NameOrder) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Name => names.cmp(a.name, b.name),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Type => b.is_dir.cmp(&a.is_dir).then_with(|| a.kind().cmp(&b.kind())),
+        };
+        let ordering = if self.descending { ordering.reverse() } else { ordering };
+        ordering.then_with(|| names.cmp(a.name, b.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header;
+
+    fn fields(name: &str, is_dir: bool, size: u64, modified: i64) -> SortFields<'_> {
+        SortFields { name, is_dir, size, modified: Some(modified) }
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_from_query_and_cookie() {
+        let headers = HeaderMap::new();
+        assert_eq!(ListingSort::from_request(&HashMap::new(), &headers), ListingSort::default());
+
+        let sort = ListingSort::from_request(&params(&[("sort", "mtime")]), &headers);
+        assert_eq!(sort.key, SortKey::Modified);
+        assert!(sort.descending);
+        assert_eq!(sort.cookie().unwrap(), "mvu_sort=mtime.desc; Path=/; Max-Age=31536000; SameSite=Lax");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "mvu_sort=size.asc".parse().unwrap());
+        let sort = ListingSort::from_request(&HashMap::new(), &headers);
+        assert_eq!((sort.key, sort.descending), (SortKey::Size, false));
+        assert_eq!(sort.cookie(), None);
+
+        // 不正な値は無視
+        let sort = ListingSort::from_request(&params(&[("sort", "bogus")]), &headers);
+        assert_eq!(sort.key, SortKey::Size);
+    }
+
+    #[test]
+    fn test_cmp() {
+        let names = NameOrder::default();
+        let mut items = [
+            fields("b.md", false, 10, 300),
+            fields("a.txt", false, 30, 100),
+            fields("docs", true, 0, 200),
+            fields("c.md", false, 20, 100),
+        ];
+
+        let sort = ListingSort { key: SortKey::Modified, descending: true, explicit: false };
+        items.sort_by(|a, b| sort.cmp(a, b, names));
+        let order: Vec<_> = items.iter().map(|f| f.name).collect();
+        assert_eq!(order, vec!["b.md", "docs", "a.txt", "c.md"]);
+
+        let sort = ListingSort { key: SortKey::Type, descending: false, explicit: false };
+        items.sort_by(|a, b| sort.cmp(a, b, names));
+        let order: Vec<_> = items.iter().map(|f| f.name).collect();
+        assert_eq!(order, vec!["docs", "b.md", "c.md", "a.txt"]);
+
+        let sort = ListingSort { key: SortKey::Size, descending: false, explicit: false };
+        assert_eq!(sort.query_for(SortKey::Size), "?sort=size&order=desc");
+        assert_eq!(sort.query_for(SortKey::Name), "?sort=name&order=asc");
+    }
+}
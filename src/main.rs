@@ -1,16 +1,53 @@
+mod archive;
 mod cli;
+mod documents;
+mod download;
+mod file_index;
+mod front_matter;
 mod handler;
+mod heading;
+mod indexer;
+mod link_index;
+mod listing_sort;
 mod markdown;
+mod natural_sort;
+mod obsidian;
+mod position;
+mod reader;
+mod search;
 mod server;
+mod session;
+mod sevenz_handler;
+mod tag_index;
+mod tar_handler;
+mod thumbnail;
+mod view_mode;
 mod watcher;
 mod zip_handler;
 
 use clap::Parser;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 
 use cli::Args;
+use archive::ArchiveIndexCache;
 use handler::AppState;
+use indexer::{IndexJob, Indexes};
+use natural_sort::NameOrder;
+use position::PositionStore;
+use session::PasswordStore;
+use thumbnail::ThumbnailCache;
+
+/// 読書位置などの保存先 ($XDG_STATE_HOME/mvu、なければ ~/.local/state/mvu)
+fn default_state_dir() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("mvu")
+}
 
 #[tokio::main]
 async fn main() {
@@ -21,20 +58,53 @@ async fn main() {
     // ファイル変更通知用のチャンネル
     let (reload_tx, _) = broadcast::channel(100);
 
-    // ファイル監視を開始
+    // 「ファイルへ移動」・全文検索・タグ・リンク元の索引はバックグラウンドで作る
+    let indexes = Indexes::new(base_dir.clone());
+    let index_tx = indexes.spawn();
+
+    // ファイル監視を開始（リロード通知を先に送り、索引の更新は索引スレッドに任せる）
     let watcher_tx = reload_tx.clone();
     let watch_path = base_dir.clone();
     std::thread::spawn(move || {
         if let Ok((_watcher, mut rx)) = watcher::start_watcher(watch_path) {
-            while let Ok(()) = rx.blocking_recv() {
+            loop {
+                let job = match rx.blocking_recv() {
+                    Ok(paths) => IndexJob::Update(paths),
+                    // 変更が多すぎて取りこぼしたら索引を作り直す
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("[INDEX] {} change notifications dropped, rebuilding", skipped);
+                        IndexJob::Rebuild
+                    }
+                    Err(RecvError::Closed) => break,
+                };
                 let _ = watcher_tx.send(());
+                if index_tx.send(job).is_err() {
+                    break;
+                }
             }
         }
     });
 
+    let cache_dir = args
+        .cache_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("mvu_thumbnails"));
+
     let state = AppState {
         base_dir: Arc::new(base_dir),
         reload_tx,
+        archive_cache: Arc::new(ArchiveIndexCache::new()),
+        zip_passwords: Arc::new(PasswordStore::new()),
+        thumbnails: Arc::new(ThumbnailCache::new(cache_dir)),
+        positions: Arc::new(PositionStore::load(args.state_dir.clone().unwrap_or_else(default_state_dir))),
+        name_order: NameOrder::new(args.sort_ignore_case),
+        readme_names: Arc::new(args.readme_names.clone()),
+        index_as_page: args.index_as_page,
+        toc_depth: args.toc_depth as usize,
+        file_index: indexes.files,
+        search_index: indexes.search,
+        tag_index: indexes.tags,
+        link_index: indexes.links,
     };
 
     if let Err(e) = server::start(state, &args.host, args.port).await {
@@ -1,10 +1,22 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use std::fs;
-use std::io::{BufRead, BufReader};
-use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+
+use crate::front_matter;
+use crate::heading::{self, Heading};
+use crate::link_index::LinkIndex;
+use crate::natural_sort::NameOrder;
+use crate::obsidian::{self, Vault};
+use crate::reader;
 
 const RELOAD_HTML: &str = r#"<script src="/__reload__.js"></script>
+<script src="/__palette__.js"></script>
+<script src="/__position__.js"></script>
+<script>mvuPosition.trackHeadings();</script>
+<script src="/__permalink__.js"></script>
 <style>
 * { box-sizing: border-box; }
 body {
@@ -64,12 +76,13 @@ body {
 }
 .menu-tab {
     flex: 1;
-    padding: 1rem;
+    padding: 1rem 0.25rem;
     text-align: center;
+    white-space: nowrap;
     background: transparent;
     border: none;
     cursor: pointer;
-    font-size: 1rem;
+    font-size: 0.9rem;
     color: #666;
     transition: all 0.2s;
     border-bottom: 3px solid transparent;
@@ -94,19 +107,75 @@ body {
 .menu-panel.active {
     display: block;
 }
+.backlinks-count, .backlinks-empty {
+    color: #6b7280;
+    font-size: 0.85rem;
+    margin-bottom: 0.5rem;
+}
+.backlinks {
+    list-style: none;
+    padding-left: 0;
+}
+.backlinks li {
+    margin: 0 0 0.75rem;
+    padding-bottom: 0.75rem;
+    border-bottom: 1px solid #e5e7eb;
+}
+.backlinks a {
+    font-weight: 600;
+    border-bottom: none;
+}
+.backlink-context {
+    margin-top: 0.25rem;
+    color: #6b7280;
+    font-size: 0.8rem;
+    line-height: 1.5;
+    word-break: break-word;
+}
 #toc-panel ul {
     list-style: none;
     padding-left: 0;
 }
+#toc-panel ul ul {
+    padding-left: 1rem;
+}
 #toc-panel li {
-    margin: 0.5rem 0;
+    margin: 0.25rem 0;
+}
+#toc-panel li.collapsed > ul {
+    display: none;
+}
+#toc-panel .toc-item {
+    display: flex;
+    align-items: center;
+}
+#toc-panel .toc-toggle {
+    flex: none;
+    width: 1.5rem;
+    height: 1.5rem;
+    border: none;
+    background: none;
+    color: #9ca3af;
+    cursor: pointer;
+    font-size: 0.75rem;
+    transition: transform 0.2s;
+}
+#toc-panel li.collapsed > .toc-item > .toc-toggle {
+    transform: rotate(-90deg);
+}
+#toc-panel .toc-spacer {
+    flex: none;
+    width: 1.5rem;
 }
 #toc-panel a {
+    flex: 1;
+    min-width: 0;
     color: #374151;
     text-decoration: none;
     display: block;
-    padding: 0.5rem;
+    padding: 0.4rem 0.5rem;
     border-radius: 6px;
+    border-left: 3px solid transparent;
     transition: all 0.2s;
 }
 #toc-panel a:hover {
@@ -114,10 +183,59 @@ body {
     color: white;
     transform: translateX(4px);
 }
+#toc-panel a.active {
+    background: #eef2ff;
+    color: #4f46e5;
+    border-left-color: #667eea;
+    font-weight: 600;
+}
 #toc-panel .toc-h1 { font-weight: 600; font-size: 1rem; }
-#toc-panel .toc-h2 { padding-left: 1rem; font-size: 0.95rem; }
-#toc-panel .toc-h3 { padding-left: 2rem; font-size: 0.9rem; }
-#toc-panel .toc-h4 { padding-left: 3rem; font-size: 0.85rem; }
+#toc-panel .toc-h2 { font-size: 0.95rem; }
+#toc-panel .toc-h3 { font-size: 0.9rem; }
+#toc-panel .toc-h4, #toc-panel .toc-h5, #toc-panel .toc-h6 { font-size: 0.85rem; }
+#toc-controls {
+    display: flex;
+    gap: 0.5rem;
+    margin-bottom: 0.5rem;
+}
+#toc-controls button {
+    padding: 0.2rem 0.6rem;
+    border: 1px solid #e5e7eb;
+    border-radius: 6px;
+    background: white;
+    color: #6b7280;
+    font-size: 0.8rem;
+    cursor: pointer;
+}
+#toc-controls button:hover {
+    color: #667eea;
+    border-color: #667eea;
+}
+#menu-pin {
+    display: none;
+    flex: none;
+    width: 3rem;
+    background: transparent;
+    border: none;
+    border-bottom: 3px solid transparent;
+    cursor: pointer;
+    font-size: 1rem;
+    opacity: 0.5;
+}
+#menu-pin:hover, #menu-pin.active {
+    opacity: 1;
+}
+@media (min-width: 1200px) {
+    #menu-pin { display: block; }
+    body.menu-pinned #side-menu {
+        left: 0;
+        box-shadow: 1px 0 0 #e5e7eb;
+    }
+    body.menu-pinned #menu-toggle { display: none; }
+    body.menu-pinned {
+        margin-left: max(320px, calc((100vw - 850px) / 2));
+    }
+}
 #file-tree ul {
     list-style: none;
     padding-left: 0;
@@ -231,11 +349,101 @@ blockquote {
     padding: 1rem 1.5rem;
     border-radius: 0 8px 8px 0;
 }
+.callout {
+    --callout-color: 102, 126, 234;
+    margin: 1.5rem 0;
+    border-left: 4px solid rgb(var(--callout-color));
+    border-radius: 0 8px 8px 0;
+    background: rgba(var(--callout-color), 0.08);
+    padding: 0.75rem 1.25rem;
+}
+.callout-title {
+    display: flex;
+    align-items: center;
+    gap: 0.5rem;
+    font-weight: 600;
+    color: rgb(var(--callout-color));
+}
+summary.callout-title { cursor: pointer; }
+.callout-content > :first-child { margin-top: 0.5rem; }
+.callout-content > :last-child { margin-bottom: 0; }
+.callout-abstract, .callout-todo { --callout-color: 8, 145, 178; }
+.callout-info { --callout-color: 37, 99, 235; }
+.callout-tip, .callout-success { --callout-color: 22, 163, 74; }
+.callout-question { --callout-color: 217, 119, 6; }
+.callout-warning { --callout-color: 234, 88, 12; }
+.callout-failure, .callout-danger, .callout-bug { --callout-color: 220, 38, 38; }
+.callout-example { --callout-color: 124, 58, 237; }
+.callout-quote { --callout-color: 107, 114, 128; }
+.wiki-link-unresolved {
+    color: #9ca3af;
+    border-bottom: 1px dashed #9ca3af;
+}
+.wiki-embed:not(img)::before { content: "📄 "; }
+.inline-tag {
+    padding: 0.05rem 0.5rem;
+    border-radius: 999px;
+    background: #eef2ff;
+    color: #4f46e5;
+    font-size: 0.9em;
+}
+.inline-tag:hover {
+    background: #667eea;
+    color: white;
+}
 ul, ol {
     padding-left: 2rem;
     margin: 1rem 0;
 }
 li { margin: 0.5rem 0; }
+.front-matter {
+    margin: 0 0 1.5rem;
+    padding: 1rem 1.25rem;
+    border: 1px solid #e5e7eb;
+    border-radius: 12px;
+    background: #f9fafb;
+    font-size: 0.9rem;
+}
+.front-matter-title {
+    font-size: 2rem;
+    font-weight: 700;
+    line-height: 1.3;
+    color: #667eea;
+    margin-bottom: 0.5rem;
+}
+.front-matter-meta {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 0.4rem;
+    align-items: center;
+}
+.front-matter-date { color: #6b7280; margin-right: 0.25rem; }
+.front-matter-draft {
+    padding: 0.1rem 0.6rem;
+    border-radius: 999px;
+    background: #fef3c7;
+    color: #92400e;
+    font-weight: 600;
+}
+.front-matter-tag {
+    padding: 0.1rem 0.6rem;
+    border-radius: 999px;
+    background: #eef2ff;
+    color: #4f46e5;
+    text-decoration: none;
+}
+.front-matter-tag:hover {
+    background: #667eea;
+    color: white;
+}
+.front-matter-fields {
+    display: grid;
+    grid-template-columns: max-content 1fr;
+    gap: 0.2rem 1rem;
+    margin: 0.75rem 0 0;
+}
+.front-matter-fields dt { color: #6b7280; }
+.front-matter-fields dd { margin: 0; word-break: break-word; }
 table {
     border-collapse: collapse;
     width: 100%;
@@ -277,35 +485,133 @@ hr {
 }
 </style>"#;
 
-pub async fn convert_to_html(file_path: &PathBuf, relative_path: &str, base_dir: &PathBuf) -> Result<String, String> {
+pub async fn convert_to_html(file_path: &Path, vault: &Vault<'_>, order: NameOrder, toc_depth: usize) -> Result<String, String> {
+    let source = tokio::fs::read_to_string(file_path)
+        .await
+        .map_err(|e| format!("Failed to read markdown file: {}", e))?;
+
+    // コマンドをログ出力
+    eprintln!("[unidoc] Running: unidoc for {}", file_path.display());
+
+    convert_source_to_html(&source, file_path.parent(), vault, order, toc_depth).await
+}
+
+/// Markdownのテキストをページにする（アーカイブ内のファイルなど、ディスク上にないもの用）
+/// `source_dir` は相対パスの取り込み（`@import` など）の基準になるディレクトリ
+/// `vault` はファイルツリーの位置、ウィキリンクとリンク元の解決に使う
+pub async fn convert_source_to_html(
+    source: &str,
+    source_dir: Option<&Path>,
+    vault: &Vault<'_>,
+    order: NameOrder,
+    toc_depth: usize,
+) -> Result<String, String> {
     // ヘッダー用の一時ファイル
-    let temp_header = std::env::temp_dir().join("mvu_reload_header.html");
-    std::fs::write(&temp_header, RELOAD_HTML)
+    let temp_header = TempFile::write("mvu_reload_header", RELOAD_HTML)
         .map_err(|e| format!("Failed to write header.html: {}", e))?;
 
+    // front matter は取り除いて、本文の前にメタデータとして表示する
+    let (front_matter, body) = front_matter::split(source);
+    let header_html = front_matter
+        .as_ref()
+        .map(|front_matter| {
+            let has_title_heading = heading::parse_headings(body).iter().any(|heading| heading.level == 1);
+            front_matter.header_html(!has_title_heading)
+        })
+        .unwrap_or_default();
+
+    // ウィキリンク・コールアウトなどの Obsidian の記法は unidoc に渡す前に書き換える
+    let prepared = obsidian::prepare(body, vault);
+
     // サイドメニュー用の一時ファイル
-    let side_menu_html = generate_side_menu(file_path, relative_path, base_dir)?;
-    let temp_body = std::env::temp_dir().join("mvu_side_menu.html");
-    std::fs::write(&temp_body, side_menu_html)
+    let side_menu_html = generate_side_menu(&prepared.source, vault, order, toc_depth)? + &header_html;
+    let temp_body = TempFile::write("mvu_side_menu", &side_menu_html)
         .map_err(|e| format!("Failed to write side_menu.html: {}", e))?;
 
-    // コマンドをログ出力
-    eprintln!("[unidoc] Running: unidoc for {}", file_path.display());
+    let mut command = Command::new("unidoc");
+    command.arg("-s").arg("-H").arg(&temp_header.0).arg("-B").arg(&temp_body.0);
+    let html = prepared.finish(&run_unidoc(command, &prepared.source, source_dir).await?);
+
+    match front_matter.and_then(|front_matter| front_matter.title) {
+        Some(title) => Ok(replace_title(&html, &title)),
+        None => Ok(html),
+    }
+}
+
+/// unidoc に渡す一時ファイル（同時に変換しても他のページのリンク元やツリーと混ざらないよう、変換ごとに名前を変える）
+/// 変換が終わったら消す
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn write(prefix: &str, contents: &str) -> std::io::Result<Self> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-{}.html",
+            prefix,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, contents)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// `<title>` を front matter のタイトルにする
+fn replace_title(html: &str, title: &str) -> String {
+    match (html.find("<title>"), html.find("</title>")) {
+        (Some(start), Some(end)) if start < end => {
+            format!("{}<title>{}{}", &html[..start], html_escape(title), &html[end..])
+        }
+        _ => html.to_string(),
+    }
+}
+
+/// ヘッダーやサイドメニューを付けずに本文だけをHTMLにする（一覧に埋め込むREADME用）
+pub async fn convert_to_fragment(file_path: &Path, vault: &Vault<'_>) -> Result<String, String> {
+    let source = tokio::fs::read_to_string(file_path)
+        .await
+        .map_err(|e| format!("Failed to read markdown file: {}", e))?;
+    let (_, body) = front_matter::split(&source);
+    let prepared = obsidian::prepare(body, vault);
+    Ok(prepared.finish(&run_unidoc(Command::new("unidoc"), &prepared.source, file_path.parent()).await?))
+}
+
+/// unidoc に標準入力から渡して変換する
+/// unidoc が解釈できない見出しは前もって書き換え、見出しのidは目次と同じものに付け直す
+async fn run_unidoc(mut command: Command, source: &str, source_dir: Option<&Path>) -> Result<String, String> {
+    if let Some(dir) = source_dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        command.current_dir(dir);
+    }
+    let mut child = command
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute unidoc: {}", e))?;
 
-    let output = Command::new("unidoc")
-        .arg("-s")
-        .arg("-H")
-        .arg(&temp_header)
-        .arg("-B")
-        .arg(&temp_body)
-        .arg("--")
-        .arg(file_path)
-        .output()
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(heading::normalize_headings(source).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to unidoc: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
         .await
         .map_err(|e| format!("Failed to execute unidoc: {}", e))?;
 
     if output.status.success() {
-        String::from_utf8(output.stdout).map_err(|e| format!("Invalid UTF-8 in output: {}", e))
+        String::from_utf8(output.stdout)
+            .map(|html| heading::rewrite_heading_ids(&html, &heading::parse_headings(source)))
+            .map_err(|e| format!("Invalid UTF-8 in output: {}", e))
     } else {
         Err(format!(
             "unidoc failed: {}",
@@ -314,9 +620,16 @@ pub async fn convert_to_html(file_path: &PathBuf, relative_path: &str, base_dir:
     }
 }
 
-fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathBuf) -> Result<String, String> {
-    let toc = extract_toc_from_markdown(file_path)?;
-    let file_tree = generate_file_tree(base_dir, relative_path)?;
+fn generate_side_menu(source: &str, vault: &Vault, order: NameOrder, toc_depth: usize) -> Result<String, String> {
+    let toc = toc_html(source, toc_depth);
+    let file_tree = generate_file_tree(&vault.base_dir.to_path_buf(), vault.current, order, vault.link_index)?;
+    let (backlinks_tab, backlinks_panel) = match vault.link_index {
+        Some(link_index) => (
+            r#"<button class="menu-tab" data-tab="backlinks">🔗 リンク元</button>"#,
+            format!(r#"<div id="backlinks-panel" class="menu-panel">{}</div>"#, backlinks_html(link_index, vault, order)),
+        ),
+        None => ("", String::new()),
+    };
 
     let menu_html = format!(r#"
 <button id="menu-toggle" aria-label="Toggle menu">☰</button>
@@ -324,6 +637,8 @@ fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathB
     <div class="menu-tabs">
         <button class="menu-tab active" data-tab="toc">📑 目次</button>
         <button class="menu-tab" data-tab="files">📁 ファイル</button>
+        {}
+        <button id="menu-pin" title="サイドメニューを固定する" aria-label="Pin menu">📌</button>
     </div>
     <div class="menu-content">
         <div id="toc-panel" class="menu-panel active">
@@ -332,6 +647,7 @@ fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathB
         <div id="file-tree" class="menu-panel">
             {}
         </div>
+        {}
     </div>
 </div>
 <script>
@@ -346,6 +662,82 @@ fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathB
         document.body.classList.toggle('menu-open');
     }});
 
+    // 広い画面ではサイドメニューを開いたまま固定できる（ブラウザごとに記憶）
+    const menuPin = document.getElementById('menu-pin');
+    const wideScreen = window.matchMedia('(min-width: 1200px)');
+    function setPinned(pinned) {{
+        document.body.classList.toggle('menu-pinned', pinned);
+        menuPin.classList.toggle('active', pinned);
+        menuPin.title = pinned ? 'サイドメニューの固定を解除する' : 'サイドメニューを固定する';
+        try {{ localStorage.setItem('mvu-menu-pinned', pinned ? '1' : '0'); }} catch (e) {{}}
+    }}
+    function isPinned() {{
+        return document.body.classList.contains('menu-pinned') && wideScreen.matches;
+    }}
+    try {{ setPinned(localStorage.getItem('mvu-menu-pinned') === '1'); }} catch (e) {{}}
+    menuPin.addEventListener('click', function() {{
+        setPinned(!document.body.classList.contains('menu-pinned'));
+    }});
+
+    // 目次の折りたたみ
+    const tocPanel = document.getElementById('toc-panel');
+    tocPanel.querySelectorAll('.toc-toggle').forEach(function(toggle) {{
+        toggle.addEventListener('click', function() {{
+            const collapsed = this.closest('li').classList.toggle('collapsed');
+            this.setAttribute('aria-expanded', collapsed ? 'false' : 'true');
+        }});
+    }});
+    function setAllCollapsed(collapsed) {{
+        tocPanel.querySelectorAll('.toc-toggle').forEach(function(toggle) {{
+            toggle.closest('li').classList.toggle('collapsed', collapsed);
+            toggle.setAttribute('aria-expanded', collapsed ? 'false' : 'true');
+        }});
+    }}
+    const expandAll = document.getElementById('toc-expand-all');
+    const collapseAll = document.getElementById('toc-collapse-all');
+    if (expandAll) expandAll.addEventListener('click', function() {{ setAllCollapsed(false); }});
+    if (collapseAll) collapseAll.addEventListener('click', function() {{ setAllCollapsed(true); }});
+
+    // スクロール位置の見出しを目次で強調する（折りたたまれていれば開き、目次内で見える位置にスクロール）
+    const tocLinks = Array.from(tocPanel.querySelectorAll('.toc-item a'));
+    const menuContent = sideMenu.querySelector('.menu-content');
+    let activeLink = null;
+    function updateActive() {{
+        let current = null;
+        for (const link of tocLinks) {{
+            const target = document.getElementById(link.getAttribute('href').substring(1));
+            if (!target) continue;
+            if (target.getBoundingClientRect().top > 80) break;
+            current = link;
+        }}
+        if (current === activeLink) return;
+        if (activeLink) activeLink.classList.remove('active');
+        activeLink = current;
+        if (!current) return;
+        current.classList.add('active');
+        let li = current.closest('li');
+        while (li) {{
+            const parent = li.parentElement.closest('li');
+            if (parent) parent.classList.remove('collapsed');
+            li = parent;
+        }}
+        const linkRect = current.getBoundingClientRect();
+        const contentRect = menuContent.getBoundingClientRect();
+        if (linkRect.top < contentRect.top || linkRect.bottom > contentRect.bottom) {{
+            menuContent.scrollTop += linkRect.top - contentRect.top - contentRect.height / 3;
+        }}
+    }}
+    let spyTimer = null;
+    window.addEventListener('scroll', function() {{
+        if (spyTimer) return;
+        spyTimer = setTimeout(function() {{
+            spyTimer = null;
+            updateActive();
+        }}, 100);
+    }}, {{ passive: true }});
+    window.addEventListener('load', updateActive);
+    updateActive();
+
     menuTabs.forEach(tab => {{
         tab.addEventListener('click', function() {{
             const tabName = this.getAttribute('data-tab');
@@ -361,6 +753,8 @@ fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathB
                 document.getElementById('toc-panel').classList.add('active');
             }} else if (tabName === 'files') {{
                 document.getElementById('file-tree').classList.add('active');
+            }} else if (tabName === 'backlinks') {{
+                document.getElementById('backlinks-panel').classList.add('active');
             }}
         }});
     }});
@@ -379,6 +773,7 @@ fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathB
 
     // Close menu when clicking outside
     document.addEventListener('click', function(e) {{
+        if (isPinned()) return;
         const isMenuOpen = sideMenu.classList.contains('open');
         const clickedInsideMenu = sideMenu.contains(e.target);
         const clickedMenuToggle = menuToggle.contains(e.target);
@@ -390,68 +785,82 @@ fn generate_side_menu(file_path: &PathBuf, relative_path: &str, base_dir: &PathB
     }});
 }})();
 </script>
-"#, toc, file_tree);
+"#, backlinks_tab, toc, file_tree, backlinks_panel);
 
     Ok(menu_html)
 }
 
-fn extract_toc_from_markdown(file_path: &PathBuf) -> Result<String, String> {
-    let file = fs::File::open(file_path)
-        .map_err(|e| format!("Failed to open markdown file: {}", e))?;
-
-    let reader = BufReader::new(file);
-    let mut toc = String::from("<ul>");
-    let mut counter = 0;
-
-    for line in reader.lines() {
-        let line = line.map_err(|e| format!("Failed to read line: {}", e))?;
-        let trimmed = line.trim();
-
-        if trimmed.starts_with('#') && !trimmed.starts_with("####") {
-            let mut level = 0;
-            let mut chars = trimmed.chars();
-
-            // Count # characters
-            while let Some('#') = chars.next() {
-                level += 1;
-                if level > 4 {
-                    break;
-                }
-            }
-
-            if level > 0 && level <= 4 {
-                // Extract text after #
-                let text = trimmed[level..].trim().to_string();
-
-                if !text.is_empty() {
-                    // Generate ID using the same logic as unidoc
-                    let id = format!("{}-{}", level, percent_encode(&text));
-                    counter += 1;
-
-                    toc.push_str(&format!(
-                        "<li class=\"toc-h{}\"><a href=\"#{}\">{}</a></li>",
-                        level, id, html_escape(&text)
-                    ));
-                }
-            }
-        }
+/// この文書にリンクしている文書の一覧（リンクのある行を添える）
+fn backlinks_html(link_index: &LinkIndex, vault: &Vault, order: NameOrder) -> String {
+    if !link_index.is_ready() {
+        return String::from("<p class=\"backlinks-empty\">索引を作成中です</p>");
     }
-
-    toc.push_str("</ul>");
-
-    if counter == 0 {
-        return Ok(String::from("<p>目次がありません</p>"));
+    let backlinks = link_index.backlinks(vault.current, vault.file_index, order);
+    if backlinks.is_empty() {
+        return String::from("<p class=\"backlinks-empty\">この文書へのリンクはありません</p>");
     }
 
-    Ok(toc)
-}
+    let mut html = format!("<p class=\"backlinks-count\">{} 件</p><ul class=\"backlinks\">", backlinks.len());
+    for backlink in &backlinks {
+        html.push_str(&format!(
+            "<li><a href=\"/{}\" title=\"{}\">{}</a><div class=\"backlink-context\">{}</div></li>",
+            html_escape(&reader::encode_url(&backlink.path)),
+            html_escape(&backlink.path),
+            html_escape(&backlink.title),
+            html_escape(&backlink.context)
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+/// 見出しから目次のHTMLを作る（`depth` より深い見出しは入れない）
+/// 見出しのレベルに合わせて入れ子のリストにし、子のある項目には折りたたみボタンを付ける
+fn toc_html(source: &str, depth: usize) -> String {
+    let headings: Vec<Heading> = heading::parse_headings(source)
+        .into_iter()
+        .filter(|heading| heading.level <= depth)
+        .collect();
+    if headings.is_empty() {
+        return String::from("<p>目次がありません</p>");
+    }
 
-fn percent_encode(input: &str) -> String {
-    const CUSTOM_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
-    utf8_percent_encode(input, CUSTOM_ENCODE_SET).to_string()
+    let mut index = 0;
+    let tree = toc_tree_html(&headings, &mut index, 0);
+    let controls = if headings.windows(2).any(|pair| pair[1].level > pair[0].level) {
+        r#"<div id="toc-controls"><button id="toc-expand-all">すべて開く</button><button id="toc-collapse-all">すべて閉じる</button></div>"#
+    } else {
+        ""
+    };
+    format!("{}{}", controls, tree)
+}
+
+/// `parent_level` より深い見出しが続く間を1つの `<ul>` にする
+fn toc_tree_html(headings: &[Heading], index: &mut usize, parent_level: usize) -> String {
+    let mut html = String::from("<ul>");
+    while let Some(heading) = headings.get(*index).filter(|heading| heading.level > parent_level) {
+        *index += 1;
+        let children = toc_tree_html(headings, index, heading.level);
+        let has_children = children != "<ul></ul>";
+        let toggle = if has_children {
+            r#"<button class="toc-toggle" aria-expanded="true" aria-label="折りたたむ">▾</button>"#
+        } else {
+            r#"<span class="toc-spacer"></span>"#
+        };
+        html.push_str(&format!(
+            "<li class=\"toc-h{}\"><div class=\"toc-item\">{}<a href=\"#{}\">{}</a></div>{}</li>",
+            heading.level,
+            toggle,
+            heading.id,
+            html_escape(&heading.text),
+            if has_children { children.as_str() } else { "" }
+        ));
+    }
+    html.push_str("</ul>");
+    html
 }
 
-fn html_escape(s: &str) -> String {
+pub fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -459,12 +868,13 @@ fn html_escape(s: &str) -> String {
         .replace('\'', "&#39;")
 }
 
-pub fn generate_file_tree_html(base_dir: &PathBuf, current_path: &str) -> Result<String, String> {
-    generate_file_tree(base_dir, current_path)
+pub fn generate_file_tree_html(base_dir: &PathBuf, current_path: &str, order: NameOrder, titles: Option<&LinkIndex>) -> Result<String, String> {
+    generate_file_tree(base_dir, current_path, order, titles)
 }
 
-fn generate_file_tree(base_dir: &PathBuf, current_path: &str) -> Result<String, String> {
-    fn build_tree(dir: &PathBuf, prefix: &str, current: &str, depth: usize) -> Result<String, String> {
+/// `titles` があれば front matter のタイトルをリンクの索引から引く
+fn generate_file_tree(base_dir: &PathBuf, current_path: &str, order: NameOrder, titles: Option<&LinkIndex>) -> Result<String, String> {
+    fn build_tree(dir: &PathBuf, prefix: &str, current: &str, depth: usize, order: NameOrder, titles: Option<&LinkIndex>) -> Result<String, String> {
         if depth > 3 {
             return Ok(String::new());
         }
@@ -476,7 +886,7 @@ fn generate_file_tree(base_dir: &PathBuf, current_path: &str) -> Result<String,
             .filter_map(|e| e.ok())
             .collect();
 
-        entries.sort_by_key(|e| e.path());
+        entries.sort_by(|a, b| order.cmp(&a.file_name().to_string_lossy(), &b.file_name().to_string_lossy()));
 
         // ディレクトリを先に
         entries.sort_by(|a, b| {
@@ -511,22 +921,24 @@ fn generate_file_tree(base_dir: &PathBuf, current_path: &str) -> Result<String,
                 ));
 
                 if current.starts_with(&link_path) {
-                    if let Ok(subtree) = build_tree(&path, &link_path, current, depth + 1) {
+                    if let Ok(subtree) = build_tree(&path, &link_path, current, depth + 1, order, titles) {
                         html.push_str(&subtree);
                     }
                 }
 
                 html.push_str("</li>");
             } else {
-                let class = if name.ends_with(".md") || name.ends_with(".mkd") {
-                    "markdown"
-                } else {
-                    "file"
-                };
+                let is_markdown = name.ends_with(".md") || name.ends_with(".mkd");
+                let class = if is_markdown { "markdown" } else { "file" };
+                // front matter にタイトルがあればファイル名の代わりに出す
+                let label = titles
+                    .filter(|_| is_markdown)
+                    .and_then(|titles| titles.front_matter_title(&link_path))
+                    .unwrap_or_else(|| name.to_string());
 
                 html.push_str(&format!(
-                    r#"<li><a href="/{}" class="{}"{}>{}</a></li>"#,
-                    html_escape(&link_path), class, style, html_escape(name)
+                    r#"<li><a href="/{}" class="{}" title="{}"{}>{}</a></li>"#,
+                    html_escape(&link_path), class, html_escape(name), style, html_escape(&label)
                 ));
             }
         }
@@ -535,5 +947,68 @@ fn generate_file_tree(base_dir: &PathBuf, current_path: &str) -> Result<String,
         Ok(html)
     }
 
-    build_tree(base_dir, "", current_path, 0)
+    build_tree(base_dir, "", current_path, 0, order, titles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents;
+
+    #[test]
+    fn test_toc_is_nested_by_level() {
+        let source = "# Title\n\n## A\n\n#### A.1\n\n## B\n\n### B.1\n";
+        let toc = toc_html(source, 6);
+        assert!(toc.starts_with("<div id=\"toc-controls\">"));
+        let expected = concat!(
+            "<ul><li class=\"toc-h1\"><div class=\"toc-item\"><button class=\"toc-toggle\" aria-expanded=\"true\" aria-label=\"折りたたむ\">▾</button><a href=\"#1-Title\">Title</a></div>",
+            "<ul><li class=\"toc-h2\"><div class=\"toc-item\"><button class=\"toc-toggle\" aria-expanded=\"true\" aria-label=\"折りたたむ\">▾</button><a href=\"#2-A\">A</a></div>",
+            "<ul><li class=\"toc-h4\"><div class=\"toc-item\"><span class=\"toc-spacer\"></span><a href=\"#4-A%2E1\">A.1</a></div></li></ul></li>",
+        );
+        assert!(toc.contains(expected), "{}", toc);
+        assert!(toc.contains("<a href=\"#3-B%2E1\">B.1</a></div></li></ul></li></ul></li></ul>"));
+
+        // 深さで絞る
+        let shallow = toc_html(source, 2);
+        assert!(!shallow.contains("A.1") && !shallow.contains("B.1"));
+        assert_eq!(toc_html("text only\n", 6), "<p>目次がありません</p>");
+    }
+
+    #[test]
+    fn test_side_menu_backlinks_tab() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_side_menu_backlinks");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("a.md"), "# A\n").unwrap();
+        std::fs::write(temp_dir.join("b.md"), "# B & C\n\nSee [a](a.md).\n").unwrap();
+
+        let link_index = LinkIndex::new(temp_dir.clone());
+        link_index.replace(&documents::collect(&temp_dir));
+        let mut vault = Vault { base_dir: &temp_dir, current: "a.md", file_index: None, link_index: Some(&link_index) };
+        let menu = generate_side_menu("# A\n", &vault, NameOrder::default(), 6).unwrap();
+        assert!(menu.contains("data-tab=\"backlinks\""));
+        assert!(menu.contains("<li><a href=\"/b.md\" title=\"b.md\">B &amp; C</a><div class=\"backlink-context\">See [a](a.md).</div></li>"));
+
+        vault.current = "b.md";
+        assert!(generate_side_menu("", &vault, NameOrder::default(), 6).unwrap().contains("この文書へのリンクはありません"));
+
+        // アーカイブ内の文書などではタブを出さない
+        vault.link_index = None;
+        assert!(!generate_side_menu("", &vault, NameOrder::default(), 6).unwrap().contains("data-tab=\"backlinks\""));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_temp_file_is_unique_and_removed() {
+        let first = TempFile::write("mvu_test_temp", "a").unwrap();
+        let second = TempFile::write("mvu_test_temp", "b").unwrap();
+        assert_ne!(first.0, second.0);
+        assert_eq!(std::fs::read_to_string(&first.0).unwrap(), "a");
+
+        let path = first.0.clone();
+        drop(first);
+        assert!(!path.exists());
+    }
 }
@@ -0,0 +1,103 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// 一覧・ファイルツリー・アーカイブ・ギャラリーで共通の名前の並び順
+/// 数字の並びは数値として比較する (`page2` < `page10`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NameOrder {
+    pub ignore_case: bool,
+}
+
+impl NameOrder {
+    pub fn new(ignore_case: bool) -> Self {
+        Self { ignore_case }
+    }
+
+    pub fn cmp(&self, a: &str, b: &str) -> Ordering {
+        natural_cmp(a, b, self.ignore_case)
+    }
+}
+
+pub fn natural_cmp(a: &str, b: &str, ignore_case: bool) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_digits(&mut a_chars);
+                let y = take_digits(&mut b_chars);
+                let x_trimmed = x.trim_start_matches('0');
+                let y_trimmed = y.trim_start_matches('0');
+                // 桁数 → 値 → ゼロ埋めの長さ（`01` と `1` を区別する）
+                x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x.len().cmp(&y.len()))
+            }
+            (Some(_), Some(_)) => {
+                let x = a_chars.next().unwrap_or_default();
+                let y = b_chars.next().unwrap_or_default();
+                if ignore_case {
+                    x.to_lowercase().cmp(y.to_lowercase())
+                } else {
+                    x.cmp(&y)
+                }
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // 大文字小文字だけが違う名前も順序が決まるようにする
+    if ignore_case {
+        a.cmp(b)
+    } else {
+        Ordering::Equal
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str], ignore_case: bool) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b, ignore_case));
+        names
+    }
+
+    #[test]
+    fn test_numbers_compare_by_value() {
+        assert_eq!(
+            sorted(&["page10.jpg", "page2.jpg", "page1.jpg", "page02.jpg"], false),
+            vec!["page1.jpg", "page2.jpg", "page02.jpg", "page10.jpg"]
+        );
+        assert_eq!(
+            sorted(&["ch1/p10", "ch1/p9", "ch10/p1", "ch2/p1"], false),
+            vec!["ch1/p9", "ch1/p10", "ch2/p1", "ch10/p1"]
+        );
+        assert_eq!(natural_cmp("a99999999999999999999b", "a100000000000000000000", false), Ordering::Less);
+    }
+
+    #[test]
+    fn test_case_option() {
+        assert_eq!(sorted(&["b.md", "Z.md", "a.md"], false), vec!["Z.md", "a.md", "b.md"]);
+        assert_eq!(sorted(&["b.md", "Z.md", "a.md", "A.md"], true), vec!["A.md", "a.md", "b.md", "Z.md"]);
+    }
+}
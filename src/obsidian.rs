@@ -0,0 +1,594 @@
+use std::ops::Range;
+use std::path::Path;
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use crate::file_index::FileIndex;
+use crate::heading::{self, Heading};
+use crate::link_index::LinkIndex;
+use crate::markdown::html_escape;
+use crate::reader;
+use crate::tag_index::tag_url;
+
+/// unidoc に渡す前にコールアウトを置き換える段落（変換後にHTMLへ戻す）
+const CALLOUT_START: &str = "MVU-CALLOUT-";
+const CALLOUT_END: &str = "MVU-CALLOUT-END-";
+
+/// ウィキリンク `[[Note#Heading|alias]]` / 埋め込み `![[image.png|300]]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiLink {
+    // 空なら同じ文書の見出しへのリンク（`[[#Heading]]`）
+    pub target: String,
+    pub heading: Option<String>,
+    pub alias: Option<String>,
+    pub embed: bool,
+}
+
+impl WikiLink {
+    fn parse(raw: &str) -> Option<Self> {
+        let embed = raw.starts_with('!');
+        let inner = raw.trim_start_matches('!').strip_prefix("[[")?.strip_suffix("]]")?;
+        // 表の中では `|` を `\|` と書く
+        let inner = inner.replace("\\|", "|");
+        let (link, alias) = match inner.split_once('|') {
+            Some((link, alias)) => (link, Some(alias.trim().to_string()).filter(|a| !a.is_empty())),
+            None => (inner.as_str(), None),
+        };
+        let (target, heading) = match link.split_once('#') {
+            // `[[Note#A#B]]` は最後の見出し、`[[Note#^block]]` のブロック参照は見出しなしで扱う
+            Some((target, heading)) => (target, heading.rsplit('#').next().filter(|h| !h.starts_with('^'))),
+            None => (link, None),
+        };
+        let target = target.trim().to_string();
+        let heading = heading.map(|h| h.trim().to_string()).filter(|h| !h.is_empty());
+        if target.is_empty() && heading.is_none() {
+            return None;
+        }
+        Some(Self { target, heading, alias, embed })
+    }
+
+    /// 拡張子のないリンク先は Markdown とみなす
+    pub fn file_name(&self) -> String {
+        if has_extension(&self.target) {
+            self.target.clone()
+        } else {
+            format!("{}.md", self.target)
+        }
+    }
+}
+
+fn has_extension(target: &str) -> bool {
+    let name = target.rsplit('/').next().unwrap_or(target);
+    name.rsplit_once('.')
+        .is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn is_markdown(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".md") || lower.ends_with(".mkd")
+}
+
+fn options() -> Options {
+    heading::options() | Options::ENABLE_WIKILINKS
+}
+
+/// 本文中のウィキリンク（コードブロック・インラインコード内は除く）
+pub fn wiki_links(source: &str) -> Vec<(Range<usize>, WikiLink)> {
+    let mut links = Vec::new();
+    for (event, range) in Parser::new_ext(source, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link { link_type: LinkType::WikiLink { .. }, .. })
+            | Event::Start(Tag::Image { link_type: LinkType::WikiLink { .. }, .. }) => {
+                if let Some(link) = WikiLink::parse(&source[range.clone()]) {
+                    links.push((range, link));
+                }
+            }
+            _ => {}
+        }
+    }
+    links
+}
+
+/// 本文中の `#tag`（空白か行頭の直後、数字だけのものは除く）
+pub fn inline_tags(source: &str) -> Vec<(Range<usize>, String)> {
+    let mut tags = Vec::new();
+    let mut in_code_block = false;
+    let mut link_depth = 0usize;
+    let mut scanned = 0;
+
+    for (event, range) in Parser::new_ext(source, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::Link { .. }) | Event::Start(Tag::Image { .. }) => link_depth += 1,
+            Event::End(TagEnd::Link) | Event::End(TagEnd::Image) => link_depth = link_depth.saturating_sub(1),
+            Event::Text(_) if !in_code_block && link_depth == 0 => {
+                let start = range.start.max(scanned);
+                for (offset, c) in source[start..range.end.max(start)].char_indices() {
+                    let position = start + offset;
+                    if c != '#' || position < scanned {
+                        continue;
+                    }
+                    if !source[..position].chars().next_back().is_none_or(char::is_whitespace) {
+                        continue;
+                    }
+                    // `_` などで Text が分かれることがあるので、タグの終わりは元の文字列で探す
+                    let name_start = position + 1;
+                    let length: usize = source[name_start..]
+                        .chars()
+                        .take_while(|&c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                        .map(char::len_utf8)
+                        .sum();
+                    let name = source[name_start..name_start + length].trim_end_matches('/');
+                    if name.is_empty() || name.chars().all(|c| c.is_ascii_digit()) {
+                        continue;
+                    }
+                    let end = name_start + name.len();
+                    tags.push((position..end, name.to_string()));
+                    scanned = end;
+                }
+            }
+            _ => {}
+        }
+    }
+    tags
+}
+
+/// 本文中のタグの名前（タグの索引用）
+pub fn inline_tag_names(source: &str) -> Vec<String> {
+    inline_tags(source).into_iter().map(|(_, tag)| tag).collect()
+}
+
+/// 変換する文書と、リンクを解決する索引（Obsidian と同じく、名前が一致するファイルのうち一番近いもの）
+pub struct Vault<'a> {
+    pub base_dir: &'a Path,
+    // 変換する文書の base_dir からの相対パス
+    pub current: &'a str,
+    // None のとき（アーカイブ内の文書など）はリンク先を探さず相対パスにする
+    pub file_index: Option<&'a FileIndex>,
+    // リンク元の表示に使う（None ならリンク元のタブを出さない）
+    pub link_index: Option<&'a LinkIndex>,
+}
+
+impl Vault<'_> {
+    /// リンク先のファイル（base_dir からの相対パス）
+    /// 同じディレクトリにあるもの、次にパスの短いものを選ぶ
+    pub fn resolve(&self, link: &WikiLink) -> Option<String> {
+        let index = self.file_index.filter(|index| index.is_ready())?;
+        let name = link.file_name();
+        let mut candidates = index.find_by_suffix(name.trim_start_matches('/'));
+        if candidates.is_empty() && name != link.target {
+            candidates = index.find_by_suffix(link.target.trim_start_matches('/'));
+        }
+
+        let current_dir = parent(self.current);
+        candidates.sort_by_cached_key(|path| (parent(path) != current_dir, path.matches('/').count(), path.len(), path.clone()));
+        candidates.into_iter().next()
+    }
+
+    /// 見出しのid（`own` は同じ文書へのリンクのときのその文書の見出し）
+    /// ほかの文書の見出しはリンクの索引から引く（ファイルは読まない）
+    fn heading_id(&self, path: Option<&str>, heading: &str, own: &[Heading]) -> Option<String> {
+        match path {
+            Some(path) if is_markdown(path) => self.link_index?.heading_id(path, heading),
+            Some(_) => None,
+            None => find_heading_id(own, heading),
+        }
+    }
+
+    fn link_html(&self, link: &WikiLink, own: &[Heading]) -> String {
+        let resolved = if link.target.is_empty() { None } else { self.resolve(link) };
+        let unresolved = !link.target.is_empty() && resolved.is_none() && self.file_index.is_some_and(|index| index.is_ready());
+        let url = match &resolved {
+            Some(path) => format!("/{}", reader::encode_url(path)),
+            None if link.target.is_empty() => String::new(),
+            None => reader::encode_url(&link.file_name()),
+        };
+
+        if link.embed && reader::is_image_file(&link.target) {
+            // `![[image.png|300]]` / `![[image.png|300x200]]` は大きさの指定
+            let size = link.alias.as_deref().and_then(|alias| {
+                let (width, height) = alias.split_once('x').unwrap_or((alias, ""));
+                width.parse::<u32>().ok().map(|width| (width, height.parse::<u32>().ok()))
+            });
+            let (alt, size) = match size {
+                Some((width, Some(height))) => (link.target.clone(), format!(" width=\"{}\" height=\"{}\"", width, height)),
+                Some((width, None)) => (link.target.clone(), format!(" width=\"{}\"", width)),
+                None => (link.alias.clone().unwrap_or_else(|| link.target.clone()), String::new()),
+            };
+            return format!("<img class=\"wiki-embed\" src=\"{}\" alt=\"{}\"{}>", html_escape(&url), html_escape(&alt), size);
+        }
+
+        let fragment = link
+            .heading
+            .as_ref()
+            .and_then(|heading| match &resolved {
+                Some(path) => self.heading_id(Some(path), heading, own),
+                None if link.target.is_empty() => self.heading_id(None, heading, own),
+                None => None,
+            })
+            .map(|id| format!("#{}", id))
+            .unwrap_or_default();
+        let text = match (&link.alias, &link.heading) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(heading)) if link.target.is_empty() => heading.clone(),
+            (None, Some(heading)) => format!("{} > {}", link.target, heading),
+            (None, None) => link.target.clone(),
+        };
+        let mut class = String::from("wiki-link");
+        if unresolved {
+            class.push_str(" wiki-link-unresolved");
+        }
+        if link.embed {
+            class.push_str(" wiki-embed");
+        }
+        format!("<a class=\"{}\" href=\"{}{}\">{}</a>", class, html_escape(&url), fragment, html_escape(&text))
+    }
+}
+
+/// 見出しのテキスト（大文字小文字と連続する空白は区別しない）からidを探す
+pub fn find_heading_id(headings: &[Heading], heading: &str) -> Option<String> {
+    let heading = heading.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    headings.iter().find(|h| h.text.to_lowercase() == heading).map(|h| h.id.clone())
+}
+
+/// 見出し（行は元の本文のもの、テキストとidはウィキリンクを書き換えた後の、レンダリング結果と同じもの）
+pub fn headings(body: &str) -> Vec<Heading> {
+    let mut headings = heading::parse_headings(body);
+    // 見出しのテキストが変わるのはウィキリンクだけ
+    if !body.contains("[[") {
+        return headings;
+    }
+    let vault = Vault { base_dir: Path::new(""), current: "", file_index: None, link_index: None };
+    let prepared = heading::parse_headings(&prepare_with(body, &vault, &[]).source);
+    if prepared.len() == headings.len() {
+        for (heading, prepared) in headings.iter_mut().zip(prepared) {
+            heading.text = prepared.text;
+            heading.id = prepared.id;
+        }
+    }
+    headings
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+/// コールアウト `> [!note] タイトル`
+#[derive(Debug, Clone, PartialEq)]
+struct Callout {
+    kind: String,
+    title: String,
+    // 折りたたみ（`-` は閉じた状態、`+` は開いた状態）
+    fold: Option<bool>,
+}
+
+impl Callout {
+    fn open_html(&self) -> String {
+        let (class, icon) = callout_style(&self.kind);
+        let title = format!(
+            "<span class=\"callout-icon\">{}</span><span class=\"callout-title-text\">{}</span>",
+            icon,
+            html_escape(&self.title)
+        );
+        match self.fold {
+            Some(open) => format!(
+                "<details class=\"callout callout-{}\" data-callout=\"{}\"{}><summary class=\"callout-title\">{}</summary><div class=\"callout-content\">",
+                class,
+                html_escape(&self.kind),
+                if open { " open" } else { "" },
+                title
+            ),
+            None => format!(
+                "<div class=\"callout callout-{}\" data-callout=\"{}\"><div class=\"callout-title\">{}</div><div class=\"callout-content\">",
+                class,
+                html_escape(&self.kind),
+                title
+            ),
+        }
+    }
+
+    fn close_html(&self) -> &'static str {
+        match self.fold {
+            Some(_) => "</div></details>",
+            None => "</div></div>",
+        }
+    }
+}
+
+/// コールアウトの種類ごとの色（CSSのクラス）とアイコン（別名は Obsidian と同じ）
+fn callout_style(kind: &str) -> (&'static str, &'static str) {
+    match kind {
+        "abstract" | "summary" | "tldr" => ("abstract", "📋"),
+        "info" => ("info", "ℹ️"),
+        "todo" => ("todo", "☑️"),
+        "tip" | "hint" | "important" => ("tip", "🔥"),
+        "success" | "check" | "done" => ("success", "✅"),
+        "question" | "help" | "faq" => ("question", "❓"),
+        "warning" | "caution" | "attention" => ("warning", "⚠️"),
+        "failure" | "fail" | "missing" => ("failure", "❌"),
+        "danger" | "error" => ("danger", "⚡"),
+        "bug" => ("bug", "🐛"),
+        "example" => ("example", "📝"),
+        "quote" | "cite" => ("quote", "💬"),
+        _ => ("note", "✏️"),
+    }
+}
+
+/// 引用の行なら `>` を除いた残り
+fn strip_quote(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let rest = trimmed.strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// リスト項目の行なら本文の始まる桁（`- ` や `1. ` の後ろ）
+fn list_content_indent(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start_matches(' ');
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    let marker = match trimmed[digits..].chars().next()? {
+        '-' | '*' | '+' if digits == 0 => 1,
+        '.' | ')' if (1..=9).contains(&digits) => digits + 1,
+        _ => return None,
+    };
+    let rest = &trimmed[marker..];
+    let spaces = rest.len() - rest.trim_start_matches(' ').len();
+    if spaces == 0 && !rest.trim().is_empty() {
+        return None;
+    }
+    Some(line.len() - trimmed.len() + marker + spaces.clamp(1, 4))
+}
+
+/// コンテナ（リスト項目）の字下げ `prefix` の後ろの引用なら `>` を除いた残り
+fn strip_quote_in<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    strip_quote(line.strip_prefix(prefix)?)
+}
+
+/// `> [!kind]± タイトル` の行
+fn parse_callout_header(line: &str) -> Option<Callout> {
+    let rest = strip_quote(line)?.trim_start().strip_prefix("[!")?;
+    let (kind, rest) = rest.split_once(']')?;
+    if kind.is_empty() || !kind.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_')) {
+        return None;
+    }
+    let (fold, title) = match rest.chars().next() {
+        Some('-') => (Some(false), &rest[1..]),
+        Some('+') => (Some(true), &rest[1..]),
+        _ => (None, rest),
+    };
+    let kind = kind.to_lowercase();
+    let title = match title.trim() {
+        "" => {
+            let mut chars = kind.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+        title => title.to_string(),
+    };
+    Some(Callout { kind, title, fold })
+}
+
+/// コールアウトを目印の段落で囲んだ本文に置き換える（入れ子にも対応）
+fn replace_callouts(source: &str, callouts: &mut Vec<Callout>) -> String {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(source.len());
+    let mut fence: Option<(char, usize)> = None;
+    // 直前のリスト項目の本文の字下げ
+    let mut list_indent: Option<usize> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let run = marker.map(|m| trimmed.chars().take_while(|c| *c == m).count()).unwrap_or(0);
+
+        if let Some((fence_char, fence_len)) = fence {
+            if marker == Some(fence_char) && run >= fence_len && trimmed[run..].trim().is_empty() {
+                fence = None;
+            }
+            output.push_str(line);
+            i += 1;
+            continue;
+        }
+        if let (Some(marker), true) = (marker, run >= 3) {
+            fence = Some((marker, run));
+            output.push_str(line);
+            i += 1;
+            continue;
+        }
+
+        // リスト項目の中のコールアウトは、目印と本文にも項目の字下げを付けて項目の中に残す
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let prefix = match list_indent {
+            Some(list_indent) if indent >= list_indent && indent <= list_indent + 3 => &line[..list_indent],
+            _ => "",
+        };
+        let Some(callout) = parse_callout_header(&line[prefix.len()..]) else {
+            if let Some(content_indent) = list_content_indent(line) {
+                list_indent = Some(content_indent);
+            } else if indent == 0 && !line.trim().is_empty() {
+                list_indent = None;
+            }
+            output.push_str(line);
+            i += 1;
+            continue;
+        };
+        i += 1;
+        let mut body = String::new();
+        while let Some(rest) = lines.get(i).and_then(|line| strip_quote_in(line, prefix)) {
+            body.push_str(rest);
+            if !rest.ends_with('\n') {
+                body.push('\n');
+            }
+            i += 1;
+        }
+
+        let number = callouts.len();
+        callouts.push(callout);
+        let body: String = replace_callouts(&body, callouts)
+            .split_inclusive('\n')
+            .map(|line| if line.trim().is_empty() { line.to_string() } else { format!("{}{}", prefix, line) })
+            .collect();
+        output.push_str(&format!(
+            "\n{}{}{}\n\n{}\n{}{}{}\n\n",
+            prefix, CALLOUT_START, number, body, prefix, CALLOUT_END, number
+        ));
+    }
+    output
+}
+
+/// unidoc に渡す前の Obsidian 記法の変換結果
+pub struct Prepared {
+    pub source: String,
+    callouts: Vec<Callout>,
+}
+
+impl Prepared {
+    /// unidoc の出力のコールアウトの目印をHTMLにする
+    pub fn finish(&self, html: &str) -> String {
+        let mut html = html.to_string();
+        for (number, callout) in self.callouts.iter().enumerate() {
+            html = html
+                .replace(&format!("<p>{}{}</p>", CALLOUT_START, number), &callout.open_html())
+                .replace(&format!("<p>{}{}</p>", CALLOUT_END, number), callout.close_html());
+        }
+        html
+    }
+}
+
+/// ウィキリンク・埋め込み・タグをHTMLに、コールアウトを目印の段落にする
+pub fn prepare(source: &str, vault: &Vault) -> Prepared {
+    prepare_with(source, vault, &headings(source))
+}
+
+/// `own` は同じ文書の見出しへのリンク（`[[#見出し]]`）に使う
+fn prepare_with(source: &str, vault: &Vault, own: &[Heading]) -> Prepared {
+    let mut callouts = Vec::new();
+    let replaced = replace_callouts(source, &mut callouts);
+
+    let mut replacements: Vec<(Range<usize>, String)> = wiki_links(&replaced)
+        .into_iter()
+        .map(|(range, link)| (range, vault.link_html(&link, own)))
+        .collect();
+    replacements.extend(inline_tags(&replaced).into_iter().map(|(range, tag)| {
+        let html = format!("<a class=\"inline-tag\" href=\"{}\">#{}</a>", html_escape(&tag_url(&tag)), html_escape(&tag));
+        (range, html)
+    }));
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = replaced;
+    for (range, html) in replacements.into_iter().rev() {
+        output.replace_range(range, &html);
+    }
+    Prepared { source: output, callouts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wiki_links() {
+        let source = "See [[Note]], [[dir/Other#Setup|the setup]] and ![[photo.png|300]].\n\n`[[not a link]]`\n\n| a |\n|---|\n| [[Table\\|alias]] |\n";
+        let links: Vec<WikiLink> = wiki_links(source).into_iter().map(|(_, link)| link).collect();
+        assert_eq!(links.len(), 4);
+        assert_eq!(links[0], WikiLink { target: "Note".to_string(), heading: None, alias: None, embed: false });
+        assert_eq!(links[1].target, "dir/Other");
+        assert_eq!(links[1].heading.as_deref(), Some("Setup"));
+        assert_eq!(links[1].alias.as_deref(), Some("the setup"));
+        assert!(links[2].embed);
+        assert_eq!(links[2].file_name(), "photo.png");
+        assert_eq!(links[3].alias.as_deref(), Some("alias"));
+        assert_eq!(links[0].file_name(), "Note.md");
+    }
+
+    #[test]
+    fn test_inline_tags() {
+        let source = "#todo first\n\nText #日記 and #snake_case_tag, not#this or #123 or `#code`.\n\n# Heading\n\n```\n#comment\n```\n\n[#link](x.md)\n";
+        assert_eq!(inline_tag_names(source), vec!["todo", "日記", "snake_case_tag"]);
+    }
+
+    #[test]
+    fn test_resolve_shortest_match() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_obsidian_resolve");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(temp_dir.join("a/deep")).unwrap();
+        std::fs::create_dir_all(temp_dir.join("b")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        for path in ["Note.md", "a/deep/Note.md", "b/Note.md", "b/Only.md", "a/img.png"] {
+            std::fs::write(temp_dir.join(path), "# Note\n\n## Set Up\n").unwrap();
+        }
+        std::fs::write(temp_dir.join("b/Linked.md"), "# Linked\n\n## See [[Only]]\n").unwrap();
+        let index = FileIndex::new(temp_dir.clone());
+        index.build();
+        let link_index = LinkIndex::new(temp_dir.clone());
+        link_index.replace(&crate::documents::collect(&temp_dir));
+
+        let link = |raw: &str| WikiLink::parse(raw).unwrap();
+        let vault = Vault { base_dir: &temp_dir, current: "b/page.md", file_index: Some(&index), link_index: None };
+        // 同じディレクトリのものが優先、なければパスの短いもの
+        assert_eq!(vault.resolve(&link("[[Note]]")).as_deref(), Some("b/Note.md"));
+        assert_eq!(vault.resolve(&link("[[deep/note]]")).as_deref(), Some("a/deep/Note.md"));
+        let vault = Vault { base_dir: &temp_dir, current: "c/page.md", file_index: Some(&index), link_index: Some(&link_index) };
+        assert_eq!(vault.resolve(&link("[[Note]]")).as_deref(), Some("Note.md"));
+        assert_eq!(vault.resolve(&link("![[img.png]]")).as_deref(), Some("a/img.png"));
+
+        assert_eq!(
+            vault.link_html(&link("[[Only#set up|setup]]"), &[]),
+            "<a class=\"wiki-link\" href=\"/b/Only.md#2-Set%20Up\">setup</a>"
+        );
+        // 見出しのidはウィキリンクを書き換えた後のテキスト（レンダリング結果と同じ）から作る
+        assert_eq!(
+            vault.link_html(&link("[[Linked#See Only]]"), &[]),
+            "<a class=\"wiki-link\" href=\"/b/Linked.md#2-See%20Only\">Linked &gt; See Only</a>"
+        );
+        assert_eq!(
+            vault.link_html(&link("[[Missing Page]]"), &[]),
+            "<a class=\"wiki-link wiki-link-unresolved\" href=\"Missing%20Page.md\">Missing Page</a>"
+        );
+        assert_eq!(
+            vault.link_html(&link("![[img.png|300]]"), &[]),
+            "<img class=\"wiki-embed\" src=\"/a/img.png\" alt=\"img.png\" width=\"300\">"
+        );
+        assert_eq!(
+            vault.link_html(&link("[[#Set Up]]"), &headings("## Set Up\n")),
+            "<a class=\"wiki-link\" href=\"#2-Set%20Up\">Set Up</a>"
+        );
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_callouts() {
+        let source = "> [!warning]- Be careful\n> Body *text*\n>\n> > [!tip]\n> > Nested\n\n```\n> [!note] in code\n```\n\n> plain quote\n";
+        let vault = Vault { base_dir: Path::new("."), current: "x.md", file_index: None, link_index: None };
+        let prepared = prepare(source, &vault);
+        assert_eq!(
+            prepared.source,
+            "\nMVU-CALLOUT-0\n\nBody *text*\n\n\nMVU-CALLOUT-1\n\nNested\n\nMVU-CALLOUT-END-1\n\n\nMVU-CALLOUT-END-0\n\n\n```\n> [!note] in code\n```\n\n> plain quote\n"
+        );
+
+        let html = prepared.finish("<p>MVU-CALLOUT-0</p><p>Body</p><p>MVU-CALLOUT-1</p><p>Nested</p><p>MVU-CALLOUT-END-1</p><p>MVU-CALLOUT-END-0</p>");
+        assert_eq!(
+            html,
+            "<details class=\"callout callout-warning\" data-callout=\"warning\"><summary class=\"callout-title\"><span class=\"callout-icon\">⚠️</span><span class=\"callout-title-text\">Be careful</span></summary><div class=\"callout-content\"><p>Body</p>\
+<div class=\"callout callout-tip\" data-callout=\"tip\"><div class=\"callout-title\"><span class=\"callout-icon\">🔥</span><span class=\"callout-title-text\">Tip</span></div><div class=\"callout-content\"><p>Nested</p></div></div></div></details>"
+        );
+    }
+
+    #[test]
+    fn test_callout_in_list_item() {
+        let source = "- Item\n\n  > [!note]\n  > Inside\n\n  continued\n\nAfter\n    > [!note] code\n";
+        let vault = Vault { base_dir: Path::new("."), current: "x.md", file_index: None, link_index: None };
+        let prepared = prepare(source, &vault);
+        assert_eq!(
+            prepared.source,
+            "- Item\n\n\n  MVU-CALLOUT-0\n\n  Inside\n\n  MVU-CALLOUT-END-0\n\n\n  continued\n\nAfter\n    > [!note] code\n"
+        );
+        assert_eq!(list_content_indent("1.  two"), Some(4));
+        assert_eq!(list_content_indent("-item"), None);
+    }
+}
@@ -0,0 +1,207 @@
+// Ctrl-P / Cmd-P の「ファイルへ移動」パレット（/__files__ でツリー全体をあいまい検索）
+(function() {
+    const STYLE = `
+#mvu-palette {
+    display: none;
+    position: fixed;
+    inset: 0;
+    z-index: 20000;
+    background: rgba(17, 24, 39, 0.4);
+    font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', 'Noto Sans', Helvetica, Arial, sans-serif;
+}
+#mvu-palette.active { display: block; }
+#mvu-palette .palette-box {
+    width: min(640px, calc(100% - 2rem));
+    margin: 10vh auto 0;
+    background: white;
+    border-radius: 12px;
+    box-shadow: 0 20px 60px rgba(0, 0, 0, 0.3);
+    overflow: hidden;
+}
+#mvu-palette input {
+    width: 100%;
+    padding: 0.9rem 1rem;
+    border: none;
+    border-bottom: 1px solid #e5e7eb;
+    font-size: 1rem;
+    outline: none;
+}
+#mvu-palette ul {
+    list-style: none;
+    margin: 0;
+    padding: 0.25rem 0;
+    max-height: 60vh;
+    overflow-y: auto;
+}
+#mvu-palette li {
+    padding: 0.5rem 1rem;
+    color: #374151;
+    font-size: 0.9rem;
+    cursor: pointer;
+    white-space: nowrap;
+    overflow: hidden;
+    text-overflow: ellipsis;
+}
+#mvu-palette li.selected {
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+}
+#mvu-palette li mark {
+    background: none;
+    color: inherit;
+    font-weight: 700;
+    text-decoration: underline;
+}
+#mvu-palette .palette-empty {
+    padding: 0.75rem 1rem;
+    color: #9ca3af;
+    font-size: 0.85rem;
+}
+`;
+
+    let palette = null;
+    let input = null;
+    let list = null;
+    let results = [];
+    let selected = 0;
+    let timer = null;
+    let requestId = 0;
+
+    function create() {
+        const style = document.createElement('style');
+        style.textContent = STYLE;
+        document.head.appendChild(style);
+
+        palette = document.createElement('div');
+        palette.id = 'mvu-palette';
+        palette.innerHTML = '<div class="palette-box"><input type="text" placeholder="ファイルへ移動..." aria-label="go to file"><ul></ul></div>';
+        document.body.appendChild(palette);
+        input = palette.querySelector('input');
+        list = palette.querySelector('ul');
+
+        palette.addEventListener('click', function(e) {
+            if (e.target === palette) close();
+        });
+        input.addEventListener('input', function() {
+            clearTimeout(timer);
+            timer = setTimeout(search, 80);
+        });
+        input.addEventListener('keydown', function(e) {
+            if (e.key === 'ArrowDown' || (e.ctrlKey && e.key === 'n')) {
+                e.preventDefault();
+                move(1);
+            } else if (e.key === 'ArrowUp' || (e.ctrlKey && e.key === 'p')) {
+                e.preventDefault();
+                move(-1);
+            } else if (e.key === 'Enter') {
+                e.preventDefault();
+                if (results[selected]) location.href = results[selected].url;
+            } else if (e.key === 'Escape') {
+                e.preventDefault();
+                close();
+            }
+            e.stopPropagation();
+        });
+    }
+
+    // 末尾に置く「全文検索」の項目
+    function fullTextEntry(query) {
+        return { url: '/__search__?q=' + encodeURIComponent(query), label: '🔎 「' + query + '」を全文検索' };
+    }
+
+    function icon(path) {
+        if (path.endsWith('/')) return '📁 ';
+        if (path.includes('::')) return '📦 ';
+        return '📄 ';
+    }
+
+    // 一致した文字を <mark> で強調する
+    function renderPath(li, result) {
+        if (result.label) {
+            li.textContent = result.label;
+            return;
+        }
+        li.appendChild(document.createTextNode(icon(result.path)));
+        const matched = new Set(result.positions);
+        Array.from(result.path).forEach(function(ch, i) {
+            if (matched.has(i)) {
+                const mark = document.createElement('mark');
+                mark.textContent = ch;
+                li.appendChild(mark);
+            } else {
+                li.appendChild(document.createTextNode(ch));
+            }
+        });
+    }
+
+    function render() {
+        list.innerHTML = '';
+        if (results.length === 0) {
+            const empty = document.createElement('div');
+            empty.className = 'palette-empty';
+            empty.textContent = input.value.trim() ? '一致するファイルはありません' : 'ファイル名やパスの一部を入力';
+            list.appendChild(empty);
+            return;
+        }
+        results.forEach(function(result, i) {
+            const li = document.createElement('li');
+            li.classList.toggle('selected', i === selected);
+            renderPath(li, result);
+            li.addEventListener('click', function() {
+                location.href = result.url;
+            });
+            list.appendChild(li);
+        });
+    }
+
+    function move(offset) {
+        if (results.length === 0) return;
+        selected = (selected + offset + results.length) % results.length;
+        render();
+        const li = list.children[selected];
+        if (li) li.scrollIntoView({ block: 'nearest' });
+    }
+
+    function search() {
+        const query = input.value.trim();
+        if (!query) {
+            results = [];
+            render();
+            return;
+        }
+        const id = ++requestId;
+        fetch('/__files__?q=' + encodeURIComponent(query))
+            .then(function(response) { return response.json(); })
+            .then(function(data) {
+                // 古いリクエストの結果は捨てる
+                if (id !== requestId) return;
+                results = data.concat([fullTextEntry(query)]);
+                selected = 0;
+                render();
+            })
+            .catch(function(e) { console.error('[palette]', e); });
+    }
+
+    function open() {
+        if (!palette) create();
+        palette.classList.add('active');
+        input.focus();
+        input.select();
+        search();
+    }
+
+    function close() {
+        palette.classList.remove('active');
+    }
+
+    document.addEventListener('keydown', function(e) {
+        if ((e.ctrlKey || e.metaKey) && !e.altKey && e.key.toLowerCase() === 'p') {
+            e.preventDefault();
+            if (palette && palette.classList.contains('active')) {
+                close();
+            } else {
+                open();
+            }
+        }
+    });
+})();
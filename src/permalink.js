@@ -0,0 +1,131 @@
+// 見出しにパーマリンク（#）と「セクションへのリンクをコピー」ボタンを付ける
+(function() {
+    const STYLE = `
+.heading-permalink {
+    position: relative;
+}
+.heading-actions {
+    display: inline-flex;
+    gap: 0.25rem;
+    margin-left: 0.5rem;
+    opacity: 0;
+    transition: opacity 0.15s;
+    vertical-align: middle;
+}
+.heading-permalink:hover .heading-actions,
+.heading-actions:focus-within {
+    opacity: 1;
+}
+.heading-actions a,
+.heading-actions button {
+    border: none;
+    background: none;
+    color: #9ca3af;
+    font-size: 0.7em;
+    line-height: 1;
+    padding: 0.15em 0.3em;
+    border-radius: 4px;
+    cursor: pointer;
+    text-decoration: none;
+}
+.heading-actions a:hover,
+.heading-actions button:hover {
+    color: #667eea;
+    background: #f3f4f6;
+}
+#mvu-copy-toast {
+    position: fixed;
+    left: 50%;
+    bottom: 2rem;
+    transform: translateX(-50%);
+    z-index: 20000;
+    padding: 0.5rem 1rem;
+    border-radius: 8px;
+    background: rgba(17, 24, 39, 0.85);
+    color: white;
+    font-size: 0.85rem;
+    opacity: 0;
+    pointer-events: none;
+    transition: opacity 0.2s;
+}
+#mvu-copy-toast.show { opacity: 1; }
+`;
+
+    function sectionUrl(id) {
+        return location.origin + location.pathname + location.search + '#' + id;
+    }
+
+    // http で開いていると navigator.clipboard が使えないので textarea 経由で代替する
+    function copyText(text) {
+        if (navigator.clipboard && window.isSecureContext) {
+            return navigator.clipboard.writeText(text);
+        }
+        return new Promise(function(resolve, reject) {
+            const textarea = document.createElement('textarea');
+            textarea.value = text;
+            textarea.style.position = 'fixed';
+            textarea.style.opacity = '0';
+            document.body.appendChild(textarea);
+            textarea.select();
+            const ok = document.execCommand('copy');
+            textarea.remove();
+            ok ? resolve() : reject(new Error('copy failed'));
+        });
+    }
+
+    let toastTimer = null;
+    function toast(message) {
+        let element = document.getElementById('mvu-copy-toast');
+        if (!element) {
+            element = document.createElement('div');
+            element.id = 'mvu-copy-toast';
+            document.body.appendChild(element);
+        }
+        element.textContent = message;
+        element.classList.add('show');
+        clearTimeout(toastTimer);
+        toastTimer = setTimeout(function() { element.classList.remove('show'); }, 1500);
+    }
+
+    function init() {
+        const style = document.createElement('style');
+        style.textContent = STYLE;
+        document.head.appendChild(style);
+
+        document.querySelectorAll('h1[id], h2[id], h3[id], h4[id], h5[id], h6[id]').forEach(function(heading) {
+            if (heading.closest('#side-menu')) return;
+            heading.classList.add('heading-permalink');
+
+            const actions = document.createElement('span');
+            actions.className = 'heading-actions';
+
+            const anchor = document.createElement('a');
+            anchor.href = '#' + heading.id;
+            anchor.textContent = '#';
+            anchor.title = 'このセクションへのリンク';
+            anchor.setAttribute('aria-label', 'permalink');
+
+            const copy = document.createElement('button');
+            copy.type = 'button';
+            copy.textContent = '🔗';
+            copy.title = 'セクションへのリンクをコピー';
+            copy.setAttribute('aria-label', 'copy link to section');
+            copy.addEventListener('click', function(e) {
+                e.preventDefault();
+                copyText(sectionUrl(heading.id))
+                    .then(function() { toast('リンクをコピーしました'); })
+                    .catch(function() { toast('コピーできませんでした'); });
+            });
+
+            actions.appendChild(anchor);
+            actions.appendChild(copy);
+            heading.appendChild(actions);
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', init);
+    } else {
+        init();
+    }
+})();
@@ -0,0 +1,35 @@
+// 読んでいる位置をサーバーに保存する（一覧の「続きから読む」に使われる）
+window.mvuPosition = (function() {
+    function save(path, position) {
+        const body = JSON.stringify(Object.assign({ path: path }, position));
+        const blob = new Blob([body], { type: 'application/json' });
+        if (!(navigator.sendBeacon && navigator.sendBeacon('/__position__', blob))) {
+            fetch('/__position__', { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: body, keepalive: true });
+        }
+    }
+
+    // Markdown: 画面上端より上にある最後の見出しを位置として保存する
+    function trackHeadings() {
+        const path = decodeURIComponent(location.pathname.slice(1));
+        let timer = null;
+        let last = null;
+
+        window.addEventListener('scroll', function() {
+            clearTimeout(timer);
+            timer = setTimeout(function() {
+                let anchor = null;
+                document.querySelectorAll('h1[id], h2[id], h3[id], h4[id], h5[id], h6[id]').forEach(function(h) {
+                    if (h.getBoundingClientRect().top <= 80) anchor = h.id;
+                });
+                const scrollable = document.documentElement.scrollHeight - window.innerHeight;
+                const progress = scrollable > 0 ? Math.min(100, Math.round(window.scrollY * 100 / scrollable)) : 100;
+                const key = anchor + ':' + progress;
+                if (key === last) return;
+                last = key;
+                save(path, { view: 'markdown', anchor: anchor, progress: progress });
+            }, 500);
+        }, { passive: true });
+    }
+
+    return { save: save, trackHeadings: trackHeadings };
+})();
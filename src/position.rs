@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 保存しておく位置の上限（古いものから捨てる）
+const MAX_ENTRIES: usize = 1000;
+
+const POSITIONS_FILE: &str = "positions.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadingView {
+    // Markdownのスクロール位置（見出しのid）
+    Markdown,
+    // コミックリーダーのページ
+    Reader,
+    // 一覧の画像モーダル
+    Gallery,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReadingPosition {
+    pub view: ReadingView,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    // 読み進めた割合 (0-100)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<u8>,
+    // 更新日時 (UNIX秒)
+    #[serde(default)]
+    pub updated: i64,
+}
+
+impl ReadingPosition {
+    /// 続きから開くためのURL
+    pub fn resume_url(&self, path: &str) -> String {
+        match self.view {
+            ReadingView::Markdown => match &self.anchor {
+                Some(anchor) => format!("/{}#{}", path, anchor),
+                None => format!("/{}", path),
+            },
+            ReadingView::Reader => format!("/__reader__/{}#p={}", path, self.page.unwrap_or(0)),
+            ReadingView::Gallery => format!("/{}#image={}", path, self.page.unwrap_or(0)),
+        }
+    }
+}
+
+/// パスごとの最後に見ていた位置を state ディレクトリのJSONファイルに保存する
+/// パスは base_dir からの相対パス（アーカイブ内は `a.zip::dir`）
+pub struct PositionStore {
+    file: PathBuf,
+    inner: Mutex<HashMap<String, ReadingPosition>>,
+    // 書き込みとリネームを直列にする（古い内容で新しい内容を上書きしないように）
+    write_lock: Mutex<()>,
+}
+
+impl PositionStore {
+    /// 保存済みの位置を読み込む（ファイルがなければ空から始める）
+    /// 壊れている場合は `positions.json.corrupt` に退避して空から始める
+    pub fn load(state_dir: PathBuf) -> Self {
+        let file = state_dir.join(POSITIONS_FILE);
+        let positions = match std::fs::read(&file) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(positions) => positions,
+                Err(e) => {
+                    let backup = file.with_extension("json.corrupt");
+                    eprintln!(
+                        "[positions] {} is corrupt ({}), moving it to {}",
+                        file.display(),
+                        e,
+                        backup.display()
+                    );
+                    if let Err(e) = std::fs::rename(&file, &backup) {
+                        eprintln!("[positions] Failed to back up {}: {}", file.display(), e);
+                    }
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            file,
+            inner: Mutex::new(positions),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<ReadingPosition> {
+        self.inner.lock().unwrap().get(normalize(path)).cloned()
+    }
+
+    /// 位置を記録してファイルに書き出す（ブロッキングIOなので非同期ハンドラーからは spawn_blocking で呼ぶ）
+    pub fn set(&self, path: &str, mut position: ReadingPosition) -> Result<(), String> {
+        let _write = self.write_lock.lock().unwrap();
+        position.updated = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        let data = {
+            let mut positions = self.inner.lock().unwrap();
+            positions.insert(normalize(path).to_string(), position);
+
+            if positions.len() > MAX_ENTRIES {
+                let mut by_age: Vec<_> = positions.iter().map(|(k, v)| (v.updated, k.clone())).collect();
+                by_age.sort();
+                for (_, key) in by_age.into_iter().take(positions.len() - MAX_ENTRIES) {
+                    positions.remove(&key);
+                }
+            }
+
+            serde_json::to_vec(&*positions).map_err(|e| format!("Failed to serialize positions: {}", e))?
+        };
+
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create state dir: {}", e))?;
+        }
+        let temp = self.file.with_extension(format!("json.tmp{}", std::process::id()));
+        std::fs::write(&temp, data).map_err(|e| format!("Failed to write positions: {}", e))?;
+        std::fs::rename(&temp, &self.file).map_err(|e| format!("Failed to write positions: {}", e))
+    }
+
+    /// `dir`（ディレクトリ、またはアーカイブ内なら `a.zip::dir`）自身とその下の位置を新しい順に返す
+    pub fn recent_under(&self, dir: &str, limit: usize) -> Vec<(String, ReadingPosition)> {
+        let dir = normalize(dir);
+        let prefix = if dir.is_empty() || dir.ends_with("::") {
+            dir.to_string()
+        } else {
+            format!("{}/", dir)
+        };
+
+        let positions = self.inner.lock().unwrap();
+        let mut recent: Vec<_> = positions
+            .iter()
+            .filter(|(path, _)| path.as_str() == dir || path.starts_with(&prefix))
+            .map(|(path, position)| (path.clone(), position.clone()))
+            .collect();
+        recent.sort_by(|a, b| b.1.updated.cmp(&a.1.updated).then_with(|| a.0.cmp(&b.0)));
+        recent.truncate(limit);
+        recent
+    }
+}
+
+fn normalize(path: &str) -> &str {
+    path.trim_start_matches('/').trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(view: ReadingView, page: usize) -> ReadingPosition {
+        ReadingPosition {
+            view,
+            anchor: None,
+            page: Some(page),
+            total: Some(10),
+            progress: None,
+            updated: 0,
+        }
+    }
+
+    #[test]
+    fn test_positions_persist_across_loads() {
+        let dir = std::env::temp_dir().join("mvu_test_positions");
+        std::fs::remove_dir_all(&dir).ok();
+
+        let store = PositionStore::load(dir.clone());
+        store.set("comics/vol1.cbz", page(ReadingView::Reader, 12)).unwrap();
+        store
+            .set(
+                "/docs/guide.md",
+                ReadingPosition { anchor: Some("2-Install".to_string()), ..page(ReadingView::Markdown, 0) },
+            )
+            .unwrap();
+
+        let store = PositionStore::load(dir.clone());
+        assert_eq!(store.get("comics/vol1.cbz").unwrap().page, Some(12));
+        assert_eq!(store.get("docs/guide.md").unwrap().anchor.as_deref(), Some("2-Install"));
+        assert!(store.get("docs/other.md").is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_corrupt_file_is_backed_up() {
+        let dir = std::env::temp_dir().join("mvu_test_positions_corrupt");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(POSITIONS_FILE), "{not json").unwrap();
+
+        let store = PositionStore::load(dir.clone());
+        assert!(store.get("a.md").is_none());
+        assert_eq!(std::fs::read_to_string(dir.join("positions.json.corrupt")).unwrap(), "{not json");
+
+        store.set("a.md", page(ReadingView::Markdown, 0)).unwrap();
+        assert!(PositionStore::load(dir.clone()).get("a.md").is_some());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_concurrent_sets() {
+        let dir = std::env::temp_dir().join("mvu_test_positions_concurrent");
+        std::fs::remove_dir_all(&dir).ok();
+
+        let store = std::sync::Arc::new(PositionStore::load(dir.clone()));
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let store = store.clone();
+                std::thread::spawn(move || store.set(&format!("{}.cbz", i), page(ReadingView::Reader, i)).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let store = PositionStore::load(dir.clone());
+        for i in 0..8 {
+            assert_eq!(store.get(&format!("{}.cbz", i)).unwrap().page, Some(i));
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_recent_under() {
+        let dir = std::env::temp_dir().join("mvu_test_positions_recent");
+        std::fs::remove_dir_all(&dir).ok();
+
+        let store = PositionStore::load(dir.clone());
+        store.set("comics/vol1.cbz", page(ReadingView::Reader, 1)).unwrap();
+        store.set("comics/vol1.cbz::extra", page(ReadingView::Gallery, 2)).unwrap();
+        store.set("comicsx/a.md", page(ReadingView::Markdown, 0)).unwrap();
+        store.set("comics", page(ReadingView::Gallery, 0)).unwrap();
+
+        let paths: Vec<_> = store.recent_under("comics", 10).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths.len(), 3);
+        assert!(paths.contains(&"comics".to_string()));
+        assert!(!paths.contains(&"comicsx/a.md".to_string()));
+
+        let paths: Vec<_> = store.recent_under("comics/vol1.cbz::", 10).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, vec!["comics/vol1.cbz::extra"]);
+        assert_eq!(store.recent_under("", 1).len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_resume_url() {
+        assert_eq!(page(ReadingView::Reader, 3).resume_url("a.cbz"), "/__reader__/a.cbz#p=3");
+        assert_eq!(page(ReadingView::Gallery, 4).resume_url("photos"), "/photos#image=4");
+        let markdown = ReadingPosition { anchor: Some("2-Usage".to_string()), ..page(ReadingView::Markdown, 0) };
+        assert_eq!(markdown.resume_url("README.md"), "/README.md#2-Usage");
+    }
+}
@@ -0,0 +1,472 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::path::Path;
+
+use crate::archive;
+use crate::markdown::html_escape;
+use crate::natural_sort::NameOrder;
+use crate::watcher;
+use crate::zip_handler::ZipEntry;
+
+/// 画像URLのエンコード（`#` や `?` を含むファイル名でも壊れないように）
+const URL_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>')
+    .add(b'?').add(b'`').add(b'{').add(b'}').add(b'\'');
+
+/// リーダーで開く単位（画像のあるディレクトリ、またはアーカイブ内のディレクトリ）
+pub struct ReaderBook {
+    pub title: String,
+    // 一覧ページへ戻るリンク
+    pub back_link: String,
+    pub pages: Vec<String>,
+    // 読書位置の保存キーと、前回読んでいたページ
+    pub key: String,
+    pub start_page: usize,
+    // 前後の巻（同じディレクトリの兄弟ディレクトリ / アーカイブ）のリーダーURL
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+pub fn is_image_file(name: &str) -> bool {
+    let ext = name.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "gif" | "webp" | "svg")
+}
+
+pub fn encode_url(path: &str) -> String {
+    utf8_percent_encode(path, URL_ENCODE_SET).to_string()
+}
+
+/// ディレクトリ直下の画像をページとして集める
+pub fn pages_in_dir(dir: &Path, relative_path: &str, order: NameOrder) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| is_image_file(name))
+        .collect();
+    names.sort_by(|a, b| order.cmp(a, b));
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            if relative_path.is_empty() {
+                format!("/{}", name)
+            } else {
+                format!("/{}/{}", relative_path, name)
+            }
+        })
+        .collect())
+}
+
+/// アーカイブ内の `internal_dir` 以下の画像を（サブディレクトリも含めて）ページとして集める
+pub fn pages_in_archive(entries: &[ZipEntry], archive_relative_path: &str, internal_dir: &str, order: NameOrder) -> Vec<String> {
+    let prefix = if internal_dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", internal_dir.trim_end_matches('/'))
+    };
+
+    let mut names: Vec<&str> = entries
+        .iter()
+        .filter(|e| !e.is_dir && e.name.starts_with(&prefix) && is_image_file(&e.name))
+        .map(|e| e.name.as_str())
+        .collect();
+    names.sort_by(|a, b| order.cmp(a, b));
+
+    names
+        .into_iter()
+        .map(|name| format!("/{}::{}", archive_relative_path, name))
+        .collect()
+}
+
+/// 同じ親ディレクトリにある、同じ種類（ディレクトリ同士・アーカイブ同士）の前後の項目
+/// 戻り値は base_dir からの相対パス
+pub fn siblings(base_dir: &Path, relative_path: &str, order: NameOrder) -> (Option<String>, Option<String>) {
+    let path = base_dir.join(relative_path);
+    let is_dir = path.is_dir();
+    let parent_relative = relative_path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+
+    let entries = match std::fs::read_dir(base_dir.join(parent_relative)) {
+        Ok(entries) => entries,
+        Err(_) => return (None, None),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let p = e.path();
+            if is_dir { p.is_dir() } else { p.is_file() && archive::is_archive_file(&p) }
+        })
+        .filter_map(|e| e.file_name().into_string().ok())
+        .map(|name| {
+            if parent_relative.is_empty() {
+                name
+            } else {
+                format!("{}/{}", parent_relative, name)
+            }
+        })
+        .filter(|relative| !watcher::should_ignore_path(&Path::new("/").join(relative)))
+        .collect();
+    names.sort_by(|a, b| order.cmp(a, b));
+
+    match names.iter().position(|name| name == relative_path) {
+        Some(pos) => (
+            pos.checked_sub(1).map(|i| names[i].clone()),
+            names.get(pos + 1).cloned(),
+        ),
+        None => (None, None),
+    }
+}
+
+pub fn render(book: &ReaderBook) -> String {
+    let nav_link = |link: &Option<String>, label: &str| match link {
+        Some(link) => format!(
+            "<a class=\"reader-button\" href=\"/__reader__/{}\">{}</a>",
+            html_escape(&encode_url(link)),
+            label
+        ),
+        None => String::new(),
+    };
+
+    let pages: Vec<String> = book.pages.iter().map(|p| encode_url(p)).collect();
+    let next_url = book.next.as_ref().map(|n| format!("/__reader__/{}", encode_url(n)));
+
+    format!(r#"<!DOCTYPE html>
+<html><head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>📖 {title}</title>
+<script src="/__reload__.js"></script>
+<script src="/__position__.js"></script>
+<style>
+* {{ margin: 0; padding: 0; box-sizing: border-box; }}
+html, body {{ height: 100%; background: #111; color: #eee; }}
+body {{
+    font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', 'Noto Sans', Helvetica, Arial, sans-serif;
+    overflow: hidden;
+}}
+#toolbar {{
+    position: fixed;
+    top: 0;
+    left: 0;
+    right: 0;
+    display: flex;
+    align-items: center;
+    gap: 0.5rem;
+    padding: 0.5rem 1rem;
+    background: rgba(0, 0, 0, 0.75);
+    z-index: 10;
+    transition: opacity 0.2s;
+}}
+#toolbar.hidden {{ opacity: 0; pointer-events: none; }}
+#toolbar .title {{
+    flex: 1;
+    min-width: 0;
+    overflow: hidden;
+    text-overflow: ellipsis;
+    white-space: nowrap;
+    font-weight: 600;
+}}
+.reader-button {{
+    padding: 0.35rem 0.75rem;
+    border: none;
+    border-radius: 8px;
+    background: rgba(255, 255, 255, 0.12);
+    color: #eee;
+    font-size: 0.85rem;
+    text-decoration: none;
+    cursor: pointer;
+    white-space: nowrap;
+}}
+.reader-button:hover {{
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+}}
+#counter {{ font-size: 0.85rem; color: #aaa; white-space: nowrap; }}
+#viewer {{
+    height: 100vh;
+    overflow: auto;
+    display: flex;
+    justify-content: center;
+    align-items: flex-start;
+}}
+#spread {{
+    display: flex;
+    justify-content: center;
+    align-items: center;
+    min-height: 100vh;
+}}
+body.rtl #spread {{ flex-direction: row-reverse; }}
+#spread img {{ display: block; }}
+body.fit-height #spread img {{ max-height: 100vh; max-width: 100vw; }}
+body.fit-height.spread-mode #spread img {{ max-width: 50vw; }}
+body.fit-width #spread img {{ width: 100vw; height: auto; }}
+body.fit-width.spread-mode #spread img {{ width: 50vw; }}
+body.fit-width #spread {{ align-items: flex-start; }}
+#end-notice {{
+    display: none;
+    position: fixed;
+    bottom: 2rem;
+    left: 50%;
+    transform: translateX(-50%);
+    padding: 0.75rem 1.5rem;
+    border-radius: 12px;
+    background: rgba(0, 0, 0, 0.85);
+    font-size: 0.9rem;
+    z-index: 10;
+}}
+#end-notice.active {{ display: block; }}
+.empty {{ padding: 6rem 2rem; text-align: center; color: #aaa; }}
+</style>
+</head><body class="rtl fit-height">
+<div id="toolbar">
+    <a class="reader-button" href="{back}">✕</a>
+    <span class="title">{title}</span>
+    <span id="counter"></span>
+    {prev}
+    {next}
+    <button class="reader-button" id="toggleDirection" title="r">⇄</button>
+    <button class="reader-button" id="toggleSpread" title="d">📖</button>
+    <button class="reader-button" id="toggleFit" title="f">↕</button>
+</div>
+<div id="viewer"><div id="spread"></div></div>
+<div id="end-notice"></div>
+<script>
+const pages = {pages};
+const bookKey = {key};
+const nextBook = {next_url};
+const backLink = {back_json};
+const PRELOAD = 4;
+
+// 表示設定はブラウザに保存（全作品共通）
+const settings = Object.assign(
+    {{ rtl: true, spread: false, fit: 'height' }},
+    JSON.parse(localStorage.getItem('mvu-reader') || '{{}}')
+);
+const hashPage = (location.hash.match(/p=(\d+)/) || [])[1];
+let current = Math.min(hashPage ? parseInt(hashPage, 10) : {start_page}, Math.max(pages.length - 1, 0));
+let atEnd = false;
+const preloaded = new Map();
+
+function saveSettings() {{
+    localStorage.setItem('mvu-reader', JSON.stringify(settings));
+}}
+
+// 見開きでは表紙（最初のページ）だけ単独で表示し、以降は2ページずつ
+function spreadStart(index) {{
+    if (!settings.spread || index === 0) return index;
+    return index - ((index - 1) % 2);
+}}
+
+function spreadPages(start) {{
+    if (!settings.spread || start === 0) return [start];
+    return start + 1 < pages.length ? [start, start + 1] : [start];
+}}
+
+function preload(from) {{
+    for (let i = from; i < Math.min(from + PRELOAD, pages.length); i++) {{
+        if (!preloaded.has(pages[i])) {{
+            const img = new Image();
+            img.src = pages[i];
+            preloaded.set(pages[i], img);
+        }}
+    }}
+}}
+
+function render() {{
+    document.body.classList.toggle('rtl', settings.rtl);
+    document.body.classList.toggle('spread-mode', settings.spread);
+    document.body.classList.toggle('fit-width', settings.fit === 'width');
+    document.body.classList.toggle('fit-height', settings.fit === 'height');
+
+    const spread = document.getElementById('spread');
+    spread.innerHTML = '';
+    if (pages.length === 0) {{
+        spread.innerHTML = '<div class="empty">画像がありません</div>';
+        return;
+    }}
+
+    current = spreadStart(current);
+    const shown = spreadPages(current);
+    shown.forEach(i => {{
+        const img = document.createElement('img');
+        img.src = pages[i];
+        img.alt = pages[i];
+        spread.appendChild(img);
+    }});
+
+    const last = shown[shown.length - 1];
+    document.getElementById('counter').textContent =
+        shown.map(i => i + 1).join('-') + ' / ' + pages.length;
+    document.getElementById('viewer').scrollTop = 0;
+    history.replaceState(null, '', '#p=' + current);
+    mvuPosition.save(bookKey, {{ view: 'reader', page: current, total: pages.length }});
+    preload(last + 1);
+    hideEndNotice();
+}}
+
+function showEndNotice() {{
+    const notice = document.getElementById('end-notice');
+    notice.textContent = nextBook ? '最後のページです。もう一度進むと次の巻へ移動します' : '最後のページです';
+    notice.classList.add('active');
+    atEnd = true;
+}}
+
+function hideEndNotice() {{
+    document.getElementById('end-notice').classList.remove('active');
+    atEnd = false;
+}}
+
+function nextPage() {{
+    const shown = spreadPages(current);
+    const following = shown[shown.length - 1] + 1;
+    if (following < pages.length) {{
+        current = following;
+        render();
+    }} else if (atEnd && nextBook) {{
+        location.href = nextBook;
+    }} else {{
+        showEndNotice();
+    }}
+}}
+
+function prevPage() {{
+    if (current === 0) return;
+    current = spreadStart(current - 1);
+    render();
+}}
+
+// 右綴じ（右から左）では左が「次」
+function goLeft() {{ settings.rtl ? nextPage() : prevPage(); }}
+function goRight() {{ settings.rtl ? prevPage() : nextPage(); }}
+
+document.getElementById('toggleDirection').addEventListener('click', function() {{
+    settings.rtl = !settings.rtl;
+    saveSettings();
+    render();
+}});
+document.getElementById('toggleSpread').addEventListener('click', function() {{
+    settings.spread = !settings.spread;
+    saveSettings();
+    render();
+}});
+document.getElementById('toggleFit').addEventListener('click', function() {{
+    settings.fit = settings.fit === 'height' ? 'width' : 'height';
+    saveSettings();
+    render();
+}});
+
+document.addEventListener('keydown', function(e) {{
+    if (e.ctrlKey || e.metaKey || e.altKey) return;
+    switch (e.key) {{
+        case 'ArrowLeft': goLeft(); break;
+        case 'ArrowRight': goRight(); break;
+        case ' ':
+        case 'PageDown': nextPage(); break;
+        case 'PageUp': prevPage(); break;
+        case 'Home': current = 0; render(); break;
+        case 'End': current = Math.max(pages.length - 1, 0); render(); break;
+        case 'r': document.getElementById('toggleDirection').click(); break;
+        case 'd': document.getElementById('toggleSpread').click(); break;
+        case 'f': document.getElementById('toggleFit').click(); break;
+        case 'Escape': location.href = backLink; break;
+        default: return;
+    }}
+    e.preventDefault();
+}});
+
+// タップ: 画面の左右1/3でページ送り、中央でツールバーの表示切り替え
+document.getElementById('viewer').addEventListener('click', function(e) {{
+    const x = e.clientX / window.innerWidth;
+    if (x < 1 / 3) goLeft();
+    else if (x > 2 / 3) goRight();
+    else document.getElementById('toolbar').classList.toggle('hidden');
+}});
+
+render();
+</script>
+</body></html>"#,
+        title = html_escape(&book.title),
+        back = html_escape(&encode_url(&book.back_link)),
+        prev = nav_link(&book.prev, "« 前の巻"),
+        next = nav_link(&book.next, "次の巻 »"),
+        pages = serde_json::to_string(&pages).unwrap_or_else(|_| "[]".to_string()),
+        key = serde_json::to_string(&book.key).unwrap_or_else(|_| "\"\"".to_string()),
+        start_page = book.start_page,
+        next_url = serde_json::to_string(&next_url).unwrap_or_else(|_| "null".to_string()),
+        back_json = serde_json::to_string(&encode_url(&book.back_link)).unwrap_or_else(|_| "\"/\"".to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_is_image_file() {
+        assert!(is_image_file("001.JPG"));
+        assert!(is_image_file("cover.webp"));
+        assert!(!is_image_file("notes.md"));
+    }
+
+    #[test]
+    fn test_pages_in_archive() {
+        let entries = vec![
+            ZipEntry { name: "vol1/".to_string(), is_dir: true, ..Default::default() },
+            ZipEntry { name: "vol1/002.png".to_string(), ..Default::default() },
+            ZipEntry { name: "vol1/001.png".to_string(), ..Default::default() },
+            ZipEntry { name: "vol1/info.txt".to_string(), ..Default::default() },
+            ZipEntry { name: "cover.jpg".to_string(), ..Default::default() },
+        ];
+
+        assert_eq!(
+            pages_in_archive(&entries, "books/a.cbz", "vol1", NameOrder::default()),
+            vec!["/books/a.cbz::vol1/001.png", "/books/a.cbz::vol1/002.png"]
+        );
+        assert_eq!(pages_in_archive(&entries, "a.cbz", "", NameOrder::default()).len(), 3);
+    }
+
+    #[test]
+    fn test_pages_and_siblings() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_reader");
+        fs::create_dir_all(temp_dir.join("comic/ch1")).unwrap();
+        fs::create_dir_all(temp_dir.join("comic/ch2")).unwrap();
+        fs::create_dir_all(temp_dir.join("comic/.git")).unwrap();
+        fs::write(temp_dir.join("comic/ch1/b.png"), "").unwrap();
+        fs::write(temp_dir.join("comic/ch1/a.png"), "").unwrap();
+        fs::write(temp_dir.join("comic/ch1/note.txt"), "").unwrap();
+        fs::write(temp_dir.join("comic/vol1.cbz"), "").unwrap();
+        fs::write(temp_dir.join("comic/vol2.zip"), "").unwrap();
+        fs::write(temp_dir.join("comic/readme.md"), "").unwrap();
+
+        assert_eq!(
+            pages_in_dir(&temp_dir.join("comic/ch1"), "comic/ch1", NameOrder::default()).unwrap(),
+            vec!["/comic/ch1/a.png", "/comic/ch1/b.png"]
+        );
+
+        // ディレクトリはディレクトリ同士、アーカイブはアーカイブ同士で前後をたどる
+        assert_eq!(siblings(&temp_dir, "comic/ch1", NameOrder::default()), (None, Some("comic/ch2".to_string())));
+        assert_eq!(siblings(&temp_dir, "comic/ch2", NameOrder::default()), (Some("comic/ch1".to_string()), None));
+        assert_eq!(siblings(&temp_dir, "comic/vol1.cbz", NameOrder::default()), (None, Some("comic/vol2.zip".to_string())));
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_render_escapes_urls() {
+        let book = ReaderBook {
+            title: "<vol>".to_string(),
+            back_link: "/comic".to_string(),
+            pages: vec!["/comic/#1.png".to_string()],
+            key: "comic".to_string(),
+            start_page: 0,
+            prev: None,
+            next: Some("comic/vol 2.cbz".to_string()),
+        };
+        let html = render(&book);
+        assert!(html.contains("&lt;vol&gt;"));
+        assert!(html.contains("/comic/%231.png"));
+        assert!(html.contains("/__reader__/comic/vol%202.cbz"));
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::documents::{Changes, Document};
+use crate::file_index::is_under;
+use crate::heading::Heading;
+use crate::markdown::html_escape;
+
+/// 検索結果の最大件数
+pub const MAX_RESULTS: usize = 50;
+
+/// スニペットの長さ（文字数）
+const SNIPPET_CHARS: usize = 160;
+
+// BM25 のパラメータ
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// 漢字・かな・ハングルか（空白で区切られない文字）
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3005..=0x3007 // 々〆〇
+        | 0x3040..=0x30FF // ひらがな・カタカナ
+        | 0x31F0..=0x31FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF
+        | 0xF900..=0xFAFF
+        | 0xFF66..=0xFF9F // 半角カタカナ
+        | 0x20000..=0x2FFFF)
+}
+
+/// 英数字の語の一部になる文字か
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() && !is_cjk(c)
+}
+
+/// 英数字の語と、漢字・かなの並びに分けて小文字にする
+/// 日本語の段落は行をまたいで折り返されるので、漢字・かなの並びは改行では切らない
+pub fn phrases(text: &str) -> Vec<String> {
+    let mut phrases = Vec::new();
+    let mut current = String::new();
+    let mut current_cjk = false;
+
+    for c in text.chars() {
+        if current_cjk && matches!(c, '\r' | '\n') {
+            continue;
+        }
+        let cjk = is_cjk(c);
+        if !cjk && !c.is_alphanumeric() {
+            if !current.is_empty() {
+                phrases.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if cjk != current_cjk && !current.is_empty() {
+            phrases.push(std::mem::take(&mut current));
+        }
+        current_cjk = cjk;
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        phrases.push(current);
+    }
+
+    phrases
+}
+
+/// 索引の語にする。英数字は語ごと、漢字・かなは2文字ずつ（1文字だけの並びはそのまま）
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for phrase in phrases(text) {
+        let chars: Vec<char> = phrase.chars().collect();
+        if chars.len() < 2 || !is_cjk(chars[0]) {
+            tokens.push(phrase);
+        } else {
+            tokens.extend(chars.windows(2).map(|pair| pair.iter().collect::<String>()));
+        }
+    }
+    tokens
+}
+
+/// 文書を索引に入れるときの語。`tokenize` に加えて漢字・かなは1文字ずつも入れる
+/// （1文字だけの検索語は1文字の語になるので、2文字ずつの語だけだと見つからない）
+fn index_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for phrase in phrases(text) {
+        let chars: Vec<char> = phrase.chars().collect();
+        if chars.len() < 2 || !is_cjk(chars[0]) {
+            tokens.push(phrase);
+        } else {
+            tokens.extend(chars.windows(2).map(|pair| pair.iter().collect::<String>()));
+            tokens.extend(chars.iter().map(|c| c.to_string()));
+        }
+    }
+    tokens
+}
+
+struct SearchDocument {
+    title: String,
+    content: String,
+    // 小文字にして改行を除いた本文（漢字・かなの並びの照合用）
+    normalized: String,
+    // 小文字にしたタイトルとパス（タイトルとの照合用）
+    title_key: String,
+    // Markdownの front matter のタイトル（アーカイブ一覧用）
+    front_matter_title: Option<String>,
+    headings: Vec<Heading>,
+    // 語ごとの出現回数
+    terms: HashMap<String, u32>,
+    length: usize,
+}
+
+impl SearchDocument {
+    fn new(document: &Document) -> Self {
+        // Markdownの front matter は本文から外し、タイトルとタグだけ索引に入れる
+        let mut tokens = index_tokens(&document.text);
+        let (title, front_matter_title, headings) = match &document.markdown {
+            Some(markdown) => {
+                let front_matter = &markdown.front_matter;
+                tokens.extend(front_matter.title.iter().flat_map(|title| index_tokens(title)));
+                tokens.extend(front_matter.tags.iter().flat_map(|tag| index_tokens(tag)));
+                (markdown.title.clone(), front_matter.title.clone(), markdown.headings.clone())
+            }
+            None => {
+                let path = &document.relative;
+                (path.rsplit(['/', ':']).next().unwrap_or(path).to_string(), None, Vec::new())
+            }
+        };
+        let mut terms = HashMap::new();
+        for token in &tokens {
+            *terms.entry(token.clone()).or_insert(0) += 1;
+        }
+
+        Self {
+            normalized: document.text.to_lowercase().replace(['\r', '\n'], ""),
+            title_key: format!("{} {}", title, document.relative).to_lowercase(),
+            front_matter_title,
+            title,
+            headings,
+            terms,
+            length: tokens.len(),
+            content: document.text.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: String,
+    pub title: String,
+    pub score: f64,
+    // 一致した語を <mark> で囲んだHTML
+    pub snippet: String,
+    // 一致した行の直前の見出し
+    pub heading: Option<String>,
+    pub anchor: Option<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    documents: HashMap<String, SearchDocument>,
+    // 語 → (パス → 出現回数)
+    postings: HashMap<String, HashMap<String, u32>>,
+    total_length: usize,
+}
+
+impl Inner {
+    fn insert(&mut self, path: String, document: SearchDocument) {
+        self.remove(&path);
+        for (term, count) in &document.terms {
+            self.postings.entry(term.clone()).or_default().insert(path.clone(), *count);
+        }
+        self.total_length += document.length;
+        self.documents.insert(path, document);
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some(document) = self.documents.remove(path) {
+            for term in document.terms.keys() {
+                if let Some(posting) = self.postings.get_mut(term) {
+                    posting.remove(path);
+                    if posting.is_empty() {
+                        self.postings.remove(term);
+                    }
+                }
+            }
+            self.total_length -= document.length;
+        }
+    }
+}
+
+/// Markdown・テキスト・ソースファイルの全文検索用の転置索引
+#[derive(Default)]
+pub struct SearchIndex {
+    inner: RwLock<Inner>,
+    ready: AtomicBool,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self {
+            inner: RwLock::new(Inner::default()),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// ツリー全体の文書で作り直す（起動時にバックグラウンドで呼ぶ）
+    pub fn replace(&self, documents: &[Document]) {
+        let mut inner = Inner::default();
+        for document in documents {
+            inner.insert(document.relative.clone(), SearchDocument::new(document));
+        }
+        *self.inner.write().unwrap() = inner;
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// 起動時の索引作成が終わったか
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().documents.len()
+    }
+
+    /// watcher で変更があったパスを反映する
+    pub fn apply(&self, changes: &Changes) {
+        if changes.removed.is_empty() {
+            return;
+        }
+        let fresh: Vec<SearchDocument> = changes.documents.iter().map(SearchDocument::new).collect();
+
+        let mut inner = self.inner.write().unwrap();
+        let stale: Vec<String> = inner
+            .documents
+            .keys()
+            .filter(|path| changes.removed.iter().any(|relative| is_under(path, relative)))
+            .cloned()
+            .collect();
+        for path in stale {
+            inner.remove(&path);
+        }
+        for (document, fresh) in changes.documents.iter().zip(fresh) {
+            inner.insert(document.relative.clone(), fresh);
+        }
+    }
+
+    /// Markdownの front matter のタイトル（ZIP内のエントリは `a.zip::dir/file.md`、ファイルは読まない）
+    pub fn front_matter_title(&self, path: &str) -> Option<String> {
+        self.inner.read().unwrap().documents.get(path)?.front_matter_title.clone()
+    }
+
+    /// すべての語を含む文書を BM25 の順に返す
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut terms = tokenize(query);
+        let mut seen = HashSet::new();
+        terms.retain(|term| seen.insert(term.clone()));
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        // ハイライトやタイトルとの照合は語・漢字かなの並び単位で行う
+        let mut phrases = phrases(query);
+        let mut seen = HashSet::new();
+        phrases.retain(|phrase| seen.insert(phrase.clone()));
+        let cjk_phrases: Vec<&String> = phrases.iter().filter(|phrase| phrase.chars().any(is_cjk)).collect();
+
+        let inner = self.inner.read().unwrap();
+        let total = inner.documents.len().max(1) as f64;
+        let average_length = (inner.total_length as f64 / total).max(1.0);
+
+        // 最も文書の少ない語から候補を絞る
+        let mut postings: Vec<&HashMap<String, u32>> = Vec::new();
+        for term in &terms {
+            match inner.postings.get(term) {
+                Some(posting) => postings.push(posting),
+                None => return Vec::new(),
+            }
+        }
+        postings.sort_by_key(|posting| posting.len());
+
+        let mut scored: Vec<(f64, &String)> = postings[0]
+            .keys()
+            .filter(|path| postings[1..].iter().all(|posting| posting.contains_key(*path)))
+            // 2文字ずつの語がすべてあっても離れている場合があるので、並びそのものを含むか確かめる
+            .filter(|path| {
+                let normalized = &inner.documents[*path].normalized;
+                cjk_phrases.iter().all(|phrase| normalized.contains(phrase.as_str()))
+            })
+            .map(|path| {
+                let document = &inner.documents[path];
+                let length_norm = 1.0 - B + B * document.length as f64 / average_length;
+                let mut score: f64 = postings
+                    .iter()
+                    .map(|posting| {
+                        let tf = posting[path] as f64;
+                        let idf = ((total - posting.len() as f64 + 0.5) / (posting.len() as f64 + 0.5) + 1.0).ln();
+                        idf * tf * (K1 + 1.0) / (tf + K1 * length_norm)
+                    })
+                    .sum();
+                // タイトルやパスに含まれる語は重く見る
+                score += phrases.iter().filter(|phrase| document.title_key.contains(phrase.as_str())).count() as f64;
+                (score, path)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        scored.truncate(limit);
+
+        scored
+            .into_iter()
+            .map(|(score, path)| {
+                let document = &inner.documents[path];
+                let (line, snippet) = snippet(&document.content, &phrases);
+                let heading = line.and_then(|line| document.headings.iter().rev().find(|h| h.line <= line));
+                SearchHit {
+                    path: path.clone(),
+                    title: document.title.clone(),
+                    score,
+                    snippet,
+                    heading: heading.map(|h| h.text.clone()),
+                    anchor: heading.map(|h| h.id.clone()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 大文字小文字を区別せずに `term` が `chars[start..]` の先頭に語として（英数字の語の途中でない位置で）あるか
+fn matches_at(chars: &[char], start: usize, term: &[char]) -> bool {
+    let end = start + term.len();
+    end <= chars.len()
+        && !term.is_empty()
+        && (start == 0 || !(is_word_char(chars[start - 1]) && is_word_char(term[0])))
+        && (end == chars.len() || !(is_word_char(chars[end]) && is_word_char(term[term.len() - 1])))
+        && chars[start..end]
+            .iter()
+            .zip(term)
+            .all(|(c, t)| c.to_lowercase().eq(std::iter::once(*t)))
+}
+
+/// 語を最も多く含む行から切り出したスニペット（行番号と、一致箇所を <mark> で囲んだHTML）
+fn snippet(content: &str, terms: &[String]) -> (Option<usize>, String) {
+    let terms: Vec<Vec<char>> = terms.iter().map(|term| term.chars().collect()).collect();
+
+    let best = content
+        .lines()
+        .enumerate()
+        .map(|(number, line)| {
+            let chars: Vec<char> = line.chars().collect();
+            let hits = terms
+                .iter()
+                .filter(|term| (0..chars.len()).any(|i| matches_at(&chars, i, term)))
+                .count();
+            (hits, number, line)
+        })
+        .filter(|(hits, _, _)| *hits > 0)
+        .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(&a.1)));
+
+    let (number, line) = match best {
+        Some((_, number, line)) => (Some(number), line),
+        None => (None, content.lines().find(|line| !line.trim().is_empty()).unwrap_or("")),
+    };
+
+    // 最初の一致が真ん中あたりに来るように切り出す
+    let chars: Vec<char> = line.trim().chars().collect();
+    let first = (0..chars.len())
+        .find(|&i| terms.iter().any(|term| matches_at(&chars, i, term)))
+        .unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_CHARS / 3);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+
+    let mut html = String::new();
+    if start > 0 {
+        html.push('…');
+    }
+    let mut i = start;
+    while i < end {
+        match terms.iter().filter(|term| matches_at(&chars, i, term)).map(Vec::len).max() {
+            Some(len) => {
+                let matched: String = chars[i..i + len].iter().collect();
+                html.push_str(&format!("<mark>{}</mark>", html_escape(&matched)));
+                i += len;
+            }
+            None => {
+                html.push_str(&html_escape(&chars[i].to_string()));
+                i += 1;
+            }
+        }
+    }
+    if end < chars.len() {
+        html.push('…');
+    }
+
+    (number, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::{self, MAX_FILE_SIZE};
+
+    #[test]
+    fn test_search_ranks_and_links_headings() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_search");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(temp_dir.join("docs")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(
+            temp_dir.join("docs/guide.md"),
+            "# Guide\n\nIntro text.\n\n```\n# not a heading\n```\n\n## Install Steps\n\nRun the <installer> script to install.\n",
+        )
+        .unwrap();
+        std::fs::write(temp_dir.join("docs/notes.txt"), "install once\n").unwrap();
+        std::fs::write(temp_dir.join("docs/image.png"), "install").unwrap();
+
+        let index = SearchIndex::new();
+        assert!(!index.is_ready());
+        index.replace(&documents::collect(&temp_dir));
+        assert!(index.is_ready());
+        assert_eq!(index.len(), 2);
+
+        let hits = index.search("Install script", MAX_RESULTS);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "docs/guide.md");
+        assert_eq!(hits[0].title, "Guide");
+        assert_eq!(hits[0].heading.as_deref(), Some("Install Steps"));
+        assert_eq!(hits[0].anchor.as_deref(), Some("2-Install%20Steps"));
+        assert_eq!(hits[0].snippet, "Run the &lt;installer&gt; <mark>script</mark> to <mark>install</mark>.");
+
+        let paths: Vec<_> = index.search("install", MAX_RESULTS).into_iter().map(|hit| hit.path).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(index.search("missing", MAX_RESULTS).is_empty());
+
+        // 変更・削除を反映する
+        std::fs::write(temp_dir.join("docs/notes.txt"), "uninstall later\n").unwrap();
+        std::fs::remove_file(temp_dir.join("docs/guide.md")).unwrap();
+        index.apply(&documents::read_changes(&temp_dir, &[temp_dir.join("docs/notes.txt"), temp_dir.join("docs/guide.md")]));
+        assert!(index.search("install", MAX_RESULTS).is_empty());
+        assert_eq!(index.search("uninstall", MAX_RESULTS)[0].path, "docs/notes.txt");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_front_matter_is_not_body() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_search_front_matter");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("note.md"), "---\ntitle: Release Plan\ntags: [roadmap]\n---\n## Steps\n\nShip it.\n").unwrap();
+
+        let index = SearchIndex::new();
+        index.replace(&documents::collect(&temp_dir));
+        let hits = index.search("roadmap", MAX_RESULTS);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Release Plan");
+        let hits = index.search("ship", MAX_RESULTS);
+        assert_eq!(hits[0].snippet, "<mark>Ship</mark> it.");
+        assert_eq!(hits[0].anchor.as_deref(), Some("2-Steps"));
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_search_inside_zip() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_search_zip");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+
+        let write_zip = |entries: &[(&str, &[u8])]| {
+            let mut writer = zip::ZipWriter::new(std::fs::File::create(temp_dir.join("release.zip")).unwrap());
+            for (name, content) in entries {
+                writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+                writer.write_all(content).unwrap();
+            }
+            writer.finish().unwrap();
+        };
+        let large = "rollback ".repeat(MAX_FILE_SIZE as usize / 8);
+        write_zip(&[
+            ("docs/upgrade.md", b"# Upgrade\n\n## Rollback\n\nRestore the backup.\n"),
+            ("docs/logo.png", b"backup"),
+            ("docs/huge.txt", large.as_bytes()),
+        ]);
+
+        let index = SearchIndex::new();
+        index.replace(&documents::collect(&temp_dir));
+        let hits = index.search("backup", MAX_RESULTS);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "release.zip::docs/upgrade.md");
+        assert_eq!(hits[0].anchor.as_deref(), Some("2-Rollback"));
+        // 大きすぎるエントリは入れない
+        assert_eq!(index.search("rollback", MAX_RESULTS).len(), 1);
+
+        // ZIPが書き換わったらエントリを入れ直す
+        write_zip(&[("notes.txt", b"fresh notes")]);
+        index.apply(&documents::read_changes(&temp_dir, &[temp_dir.join("release.zip")]));
+        assert!(index.search("backup", MAX_RESULTS).is_empty());
+        assert_eq!(index.search("fresh", MAX_RESULTS)[0].path, "release.zip::notes.txt");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_tokenize_cjk_bigrams() {
+        assert_eq!(tokenize("Hello, World"), vec!["hello", "world"]);
+        assert_eq!(tokenize("設定ファイル"), vec!["設定", "定フ", "ファ", "ァイ", "イル"]);
+        assert_eq!(tokenize("APIの設定"), vec!["api", "の設", "設定"]);
+        assert_eq!(tokenize("表 v2"), vec!["表", "v2"]);
+        assert_eq!(index_tokens("表示 v2"), vec!["表示", "表", "示", "v2"]);
+        assert_eq!(phrases("TOMLの設定ファイル"), vec!["toml", "の設定ファイル"]);
+        assert_eq!(phrases("設定\nファイル\nfoo\nbar"), vec!["設定ファイル", "foo", "bar"]);
+    }
+
+    #[test]
+    fn test_search_japanese_inside_sentence() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_search_cjk");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(
+            temp_dir.join("guide.md"),
+            "# 導入\n\n## 準備\n\nまずはTOMLの設定ファイルを\nホームディレクトリに置きます。\n",
+        )
+        .unwrap();
+        // 「設定」と「ファイル」が離れているだけの文書は一致させない
+        std::fs::write(temp_dir.join("other.md"), "設定を変えたらファイルを保存する。\n").unwrap();
+
+        let index = SearchIndex::new();
+        index.replace(&documents::collect(&temp_dir));
+
+        let hits = index.search("設定ファイル", MAX_RESULTS);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "guide.md");
+        assert_eq!(hits[0].heading.as_deref(), Some("準備"));
+        assert_eq!(hits[0].snippet, "まずはTOMLの<mark>設定ファイル</mark>を");
+
+        // 行をまたいだ並びや英数字との組み合わせ
+        assert_eq!(index.search("ファイルをホーム", MAX_RESULTS).len(), 1);
+        assert_eq!(index.search("toml 設定", MAX_RESULTS).len(), 1);
+        assert_eq!(index.search("ファイル", MAX_RESULTS).len(), 2);
+
+        // 1文字だけの検索語も文中から見つかる
+        std::fs::write(temp_dir.join("view.md"), "画面に表示する。\n").unwrap();
+        index.apply(&documents::read_changes(&temp_dir, &[temp_dir.join("view.md")]));
+        let hits = index.search("表", MAX_RESULTS);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "view.md");
+        assert_eq!(hits[0].snippet, "画面に<mark>表</mark>示する。");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+
+    #[test]
+    fn test_snippet_is_cut_around_match() {
+        let line = format!("{} needle {}", "a ".repeat(200), "b ".repeat(200));
+        let (number, html) = snippet(&line, &["needle".to_string()]);
+        assert_eq!(number, Some(0));
+        assert!(html.starts_with('…') && html.ends_with('…'));
+        assert!(html.contains("<mark>needle</mark>"));
+    }
+}
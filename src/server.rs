@@ -1,7 +1,11 @@
-use axum::{extract::Request, middleware::{self, Next}, response::Response, routing::get, Router};
+use axum::{extract::Request, middleware::{self, Next}, response::Response, routing::{get, post}, Router};
 use std::net::SocketAddr;
 
-use crate::handler::{handle_path, handle_reload_events, handle_reload_js, handle_root, AppState};
+use crate::handler::{
+    handle_download, handle_file_search, handle_listing, handle_listing_js, handle_palette_js, handle_path, handle_permalink_js,
+    handle_position, handle_position_js, handle_reader, handle_reader_root, handle_reload_events, handle_reload_js, handle_root,
+    handle_search, handle_tag, handle_tags, handle_thumbnail, handle_zip_password, AppState,
+};
 
 async fn logging_middleware(request: Request, next: Next) -> Response {
     let method = request.method().clone();
@@ -23,6 +27,21 @@ pub async fn start(state: AppState, host: &str, port: u16) -> Result<(), std::io
         .route("/", get(handle_root))
         .route("/__reload__", get(handle_reload_events))
         .route("/__reload__.js", get(handle_reload_js))
+        .route("/__position__", post(handle_position))
+        .route("/__position__.js", get(handle_position_js))
+        .route("/__zip_password__", post(handle_zip_password))
+        .route("/__download__", get(handle_download))
+        .route("/__files__", get(handle_file_search))
+        .route("/__search__", get(handle_search))
+        .route("/__tags__", get(handle_tags))
+        .route("/__tags__/{*tag}", get(handle_tag))
+        .route("/__palette__.js", get(handle_palette_js))
+        .route("/__permalink__.js", get(handle_permalink_js))
+        .route("/__list__", get(handle_listing))
+        .route("/__listing__.js", get(handle_listing_js))
+        .route("/__thumb__/{*path}", get(handle_thumbnail))
+        .route("/__reader__", get(handle_reader_root))
+        .route("/__reader__/{*path}", get(handle_reader))
         .route("/{*path}", get(handle_path))
         .layer(middleware::from_fn(logging_middleware))
         .with_state(state);
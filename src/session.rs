@@ -0,0 +1,151 @@
+use axum::http::{header, HeaderMap};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const SESSION_COOKIE: &str = "mvu_session";
+
+/// Cookieヘッダーから値を取り出す
+pub fn cookie_value(headers: &HeaderMap, cookie_name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+pub fn session_id(headers: &HeaderMap) -> Option<String> {
+    cookie_value(headers, SESSION_COOKIE)
+}
+
+pub fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("Failed to generate session id");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn session_cookie(id: &str) -> String {
+    format!("{}={}; Path=/; HttpOnly; SameSite=Lax", SESSION_COOKIE, id)
+}
+
+/// 保持しておくパスワードの数（超えたら最後に使ってから一番経ったものを捨てる）
+const MAX_STORED_PASSWORDS: usize = 1024;
+
+struct StoredPassword {
+    password: String,
+    // 最後に使った順番（大きいほど新しい）
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct StoreInner {
+    entries: HashMap<(String, PathBuf), StoredPassword>,
+    clock: u64,
+}
+
+/// 暗号化ZIPのパスワードをセッション・アーカイブごとにサーバー側で保持する
+/// Cookieが新しくなるたびに増えていかないよう、数に上限を付ける
+pub struct PasswordStore {
+    inner: Mutex<StoreInner>,
+    capacity: usize,
+}
+
+impl Default for PasswordStore {
+    fn default() -> Self {
+        Self::with_capacity(MAX_STORED_PASSWORDS)
+    }
+}
+
+impl PasswordStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(StoreInner::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn get(&self, session: &str, archive: &Path) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+        let stored = inner.entries.get_mut(&(session.to_string(), archive.to_path_buf()))?;
+        stored.last_used = clock;
+        Some(stored.password.clone())
+    }
+
+    pub fn insert(&self, session: &str, archive: &Path, password: String) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+        inner
+            .entries
+            .insert((session.to_string(), archive.to_path_buf()), StoredPassword { password, last_used: clock });
+        while inner.entries.len() > self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, stored)| stored.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => inner.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_from_cookie() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(session_id(&headers), None);
+
+        headers.insert(header::COOKIE, "theme=dark; mvu_session=abc123".parse().unwrap());
+        assert_eq!(session_id(&headers), Some("abc123".to_string()));
+    }
+
+    #[test]
+    fn test_new_session_id_is_random() {
+        let a = new_session_id();
+        let b = new_session_id();
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_password_store_is_per_session() {
+        let store = PasswordStore::new();
+        let archive = Path::new("/tmp/a.zip");
+        store.insert("s1", archive, "pw".to_string());
+
+        assert_eq!(store.get("s1", archive), Some("pw".to_string()));
+        assert_eq!(store.get("s2", archive), None);
+        assert_eq!(store.get("s1", Path::new("/tmp/b.zip")), None);
+    }
+
+    #[test]
+    fn test_password_store_evicts_least_recently_used() {
+        let store = PasswordStore::with_capacity(2);
+        let archive = Path::new("/tmp/a.zip");
+        store.insert("s1", archive, "one".to_string());
+        store.insert("s2", archive, "two".to_string());
+        // s1 を使ったので、次に追加すると s2 が捨てられる
+        assert_eq!(store.get("s1", archive), Some("one".to_string()));
+        store.insert("s3", archive, "three".to_string());
+
+        assert_eq!(store.get("s1", archive), Some("one".to_string()));
+        assert_eq!(store.get("s2", archive), None);
+        assert_eq!(store.get("s3", archive), Some("three".to_string()));
+    }
+}
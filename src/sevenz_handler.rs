@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sevenz_rust2::{Archive, ArchiveReader, Password};
+
+use crate::zip_handler::ZipEntry;
+
+/// 7zアーカイブのヘッダー情報とエントリ一覧
+/// ヘッダーの解析結果を保持しておき、ファイル読み出し時に再解析しない
+pub struct SevenZIndex {
+    pub entries: Vec<ZipEntry>,
+    archive: Archive,
+    // 正規化したパス -> アーカイブ内の元の名前
+    names: HashMap<String, String>,
+}
+
+pub fn is_sevenz_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("7z"))
+        .unwrap_or(false)
+}
+
+pub fn build_index(sevenz_path: &Path) -> Result<SevenZIndex, String> {
+    let archive = Archive::open(sevenz_path)
+        .map_err(|e| format!("Failed to read 7z archive: {}", e))?;
+
+    let mut entries = Vec::new();
+    let mut names = HashMap::new();
+
+    for file in &archive.files {
+        if file.is_anti_item {
+            continue;
+        }
+
+        // Windowsで作られたアーカイブは区切りが `\` のことがある
+        let name = file.name().replace('\\', "/");
+        let name = name.trim_end_matches('/');
+        if name.is_empty() {
+            continue;
+        }
+
+        let modified = file
+            .has_last_modified_date
+            .then(|| SystemTime::from(file.last_modified_date))
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+
+        if file.is_directory() {
+            entries.push(ZipEntry {
+                name: format!("{}/", name),
+                is_dir: true,
+                modified,
+                ..Default::default()
+            });
+        } else {
+            names.insert(name.to_string(), file.name().to_string());
+            entries.push(ZipEntry {
+                name: name.to_string(),
+                is_dir: false,
+                size: file.size,
+                // ソリッドアーカイブではエントリ単位の圧縮サイズは0になる
+                compressed_size: Some(file.compressed_size).filter(|&s| s > 0),
+                modified,
+                crc32: file.has_crc.then_some(file.crc as u32),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(SevenZIndex { entries, archive, names })
+}
+
+pub fn read_file_from_sevenz(sevenz_path: &Path, index: &SevenZIndex, file_path: &str) -> Result<Vec<u8>, String> {
+    let name = index
+        .names
+        .get(file_path)
+        .ok_or_else(|| format!("File not found in 7z: {}", file_path))?;
+
+    let file = File::open(sevenz_path)
+        .map_err(|e| format!("Failed to open 7z file: {}", e))?;
+
+    let mut reader = ArchiveReader::from_archive(index.archive.clone(), file, Password::empty());
+    reader
+        .read_file(name)
+        .map_err(|e| format!("Failed to read file from 7z: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod};
+    use std::fs;
+
+    #[test]
+    fn test_is_sevenz_file() {
+        assert!(is_sevenz_file(Path::new("scan.7z")));
+        assert!(is_sevenz_file(Path::new("scan.7Z")));
+        assert!(!is_sevenz_file(Path::new("scan.zip")));
+        assert!(!is_sevenz_file(Path::new("7z")));
+    }
+
+    #[test]
+    fn test_list_and_read_sevenz() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_sevenz_handler");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("test.7z");
+
+        let mut writer = ArchiveWriter::create(&path).unwrap();
+        writer.push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("pages"), None).unwrap();
+        writer.push_archive_entry(ArchiveEntry::new_file("pages/001.txt"), Some(&b"first page"[..])).unwrap();
+        writer.push_archive_entry(ArchiveEntry::new_file("readme.md"), Some(&b"# Scan"[..])).unwrap();
+        writer.finish().unwrap();
+
+        let index = build_index(&path).unwrap();
+        let names: Vec<_> = index.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["pages/", "pages/001.txt", "readme.md"]);
+
+        assert_eq!(read_file_from_sevenz(&path, &index, "pages/001.txt").unwrap(), b"first page");
+        assert_eq!(read_file_from_sevenz(&path, &index, "readme.md").unwrap(), b"# Scan");
+        assert!(read_file_from_sevenz(&path, &index, "missing.txt").is_err());
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+    #[test]
+    fn test_read_deflate_sevenz() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_sevenz_deflate");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("deflate.7z");
+
+        // 7-Zip で `-m0=Deflate` を指定して作ったものと同じ形式
+        let mut writer = ArchiveWriter::create(&path).unwrap();
+        writer.set_content_methods(vec![EncoderMethod::DEFLATE.into()]);
+        let text = "deflate ".repeat(100);
+        writer.push_archive_entry(ArchiveEntry::new_file("notes.txt"), Some(text.as_bytes())).unwrap();
+        writer.finish().unwrap();
+
+        let index = build_index(&path).unwrap();
+        assert_eq!(index.archive.blocks[0].coders[0].encoder_method_id(), EncoderMethod::ID_DEFLATE);
+        assert_eq!(read_file_from_sevenz(&path, &index, "notes.txt").unwrap(), text.as_bytes());
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+}
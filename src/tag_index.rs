@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::documents::{Changes, Document};
+use crate::file_index::is_under;
+use crate::natural_sort::NameOrder;
+use crate::obsidian;
+use crate::reader;
+
+/// タグの付いた文書
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedDocument {
+    pub path: String,
+    pub title: String,
+    pub date: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// タグのページの並び順
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TagSort {
+    // 新しい順（日付のないものは最後）
+    #[default]
+    Date,
+    Title,
+}
+
+impl TagSort {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "date" => Some(Self::Date),
+            "title" => Some(Self::Title),
+            _ => None,
+        }
+    }
+}
+
+/// front matter の `tags` と本文中の `#tag` から作るタグの索引（`/__tags__` 用）
+#[derive(Default)]
+pub struct TagIndex {
+    // 相対パス → タグのある文書
+    documents: RwLock<HashMap<String, TaggedDocument>>,
+    ready: AtomicBool,
+}
+
+/// タグのページのURL
+pub fn tag_url(tag: &str) -> String {
+    format!("/__tags__/{}", reader::encode_url(tag))
+}
+
+impl TagIndex {
+    pub fn new() -> Self {
+        Self {
+            documents: RwLock::new(HashMap::new()),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// ツリー全体の文書で作り直す（起動時にバックグラウンドで呼ぶ）
+    pub fn replace(&self, documents: &[Document]) {
+        let documents = documents.iter().filter_map(tagged).map(|d| (d.path.clone(), d)).collect();
+        *self.documents.write().unwrap() = documents;
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.read().unwrap().len()
+    }
+
+    /// watcher で変更があったパスを反映する
+    pub fn apply(&self, changes: &Changes) {
+        if changes.removed.is_empty() {
+            return;
+        }
+        let fresh: Vec<TaggedDocument> = changes.documents.iter().filter_map(tagged).collect();
+
+        let mut documents = self.documents.write().unwrap();
+        documents.retain(|path, _| !changes.removed.iter().any(|relative| is_under(path, relative)));
+        documents.extend(fresh.into_iter().map(|d| (d.path.clone(), d)));
+    }
+
+    /// すべてのタグと文書数（多い順、同じ数なら名前順）
+    pub fn tags(&self) -> Vec<(String, usize)> {
+        let documents = self.documents.read().unwrap();
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for document in documents.values() {
+            for tag in &document.tags {
+                *counts.entry(tag.as_str()).or_insert(0) += 1;
+            }
+        }
+        let mut tags: Vec<(String, usize)> = counts.into_iter().map(|(tag, count)| (tag.to_string(), count)).collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        tags
+    }
+
+    /// タグの付いた文書
+    pub fn documents(&self, tag: &str, sort: TagSort, order: NameOrder) -> Vec<TaggedDocument> {
+        let documents = self.documents.read().unwrap();
+        let mut tagged: Vec<TaggedDocument> = documents
+            .values()
+            .filter(|document| document.tags.iter().any(|t| t == tag))
+            .cloned()
+            .collect();
+        tagged.sort_by(|a, b| {
+            let by_title = order.cmp(&a.title, &b.title).then_with(|| a.path.cmp(&b.path));
+            match sort {
+                TagSort::Date => match (&a.date, &b.date) {
+                    (Some(x), Some(y)) => y.cmp(x).then(by_title),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => by_title,
+                },
+                TagSort::Title => by_title,
+            }
+        });
+        tagged
+    }
+}
+
+/// タグのあるMarkdown（タグがなければ None）
+fn tagged(document: &Document) -> Option<TaggedDocument> {
+    let markdown = document.tree_markdown()?;
+    // front matter の tags と本文中の `#tag`
+    let mut tags = markdown.front_matter.tags.clone();
+    tags.extend(obsidian::inline_tag_names(&document.text));
+    if tags.is_empty() {
+        return None;
+    }
+    tags.sort();
+    tags.dedup();
+
+    Some(TaggedDocument {
+        path: document.relative.clone(),
+        title: markdown.title.clone(),
+        date: markdown.front_matter.date.clone(),
+        tags,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents;
+
+    #[test]
+    fn test_tags_and_documents() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_tag_index");
+        std::fs::remove_dir_all(&temp_dir).ok();
+        std::fs::create_dir_all(temp_dir.join("notes")).unwrap();
+        let temp_dir = temp_dir.canonicalize().unwrap();
+        std::fs::write(temp_dir.join("notes/a.md"), "---\ntitle: Beta\ntags: [rust, web]\ndate: 2024-01-01\n---\n").unwrap();
+        std::fs::write(temp_dir.join("notes/b.md"), "---\ntags: [rust]\ndate: 2024-06-01\n---\n# Alpha\n").unwrap();
+        std::fs::write(temp_dir.join("notes/c.md"), "---\ntags: rust\n---\n# Aardvark\n").unwrap();
+        std::fs::write(temp_dir.join("plain.md"), "# No tags\n").unwrap();
+        std::fs::write(temp_dir.join("inline.md"), "# Inline\n\nText #web here.\n").unwrap();
+
+        let index = TagIndex::new();
+        index.replace(&documents::collect(&temp_dir));
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.tags(), vec![("rust".to_string(), 3), ("web".to_string(), 2)]);
+
+        let titles = |sort| -> Vec<String> {
+            index.documents("rust", sort, NameOrder::default()).into_iter().map(|d| d.title).collect()
+        };
+        assert_eq!(titles(TagSort::Date), vec!["Alpha", "Beta", "Aardvark"]);
+        assert_eq!(titles(TagSort::Title), vec!["Aardvark", "Alpha", "Beta"]);
+        assert_eq!(index.documents("web", TagSort::Title, NameOrder::default())[0].path, "notes/a.md");
+
+        // タグの変更・削除を反映する
+        std::fs::write(temp_dir.join("notes/a.md"), "---\ntitle: Beta\ntags: [go]\n---\n").unwrap();
+        std::fs::remove_file(temp_dir.join("notes/c.md")).unwrap();
+        index.apply(&documents::read_changes(&temp_dir, &[temp_dir.join("notes/a.md"), temp_dir.join("notes/c.md")]));
+        assert_eq!(index.tags(), vec![("go".to_string(), 1), ("rust".to_string(), 1), ("web".to_string(), 1)]);
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use tar::Archive;
+
+use crate::zip_handler::ZipEntry;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TarCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// tarアーカイブ内のエントリ一覧と、各ファイルのデータ位置（展開後ストリーム上のオフセット）
+pub struct TarIndex {
+    pub entries: Vec<ZipEntry>,
+    offsets: HashMap<String, (u64, u64)>,
+}
+
+pub fn tar_compression(path: &Path) -> Option<TarCompression> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+
+    if name.ends_with(".tar") {
+        Some(TarCompression::None)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(TarCompression::Gzip)
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        Some(TarCompression::Zstd)
+    } else {
+        None
+    }
+}
+
+fn open_stream(tar_path: &Path, compression: TarCompression) -> Result<Box<dyn Read>, String> {
+    let file = File::open(tar_path)
+        .map_err(|e| format!("Failed to open tar file: {}", e))?;
+    let reader = BufReader::new(file);
+
+    Ok(match compression {
+        TarCompression::None => Box::new(reader),
+        TarCompression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        TarCompression::Zstd => Box::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .map_err(|e| format!("Failed to read zstd stream: {}", e))?,
+        ),
+    })
+}
+
+/// ストリームを先頭から一度だけ走査してインデックスを作る
+pub fn build_index(tar_path: &Path, compression: TarCompression) -> Result<TarIndex, String> {
+    let mut archive = Archive::new(open_stream(tar_path, compression)?);
+
+    let mut entries = Vec::new();
+    let mut offsets = HashMap::new();
+
+    let iter = archive.entries()
+        .map_err(|e| format!("Failed to read tar archive: {}", e))?;
+
+    for entry in iter {
+        let entry = entry.map_err(|e| format!("Failed to read tar entry: {}", e))?;
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let modified = header.mtime().ok().map(|t| t as i64);
+
+        let raw_name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let name = raw_name.trim_start_matches("./").trim_end_matches('/');
+        if name.is_empty() {
+            continue;
+        }
+
+        if entry_type.is_dir() {
+            entries.push(ZipEntry {
+                name: format!("{}/", name),
+                is_dir: true,
+                modified,
+                ..Default::default()
+            });
+        } else if entry_type.is_file() {
+            let size = entry.size();
+            offsets.insert(name.to_string(), (entry.raw_file_position(), size));
+            entries.push(ZipEntry {
+                name: name.to_string(),
+                is_dir: false,
+                size,
+                modified,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(TarIndex { entries, offsets })
+}
+
+/// 読み込み前に確保しておく最大バイト数（それ以上は read_to_end で伸ばす）
+const PREALLOCATE_LIMIT: u64 = 8 * 1024 * 1024;
+
+pub fn read_file_from_tar(
+    tar_path: &Path,
+    compression: TarCompression,
+    index: &TarIndex,
+    file_path: &str,
+) -> Result<Vec<u8>, String> {
+    let &(offset, size) = index
+        .offsets
+        .get(file_path)
+        .ok_or_else(|| format!("File not found in tar: {}", file_path))?;
+
+    // サイズはヘッダーの値なので信用しすぎない（細工したアーカイブで巨大な確保をしないよう上限を付ける）
+    let mut contents = Vec::with_capacity(size.min(PREALLOCATE_LIMIT) as usize);
+
+    if compression == TarCompression::None {
+        // 非圧縮tarはシークで直接読める
+        let mut file = File::open(tar_path)
+            .map_err(|e| format!("Failed to open tar file: {}", e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| format!("Failed to seek tar file: {}", e))?;
+        file.take(size).read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read file from tar: {}", e))?;
+    } else {
+        // 圧縮tarは展開しながらオフセットまで読み飛ばす（ヘッダーの再解析は不要）
+        let mut stream = open_stream(tar_path, compression)?;
+        io::copy(&mut (&mut stream).take(offset), &mut io::sink())
+            .map_err(|e| format!("Failed to read file from tar: {}", e))?;
+        stream.take(size).read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read file from tar: {}", e))?;
+    }
+
+    if contents.len() as u64 != size {
+        return Err(format!("Truncated tar entry: {}", file_path));
+    }
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn build_tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "docs/", io::empty()).unwrap();
+
+        for (name, body) in [("docs/readme.md", "# Hello"), ("top.txt", "top level")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(body.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, body.as_bytes()).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_tar_compression() {
+        assert_eq!(tar_compression(Path::new("a.tar")), Some(TarCompression::None));
+        assert_eq!(tar_compression(Path::new("a.TAR.GZ")), Some(TarCompression::Gzip));
+        assert_eq!(tar_compression(Path::new("a.tgz")), Some(TarCompression::Gzip));
+        assert_eq!(tar_compression(Path::new("a.tar.zst")), Some(TarCompression::Zstd));
+        assert_eq!(tar_compression(Path::new("a.gz")), None);
+        assert_eq!(tar_compression(Path::new("a.zip")), None);
+    }
+
+    #[test]
+    fn test_read_plain_and_compressed_tar() {
+        let temp_dir = std::env::temp_dir().join("mvu_test_tar_handler");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let tar_bytes = build_tar();
+
+        let plain = temp_dir.join("test.tar");
+        fs::write(&plain, &tar_bytes).unwrap();
+
+        let gz = temp_dir.join("test.tar.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&tar_bytes).unwrap();
+        fs::write(&gz, encoder.finish().unwrap()).unwrap();
+
+        let zst = temp_dir.join("test.tar.zst");
+        fs::write(&zst, zstd::stream::encode_all(&tar_bytes[..], 0).unwrap()).unwrap();
+
+        for path in [&plain, &gz, &zst] {
+            let compression = tar_compression(path).unwrap();
+            let index = build_index(path, compression).unwrap();
+
+            let names: Vec<_> = index.entries.iter().map(|e| e.name.as_str()).collect();
+            assert_eq!(names, vec!["docs/", "docs/readme.md", "top.txt"]);
+            assert!(index.entries[0].is_dir);
+
+            let data = read_file_from_tar(path, compression, &index, "top.txt").unwrap();
+            assert_eq!(data, b"top level");
+            let data = read_file_from_tar(path, compression, &index, "docs/readme.md").unwrap();
+            assert_eq!(data, b"# Hello");
+            assert!(read_file_from_tar(path, compression, &index, "missing").is_err());
+        }
+
+        fs::remove_dir_all(&temp_dir).ok();
+    }
+}
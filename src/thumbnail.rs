@@ -0,0 +1,175 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// サムネイルの長辺の最大ピクセル数（一覧の80pxを高解像度ディスプレイでも綺麗に見せる）
+pub const THUMBNAIL_SIZE: u32 = 320;
+
+const JPEG_QUALITY: u8 = 80;
+
+/// サムネイルを生成できるラスター画像か（SVGは元ファイルをそのまま使う）
+pub fn is_thumbnailable(name: &str) -> bool {
+    let ext = name.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "gif" | "webp")
+}
+
+/// デコードする画像の上限（一覧に細工した画像があってもメモリを使い切らないように）
+const MAX_DECODE_DIMENSION: u32 = 16384;
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// 画像を縮小してJPEG（透過がある場合はPNG）にエンコードする
+pub fn generate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    reader.limits(limits);
+    let image = reader.decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+    let mut out = Vec::new();
+    if thumbnail.color().has_alpha() {
+        thumbnail
+            .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    } else {
+        let rgb = DynamicImage::ImageRgb8(thumbnail.to_rgb8());
+        rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))
+            .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    }
+
+    Ok(out)
+}
+
+pub fn content_type(thumbnail: &[u8]) -> &'static str {
+    if thumbnail.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// 生成したサムネイルをディスクにキャッシュする
+/// キーは元画像のパスと更新日時・サイズなので、画像が更新されれば作り直される
+pub struct ThumbnailCache {
+    dir: PathBuf,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// ETagとキャッシュファイル名に使うキー
+    /// ディスクに残るのでツールチェーンで値が変わらない SHA-256 を使う
+    pub fn cache_key(source: &str, modified: Option<SystemTime>, len: u64) -> String {
+        let modified = modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let mut hasher = Sha256::new();
+        hasher.update((source.len() as u64).to_le_bytes());
+        hasher.update(source.as_bytes());
+        hasher.update(modified.to_le_bytes());
+        hasher.update(len.to_le_bytes());
+        hasher.update(THUMBNAIL_SIZE.to_le_bytes());
+        hasher.finalize()[..16].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn cache_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.thumb", key))
+    }
+
+    pub fn get_or_create(&self, key: &str, load: impl FnOnce() -> Result<Vec<u8>, String>) -> Result<Vec<u8>, String> {
+        let path = self.cache_path(key);
+        if let Ok(data) = std::fs::read(&path) {
+            return Ok(data);
+        }
+
+        let thumbnail = generate(&load()?)?;
+
+        // キャッシュの書き込みに失敗してもサムネイル自体は返す
+        if std::fs::create_dir_all(&self.dir).is_ok() {
+            write_atomic(&path, &thumbnail).ok();
+        }
+
+        Ok(thumbnail)
+    }
+}
+
+/// 同じサムネイルを同時に書いても一時ファイルがぶつからないよう、書き込みごとに名前を変える
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let temp = path.with_extension(format!("tmp{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    std::fs::write(&temp, data)?;
+    std::fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, ImageBuffer, Rgb, Rgba};
+
+    fn encode(image: DynamicImage) -> Vec<u8> {
+        let mut out = Vec::new();
+        image.write_to(&mut Cursor::new(&mut out), ImageFormat::Png).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_is_thumbnailable() {
+        assert!(is_thumbnailable("photo.JPG"));
+        assert!(is_thumbnailable("a/b.webp"));
+        assert!(!is_thumbnailable("icon.svg"));
+        assert!(!is_thumbnailable("notes.md"));
+    }
+
+    #[test]
+    fn test_generate_shrinks_and_keeps_aspect() {
+        let source = encode(DynamicImage::ImageRgb8(ImageBuffer::from_pixel(1200, 600, Rgb([200, 10, 10]))));
+        let thumbnail = generate(&source).unwrap();
+        assert_eq!(content_type(&thumbnail), "image/jpeg");
+
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!(decoded.dimensions(), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+
+        let transparent = encode(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(10, 10, Rgba([0, 0, 0, 0]))));
+        assert_eq!(content_type(&generate(&transparent).unwrap()), "image/png");
+
+        assert!(generate(b"not an image").is_err());
+
+        // 上限を超える大きさの画像はデコードしない
+        let huge = encode(DynamicImage::ImageRgb8(ImageBuffer::from_pixel(MAX_DECODE_DIMENSION + 1, 1, Rgb([0, 0, 0]))));
+        assert!(generate(&huge).is_err());
+    }
+
+    #[test]
+    fn test_cache_reuses_generated_thumbnail() {
+        let dir = std::env::temp_dir().join("mvu_test_thumbnail_cache");
+        std::fs::remove_dir_all(&dir).ok();
+        let cache = ThumbnailCache::new(dir.clone());
+
+        let source = encode(DynamicImage::ImageRgb8(ImageBuffer::from_pixel(400, 400, Rgb([0, 0, 255]))));
+        let key = ThumbnailCache::cache_key("a.png", None, source.len() as u64);
+
+        let first = cache.get_or_create(&key, || Ok(source.clone())).unwrap();
+        let second = cache.get_or_create(&key, || panic!("should be cached")).unwrap();
+        assert_eq!(first, second);
+
+        let other_key = ThumbnailCache::cache_key("a.png", Some(SystemTime::now()), source.len() as u64);
+        assert_ne!(key, other_key);
+
+        // キーはビルドに依らず固定
+        assert_eq!(ThumbnailCache::cache_key("a.png", None, 100), "745a7fdf0b53fd976080e86d63a5b189");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
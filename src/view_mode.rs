@@ -0,0 +1,273 @@
+use crate::markdown::html_escape;
+
+/// 一覧の表示モード（リスト / サムネイルのグリッド / 画像中心のギャラリー）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    #[default]
+    List,
+    Grid,
+    Gallery,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 3] = [ViewMode::List, ViewMode::Grid, ViewMode::Gallery];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ViewMode::List => "list",
+            ViewMode::Grid => "grid",
+            ViewMode::Gallery => "gallery",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            ViewMode::List => "☰",
+            ViewMode::Grid => "▦",
+            ViewMode::Gallery => "🖼️",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ViewMode::List => "リスト",
+            ViewMode::Grid => "グリッド",
+            ViewMode::Gallery => "ギャラリー",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        ViewMode::ALL.into_iter().find(|mode| mode.as_str() == value)
+    }
+
+    /// 項目の過半数が画像ならギャラリー、それ以外はリスト
+    pub fn auto(image_count: usize, entry_count: usize) -> Self {
+        if image_count > 0 && image_count * 2 > entry_count {
+            ViewMode::Gallery
+        } else {
+            ViewMode::List
+        }
+    }
+}
+
+/// 表示モードの切り替えボタン
+pub fn switch_html() -> String {
+    let buttons: Vec<String> = ViewMode::ALL
+        .into_iter()
+        .map(|mode| {
+            format!(
+                "<button class=\"view-button\" data-view=\"{}\" title=\"{}\">{}</button>",
+                mode.as_str(),
+                mode.label(),
+                mode.icon()
+            )
+        })
+        .collect();
+    format!("<div class=\"view-switch\">{}</div>", buttons.concat())
+}
+
+/// 一覧の `<ul>` の開始タグ
+/// ディレクトリごとの切り替えはブラウザに保存し、描画前に反映する（`?view=` 指定時はそちらが優先）
+pub fn listing_open_html(key: &str, auto: ViewMode, forced: Option<ViewMode>) -> String {
+    let mode = forced.unwrap_or(auto);
+    format!(
+        r#"<ul id="listing" class="view-{mode}" data-key="{key}" data-auto="{auto}" data-forced="{forced}"><script>
+(function() {{
+    const listing = document.getElementById('listing');
+    const storageKey = 'mvu-view:' + listing.dataset.key;
+    const buttons = document.querySelectorAll('.view-button');
+
+    function apply(mode) {{
+        listing.className = 'view-' + mode;
+        buttons.forEach(b => b.classList.toggle('active', b.dataset.view === mode));
+    }}
+
+    const saved = localStorage.getItem(storageKey);
+    apply(listing.dataset.forced !== '1' && saved ? saved : '{mode}');
+
+    buttons.forEach(button => {{
+        button.addEventListener('click', function() {{
+            const mode = this.dataset.view;
+            // 自動で選ばれるモードに戻したら保存を消す
+            if (mode === listing.dataset.auto) {{
+                localStorage.removeItem(storageKey);
+            }} else {{
+                localStorage.setItem(storageKey, mode);
+            }}
+            apply(mode);
+        }});
+    }});
+}})();
+</script>"#,
+        mode = mode.as_str(),
+        key = html_escape(key),
+        auto = auto.as_str(),
+        forced = if forced.is_some() { "1" } else { "0" },
+    )
+}
+
+/// 一覧の操作部分とグリッド・ギャラリー表示のCSS（ファイルシステムとアーカイブの一覧で共通）
+pub const VIEW_MODE_CSS: &str = r#"<style>
+.list-controls {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 0.5rem;
+    margin-bottom: 0.75rem;
+}
+.listing-filter {
+    width: 12rem;
+    padding: 0.45rem 0.75rem;
+    border: 1px solid #e5e7eb;
+    border-radius: 8px;
+    font-size: 0.9rem;
+}
+.listing-filter:focus {
+    outline: none;
+    border-color: #667eea;
+    box-shadow: 0 0 0 3px rgba(102, 126, 234, 0.2);
+}
+#listing > li.filtered-out {
+    display: none;
+}
+#listing > li.selected > a {
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+}
+.list-controls .sort-bar {
+    flex: 1;
+    margin-bottom: 0;
+}
+.view-switch {
+    display: flex;
+    gap: 0.25rem;
+}
+.view-button {
+    width: 2.2rem;
+    height: 2.2rem;
+    border: none;
+    border-radius: 8px;
+    background: #f3f4f6;
+    color: #374151;
+    cursor: pointer;
+    font-size: 1rem;
+}
+.view-button.active {
+    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+    color: white;
+}
+ul.view-grid {
+    display: grid;
+    grid-template-columns: repeat(auto-fill, minmax(150px, 1fr));
+    gap: 0.75rem;
+}
+ul.view-grid > li {
+    position: relative;
+    display: block;
+    border: none;
+    border-radius: 12px;
+    background: #f9fafb;
+}
+ul.view-grid > li > a {
+    flex-direction: column;
+    justify-content: center;
+    height: 100%;
+    padding: 0.75rem;
+    text-align: center;
+}
+ul.view-grid > li > a:hover {
+    transform: translateY(-4px);
+}
+ul.view-grid .thumbnail {
+    width: 100%;
+    height: 130px;
+    margin: 0 0 0.5rem;
+}
+ul.view-grid .icon {
+    margin: 1.5rem 0 1rem;
+    font-size: 3rem;
+}
+ul.view-grid .name, ul.view-grid .image-item span {
+    font-size: 0.85rem;
+    overflow-wrap: anywhere;
+}
+ul.view-grid .select-box {
+    position: absolute;
+    top: 0.5rem;
+    left: 0.5rem;
+    z-index: 1;
+    margin: 0;
+}
+ul.view-gallery {
+    columns: 220px;
+    column-gap: 0.75rem;
+}
+ul.view-gallery > li {
+    position: relative;
+    display: block;
+    margin-bottom: 0.75rem;
+    border: none;
+    break-inside: avoid;
+}
+ul.view-gallery > li > a {
+    padding: 0.75rem 1rem;
+    border-radius: 10px;
+    background: #f9fafb;
+}
+ul.view-gallery > li.image-item > a {
+    flex-direction: column;
+    padding: 0;
+    background: none;
+}
+ul.view-gallery > li > a:hover {
+    transform: none;
+}
+ul.view-gallery .thumbnail {
+    width: 100%;
+    height: auto;
+    margin: 0;
+    object-fit: contain;
+    border-radius: 10px;
+}
+ul.view-gallery .image-item span {
+    padding: 0.25rem 0.5rem 0;
+    font-size: 0.8rem;
+    overflow-wrap: anywhere;
+}
+ul.view-gallery .select-box {
+    position: absolute;
+    top: 0.5rem;
+    left: 0.5rem;
+    z-index: 1;
+    margin: 0;
+}
+ul.view-grid .col, ul.view-grid .meta, ul.view-grid .comment,
+ul.view-gallery .col, ul.view-gallery .meta, ul.view-gallery .comment {
+    display: none;
+}
+</style>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_mode() {
+        assert_eq!(ViewMode::auto(0, 0), ViewMode::List);
+        assert_eq!(ViewMode::auto(2, 4), ViewMode::List);
+        assert_eq!(ViewMode::auto(3, 4), ViewMode::Gallery);
+        assert_eq!(ViewMode::parse("grid"), Some(ViewMode::Grid));
+        assert_eq!(ViewMode::parse("tiles"), None);
+    }
+
+    #[test]
+    fn test_listing_open_html() {
+        let html = listing_open_html("photos", ViewMode::Gallery, None);
+        assert!(html.starts_with("<ul id=\"listing\" class=\"view-gallery\" data-key=\"photos\" data-auto=\"gallery\" data-forced=\"0\">"));
+
+        let html = listing_open_html("a.zip::", ViewMode::Gallery, Some(ViewMode::List));
+        assert!(html.contains("class=\"view-list\""));
+        assert!(html.contains("data-forced=\"1\""));
+    }
+}
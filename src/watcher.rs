@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use tokio::sync::broadcast;
 
 /// 監視対象から除外すべきディレクトリやファイルパターンをチェック
-fn should_ignore_path(path: &Path) -> bool {
+pub fn should_ignore_path(path: &Path) -> bool {
     let path_str = path.to_string_lossy();
 
     // 除外すべきディレクトリ
@@ -53,18 +53,21 @@ fn should_ignore_path(path: &Path) -> bool {
     false
 }
 
+/// 変更・作成・削除されたパスの通知
+pub type ChangeReceiver = broadcast::Receiver<Vec<PathBuf>>;
+
 pub fn start_watcher(
     watch_path: PathBuf,
-) -> Result<(RecommendedWatcher, broadcast::Receiver<()>), Box<dyn std::error::Error>> {
+) -> Result<(RecommendedWatcher, ChangeReceiver), Box<dyn std::error::Error>> {
     let (tx, rx) = broadcast::channel(100);
 
     let mut watcher = RecommendedWatcher::new(
         move |res: Result<Event, notify::Error>| {
             if let Ok(event) = res {
-                // ファイルの変更イベントを検知
+                // ファイルの変更イベントを検知（削除は索引の更新に必要）
                 if matches!(
                     event.kind,
-                    notify::EventKind::Modify(_) | notify::EventKind::Create(_)
+                    notify::EventKind::Modify(_) | notify::EventKind::Create(_) | notify::EventKind::Remove(_)
                 ) {
                     // 無視すべきパスかチェック
                     let should_ignore = event.paths.iter().all(|path| should_ignore_path(path));
@@ -77,7 +80,7 @@ pub fn start_watcher(
 
                     // デバッグ用：有効な変更をログに出力
                     eprintln!("[RELOAD] Event detected: {:?}, paths: {:?}", event.kind, event.paths);
-                    let _ = tx.send(());
+                    let _ = tx.send(event.paths);
                 }
             }
         },
@@ -1,17 +1,34 @@
+use serde::Serialize;
 use std::io::{Read, Seek};
 use std::path::Path;
+use zip::result::ZipError;
 use zip::ZipArchive;
 
+use crate::natural_sort::NameOrder;
+
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct ZipEntry {
     pub name: String,
     pub is_dir: bool,
     pub size: u64,
+    pub compressed_size: Option<u64>,
+    // 圧縮方式 (Stored, Deflated など)
+    pub compression: Option<String>,
+    // 更新日時 (UNIX秒)
+    pub modified: Option<i64>,
+    pub crc32: Option<u32>,
+    pub comment: Option<String>,
+    // ZipCrypto / AES で暗号化されているか
+    pub encrypted: bool,
+    // Markdownの front matter のタイトル（ディレクトリ一覧・ZIP一覧でファイル名の代わりに出す、索引から引く）
+    pub title: Option<String>,
 }
 
+/// `.cbz`（コミックアーカイブ）も中身はZIP
 pub fn is_zip_file(path: &Path) -> bool {
     path.extension()
         .and_then(|s| s.to_str())
-        .map(|ext| ext.eq_ignore_ascii_case("zip"))
+        .map(|ext| ext.eq_ignore_ascii_case("zip") || ext.eq_ignore_ascii_case("cbz"))
         .unwrap_or(false)
 }
 
@@ -25,17 +42,28 @@ pub fn list_zip_contents(zip_path: &Path) -> Result<Vec<ZipEntry>, String> {
     let mut entries = Vec::new();
 
     for i in 0..archive.len() {
-        let file = archive.by_index(i)
+        // 暗号化エントリでもメタデータは読めるように展開せずに開く
+        let file = archive.by_index_raw(i)
             .map_err(|e| format!("Failed to read zip entry: {}", e))?;
 
-        let name = file.name().to_string();
-        let is_dir = file.is_dir();
-        let size = file.size();
+        // ZIPの日時はタイムゾーンを持たないのでUTCとして扱う
+        let modified = file
+            .last_modified()
+            .and_then(|dt| time::PrimitiveDateTime::try_from(dt).ok())
+            .map(|dt| dt.assume_utc().unix_timestamp());
+        let comment = Some(file.comment().to_string()).filter(|c| !c.is_empty());
 
         entries.push(ZipEntry {
-            name,
-            is_dir,
-            size,
+            name: file.name().to_string(),
+            is_dir: file.is_dir(),
+            size: file.size(),
+            compressed_size: Some(file.compressed_size()),
+            compression: Some(file.compression().to_string()),
+            modified,
+            crc32: Some(file.crc32()),
+            comment,
+            encrypted: file.encrypted(),
+            title: None,
         });
     }
 
@@ -45,10 +73,16 @@ pub fn list_zip_contents(zip_path: &Path) -> Result<Vec<ZipEntry>, String> {
 pub fn extract_file_from_zip<R: Read + Seek>(
     archive: &mut ZipArchive<R>,
     file_path: &str,
+    password: Option<&str>,
 ) -> Result<Vec<u8>, String> {
-    let mut file = archive
-        .by_name(file_path)
-        .map_err(|e| format!("File not found in zip: {}", e))?;
+    let file = match password {
+        Some(password) => archive.by_name_decrypt(file_path, password.as_bytes()),
+        None => archive.by_name(file_path),
+    };
+    let mut file = file.map_err(|e| match e {
+        ZipError::FileNotFound => format!("File not found in zip: {}", e),
+        _ => format!("Failed to open file in zip: {}", e),
+    })?;
 
     let mut contents = Vec::new();
     file.read_to_end(&mut contents)
@@ -57,17 +91,20 @@ pub fn extract_file_from_zip<R: Read + Seek>(
     Ok(contents)
 }
 
-pub fn read_file_from_zip(zip_path: &Path, file_path: &str) -> Result<Vec<u8>, String> {
+pub fn open_zip(zip_path: &Path) -> Result<ZipArchive<std::fs::File>, String> {
     let file = std::fs::File::open(zip_path)
         .map_err(|e| format!("Failed to open zip file: {}", e))?;
 
-    let mut archive = ZipArchive::new(file)
-        .map_err(|e| format!("Failed to read zip archive: {}", e))?;
+    ZipArchive::new(file)
+        .map_err(|e| format!("Failed to read zip archive: {}", e))
+}
 
-    extract_file_from_zip(&mut archive, file_path)
+pub fn read_file_from_zip(zip_path: &Path, file_path: &str, password: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut archive = open_zip(zip_path)?;
+    extract_file_from_zip(&mut archive, file_path, password)
 }
 
-pub fn get_directory_entries(entries: &[ZipEntry], dir_path: &str) -> Vec<ZipEntry> {
+pub fn get_directory_entries(entries: &[ZipEntry], dir_path: &str, order: NameOrder) -> Vec<ZipEntry> {
     let prefix = if dir_path.is_empty() {
         String::new()
     } else {
@@ -92,15 +129,11 @@ pub fn get_directory_entries(entries: &[ZipEntry], dir_path: &str) -> Vec<ZipEnt
                 result.push(ZipEntry {
                     name: format!("{}{}", prefix, dir_name),
                     is_dir: true,
-                    size: 0,
+                    ..Default::default()
                 });
             }
         } else if !relative.is_empty() {
-            result.push(ZipEntry {
-                name: entry.name.clone(),
-                is_dir: entry.is_dir,
-                size: entry.size,
-            });
+            result.push(entry.clone());
         }
     }
 
@@ -109,7 +142,7 @@ pub fn get_directory_entries(entries: &[ZipEntry], dir_path: &str) -> Vec<ZipEnt
         match (a.is_dir, b.is_dir) {
             (true, false) => std::cmp::Ordering::Less,
             (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
+            _ => order.cmp(&a.name, &b.name),
         }
     });
 
@@ -124,7 +157,44 @@ mod tests {
     fn test_is_zip_file() {
         assert!(is_zip_file(Path::new("test.zip")));
         assert!(is_zip_file(Path::new("test.ZIP")));
+        assert!(is_zip_file(Path::new("comic.cbz")));
         assert!(!is_zip_file(Path::new("test.txt")));
         assert!(!is_zip_file(Path::new("test")));
     }
+
+    #[test]
+    fn test_encrypted_zip() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let temp_dir = std::env::temp_dir().join("mvu_test_zip_encrypted");
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let zip_path = temp_dir.join("secret.zip");
+
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        writer.start_file("plain.txt", SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"public").unwrap();
+        let options = SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "hunter2");
+        writer.start_file("secret.txt", options).unwrap();
+        writer.write_all(b"classified").unwrap();
+        writer.finish().unwrap();
+
+        // 一覧は暗号化されていても取得できる
+        let entries = list_zip_contents(&zip_path).unwrap();
+        assert!(!entries[0].encrypted);
+        assert_eq!(entries[0].size, 6);
+        assert_eq!(entries[0].compression.as_deref(), Some("Deflated"));
+        let mut crc = flate2::Crc::new();
+        crc.update(b"public");
+        assert_eq!(entries[0].crc32, Some(crc.sum()));
+        assert!(entries[0].modified.is_some());
+        assert!(entries[1].encrypted);
+
+        assert_eq!(read_file_from_zip(&zip_path, "plain.txt", None).unwrap(), b"public");
+        assert!(read_file_from_zip(&zip_path, "secret.txt", None).is_err());
+        assert!(read_file_from_zip(&zip_path, "secret.txt", Some("wrong")).is_err());
+        assert_eq!(read_file_from_zip(&zip_path, "secret.txt", Some("hunter2")).unwrap(), b"classified");
+
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
 }